@@ -0,0 +1,63 @@
+//! Per-channel color write masks
+
+/// Mask of the channels of a color that are written to the framebuffer, similar to `glColorMask` in OpenGL.
+///
+/// Colors with fewer channels use the red, green and blue flags for their color channels in that order,
+/// and the alpha flag for their alpha channel, if they have one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ColorMask {
+    pub red: bool,
+    pub green: bool,
+    pub blue: bool,
+    pub alpha: bool,
+}
+
+impl Default for ColorMask {
+    fn default() -> ColorMask {
+        ColorMask::all()
+    }
+}
+
+impl ColorMask {
+    pub fn new(red: bool, green: bool, blue: bool, alpha: bool) -> ColorMask {
+        ColorMask { red, green, blue, alpha }
+    }
+
+    /// Write all channels, which is the default
+    pub fn all() -> ColorMask {
+        ColorMask::new(true, true, true, true)
+    }
+
+    /// Don't write any channels
+    pub fn none() -> ColorMask {
+        ColorMask::new(false, false, false, false)
+    }
+
+    /// Write only the color channels, preserving alpha
+    pub fn rgb() -> ColorMask {
+        ColorMask::new(true, true, true, false)
+    }
+
+    /// Write only the alpha channel, preserving the color channels
+    pub fn alpha_only() -> ColorMask {
+        ColorMask::new(false, false, false, true)
+    }
+
+    /// Checks if every channel is written
+    #[inline]
+    pub fn is_all(&self) -> bool {
+        self.red && self.green && self.blue && self.alpha
+    }
+
+    /// Flags for the color channels, in order
+    #[inline]
+    pub fn channels(&self) -> [bool; 3] {
+        [self.red, self.green, self.blue]
+    }
+
+    /// Returns `value` if the channel is enabled, otherwise `previous`
+    #[inline(always)]
+    pub fn select<T>(enabled: bool, value: T, previous: T) -> T {
+        if enabled { value } else { previous }
+    }
+}
@@ -7,8 +7,10 @@ use ::numeric::FloatScalar;
 
 pub mod blend;
 pub mod helper;
+pub mod mask;
 
 pub use self::helper::AlphaMultiply;
+pub use self::mask::ColorMask;
 
 pub trait ColorAlpha: ThreadSafeCopyable + Default {
     fn from_scalar<N: FloatScalar>(n: N) -> Self;
@@ -46,6 +48,17 @@ pub trait Color: ThreadSafeCopyable {
     fn mul_alpha(self, alpha: Self::Alpha) -> Self;
     /// Get the alpha of the color
     fn get_alpha(&self) -> Self::Alpha;
+    /// Take the channels enabled in the mask from this color, and the rest from `previous`.
+    ///
+    /// The default implementation can't tell the color channels apart, so it takes all of them from this color
+    /// if any are enabled, or all from `previous` if none are, and masks the alpha channel separately.
+    /// Masks that enable some but not all color channels should be handled by implementing this.
+    #[inline]
+    fn mask(self, previous: Self, mask: ColorMask) -> Self {
+        let alpha = ColorMask::select(mask.alpha, self.get_alpha(), previous.get_alpha());
+
+        ColorMask::select(mask.red || mask.green || mask.blue, self, previous).with_alpha(alpha)
+    }
 }
 
 impl Color for () {
@@ -55,9 +68,38 @@ impl Color for () {
     fn with_alpha(self, _: Self::Alpha) -> () { () }
     fn mul_alpha(self, _: Self::Alpha) -> () { () }
     fn get_alpha(&self) -> Self::Alpha { () }
+    fn mask(self, _: Self, _: ColorMask) -> () { () }
 }
 
 pub mod predefined;
 
 #[doc(hidden)]
 pub fn __assert_color<C: Color>() {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Color with only the default mask implementation
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Gray(f32, f32);
+
+    impl Color for Gray {
+        type Alpha = f32;
+
+        fn empty() -> Gray { Gray(0.0, 0.0) }
+        fn with_alpha(self, alpha: f32) -> Gray { Gray(self.0, alpha) }
+        fn mul_alpha(self, alpha: f32) -> Gray { Gray(self.0, self.1 * alpha) }
+        fn get_alpha(&self) -> f32 { self.1 }
+    }
+
+    #[test]
+    fn test_default_mask() {
+        let (color, previous) = (Gray(1.0, 0.5), Gray(0.0, 1.0));
+
+        assert_eq!(color.mask(previous, ColorMask::all()), color);
+        assert_eq!(color.mask(previous, ColorMask::none()), previous);
+        assert_eq!(color.mask(previous, ColorMask::rgb()), Gray(1.0, 1.0));
+        assert_eq!(color.mask(previous, ColorMask::alpha_only()), Gray(0.0, 0.5));
+    }
+}
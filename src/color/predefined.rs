@@ -7,7 +7,7 @@ use nalgebra::coordinates::XYZW;
 
 use ::behavior::ThreadSafeCopyable;
 
-use super::{Color, ColorAlpha};
+use super::{Color, ColorAlpha, ColorMask};
 use super::helper::AlphaMultiply;
 
 pub mod formats {
@@ -87,6 +87,17 @@ impl<T> Color for Vector4<T> where T: Scalar + Num + AlphaMultiply + ColorAlpha
 
     #[inline]
     fn get_alpha(&self) -> T { self.w }
+
+    #[inline]
+    fn mask(self, previous: Vector4<T>, mask: ColorMask) -> Vector4<T> {
+        let XYZW { x, y, z, w } = *self;
+        let XYZW { x: px, y: py, z: pz, w: pw } = *previous;
+
+        Vector4::new(ColorMask::select(mask.red, x, px),
+                     ColorMask::select(mask.green, y, py),
+                     ColorMask::select(mask.blue, z, pz),
+                     ColorMask::select(mask.alpha, w, pw))
+    }
 }
 
 macro_rules! impl_vector_color_without_alpha {
@@ -105,6 +116,17 @@ macro_rules! impl_vector_color_without_alpha {
 
             #[inline(always)]
             fn get_alpha(&self) -> () { () }
+
+            #[inline]
+            fn mask(self, previous: $name<T>, mask: ColorMask) -> $name<T> {
+                let mut color = previous;
+
+                for (i, &enabled) in mask.channels().iter().take(self.len()).enumerate() {
+                    if enabled { color[i] = self[i]; }
+                }
+
+                color
+            }
         }
     }
 }
@@ -12,7 +12,11 @@ pub type RenderResult<T> = TraceResult<T, RenderError>;
 #[derive(Debug)]
 pub enum RenderError {
     /// An invalid coordinate was used to access a pixel
-    InvalidPixelCoordinate
+    InvalidPixelCoordinate,
+    /// Pixelbuffers were given with dimensions that are invalid for their use
+    InvalidDimensions,
+    /// A multisampled framebuffer was requested with an unsupported number of samples
+    InvalidSampleCount,
 }
 
 impl Display for RenderError {
@@ -24,7 +28,9 @@ impl Display for RenderError {
 impl Error for RenderError {
     fn description(&self) -> &str {
         match *self {
-            RenderError::InvalidPixelCoordinate => "Invalid Pixel Coordinate"
+            RenderError::InvalidPixelCoordinate => "Invalid Pixel Coordinate",
+            RenderError::InvalidDimensions => "Invalid Dimensions",
+            RenderError::InvalidSampleCount => "Invalid Sample Count",
         }
     }
 }
@@ -39,4 +39,42 @@ macro_rules! impl_depth_primitives {
     }
 }
 
-impl_depth_primitives!(i8, i16, i32, i64, u8, u16, u32, u64, isize, usize, f32, f64);
\ No newline at end of file
+impl_depth_primitives!(i8, i16, i32, i64, u8, u16, u32, u64, isize, usize, f32, f64);
+
+/// Defines tests which can be performed on depth buffers
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum DepthTest {
+    /// Always pass
+    Always,
+    /// Never pass
+    Never,
+    /// Pass if the new value is less than the previous
+    LessThan,
+    /// Pass if the new value is greater than the previous
+    GreaterThan,
+    /// Pass if the new value is less than or equal to the previous
+    LessThanEq,
+    /// Pass if the new value is greater than or equal to the previous
+    GreaterThanEq,
+    /// Pass only if the new value is equal to the previous
+    Equal,
+    /// Pass only if the new value is NOT equal to the previous
+    NotEqual,
+}
+
+impl DepthTest {
+    /// Tests the new `reference` value against the previous `value`
+    #[inline]
+    pub fn test<T>(&self, value: T, reference: T) -> bool where T: PartialOrd {
+        match *self {
+            DepthTest::Always => true,
+            DepthTest::Never => false,
+            DepthTest::LessThan => reference < value,
+            DepthTest::LessThanEq => reference <= value,
+            DepthTest::GreaterThan => reference > value,
+            DepthTest::GreaterThanEq => reference >= value,
+            DepthTest::Equal => reference == value,
+            DepthTest::NotEqual => reference != value,
+        }
+    }
+}
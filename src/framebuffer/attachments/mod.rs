@@ -10,8 +10,8 @@ pub trait Attachment: ThreadSafeCopyable {}
 impl<T> Attachment for T where T: ThreadSafeCopyable {}
 
 pub use self::color::Color;
-pub use self::depth::Depth;
-pub use self::stencil::{Stencil, StencilOp, StencilTest, StencilConfig, GenericStencilConfig};
+pub use self::depth::{Depth, DepthTest};
+pub use self::stencil::{Stencil, StencilOp, StencilTest, StencilConfig, GenericStencilConfig, StencilState};
 
 /// Marker trait only defined for `()`, an empty tuple.
 pub trait EmptyAttachment {}
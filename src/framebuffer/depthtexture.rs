@@ -0,0 +1,52 @@
+//! Read-only texture view of a framebuffer depth attachment
+
+use nalgebra::{Vector1, Scalar};
+
+use ::color::Color;
+use ::geometry::{Dimensions, HasDimensions};
+use ::pixels::{PixelBuffer, PixelRead};
+
+use super::Framebuffer;
+use super::types::DepthAttachment;
+
+/// Structure containing a reference to a framebuffer, exposing its depth attachment as a single channel texture.
+///
+/// This allows a framebuffer rendered from the point of view of a light to be sampled as a shadow map without copying.
+pub struct DepthTextureRef<'a, F: Framebuffer + 'a> {
+    parent: &'a F,
+}
+
+impl<'a, F: Framebuffer + 'a> Clone for DepthTextureRef<'a, F> {
+    fn clone(&self) -> DepthTextureRef<'a, F> {
+        DepthTextureRef { ..*self }
+    }
+}
+
+impl<'a, F: Framebuffer + 'a> Copy for DepthTextureRef<'a, F> {}
+
+impl<'a, F: Framebuffer + 'a> DepthTextureRef<'a, F> {
+    #[inline]
+    pub ( in ::framebuffer) fn new(parent: &'a F) -> DepthTextureRef<'a, F> {
+        DepthTextureRef { parent }
+    }
+
+    /// Reference to the parent framebuffer
+    #[inline]
+    pub fn parent(&self) -> &'a F { self.parent }
+}
+
+impl<'a, F: Framebuffer + 'a> HasDimensions for DepthTextureRef<'a, F> {
+    #[inline]
+    fn dimensions(&self) -> Dimensions { self.parent.dimensions() }
+}
+
+impl<'a, F: Framebuffer + 'a> PixelBuffer for DepthTextureRef<'a, F> where DepthAttachment<F>: Scalar, Vector1<DepthAttachment<F>>: Color {
+    type Color = Vector1<DepthAttachment<F>>;
+}
+
+impl<'a, F: Framebuffer + 'a> PixelRead for DepthTextureRef<'a, F> where DepthAttachment<F>: Scalar, Vector1<DepthAttachment<F>>: Color {
+    #[inline]
+    unsafe fn get_pixel_unchecked(&self, index: usize) -> Self::Color {
+        Vector1::new(self.parent.get_depth_unchecked(index))
+    }
+}
@@ -4,14 +4,20 @@ pub mod accessor;
 pub mod nullbuffer;
 pub mod renderbuffer;
 pub mod texturebuffer;
+pub mod depthtexture;
+pub mod multisample;
+pub mod supersample;
 
 pub use self::attachments::Attachments;
 pub use self::renderbuffer::RenderBuffer;
+pub use self::depthtexture::DepthTextureRef;
+pub use self::multisample::MultisampleBuffer;
+pub use self::supersample::{SupersampleBuffer, DownsampleFilter};
 
 use ::error::{RenderResult, RenderError};
 
 use ::geometry::{Coordinate, HasDimensions};
-use ::pixels::PixelWrite;
+use ::pixels::{PixelBuffer, PixelRead, PixelWrite};
 
 use self::types::{ColorAttachment, DepthAttachment, StencilAttachment};
 use self::accessor::{FramebufferAccessor, FramebufferAccessorMut};
@@ -20,6 +26,15 @@ use self::accessor::{FramebufferAccessor, FramebufferAccessorMut};
 pub trait FramebufferBase: Sized + Clone + HasDimensions + PixelWrite + 'static {
     /// Associated type for the framebuffer attachments
     type Attachments: Attachments;
+
+    /// Number of samples stored for each pixel, which is one unless the framebuffer is multisampled.
+    #[inline(always)]
+    fn samples(&self) -> u32 { 1 }
+
+    /// Seed for jittering the sample position of each pixel, as given by `supersample::jitter_offset`,
+    /// or `None` if pixels are sampled at their centers.
+    #[inline(always)]
+    fn jitter(&self) -> Option<u32> { None }
 }
 
 /// Unsafe Framebuffer trait defining all unsafe methods for internal use
@@ -29,6 +44,39 @@ pub trait UnsafeFramebuffer: FramebufferBase {
 
     unsafe fn get_stencil_unchecked(&self, index: usize) -> StencilAttachment<Self>;
     unsafe fn set_stencil_unchecked(&mut self, index: usize, stencil: StencilAttachment<Self>);
+
+    /// Reads the color of a single sample of a pixel.
+    ///
+    /// Framebuffers with a single sample per pixel only need to implement the non-sample methods.
+    #[inline(always)]
+    unsafe fn get_sample_color_unchecked(&self, index: usize, _sample: u32) -> <Self as PixelBuffer>::Color {
+        self.get_pixel_unchecked(index)
+    }
+
+    #[inline(always)]
+    unsafe fn set_sample_color_unchecked(&mut self, index: usize, _sample: u32, color: <Self as PixelBuffer>::Color) {
+        self.set_pixel_unchecked(index, color)
+    }
+
+    #[inline(always)]
+    unsafe fn get_sample_depth_unchecked(&self, index: usize, _sample: u32) -> DepthAttachment<Self> {
+        self.get_depth_unchecked(index)
+    }
+
+    #[inline(always)]
+    unsafe fn set_sample_depth_unchecked(&mut self, index: usize, _sample: u32, depth: DepthAttachment<Self>) {
+        self.set_depth_unchecked(index, depth)
+    }
+
+    #[inline(always)]
+    unsafe fn get_sample_stencil_unchecked(&self, index: usize, _sample: u32) -> StencilAttachment<Self> {
+        self.get_stencil_unchecked(index)
+    }
+
+    #[inline(always)]
+    unsafe fn set_sample_stencil_unchecked(&mut self, index: usize, _sample: u32, stencil: StencilAttachment<Self>) {
+        self.set_stencil_unchecked(index, stencil)
+    }
 }
 
 /// Standard Framebuffer trait defining user-facing methods
@@ -36,6 +84,18 @@ pub trait Framebuffer: UnsafeFramebuffer {
     /// Clears the framebuffer with the given color, and sets any depth or stencil buffers back to their default values.
     fn clear(&mut self, color: ColorAttachment<Self>);
 
+    /// Sets every depth value of the framebuffer to the given value.
+    ///
+    /// `clear` resets depth to `Depth::far()`, the smallest value, which suits the default depth test.
+    /// Depth tests where smaller values are nearer, such as for reversed depth, need the largest value instead.
+    fn clear_depth(&mut self, depth: DepthAttachment<Self>) {
+        for index in 0..self.dimensions().area() {
+            for sample in 0..self.samples() {
+                unsafe { self.set_sample_depth_unchecked(index, sample, depth); }
+            }
+        }
+    }
+
     fn attachments(&self, coord: Coordinate) -> RenderResult<FramebufferAccessor<Self>> {
         let dim = self.dimensions();
 
@@ -55,4 +115,10 @@ pub trait Framebuffer: UnsafeFramebuffer {
             throw!(RenderError::InvalidPixelCoordinate);
         }
     }
+
+    /// Returns a read-only texture view of the depth attachment, such as for sampling shadow maps.
+    #[inline]
+    fn depth_texture(&self) -> DepthTextureRef<Self> {
+        DepthTextureRef::new(self)
+    }
 }
\ No newline at end of file
@@ -0,0 +1,316 @@
+//! Multisampled framebuffer for anti-aliased rendering
+
+use ::error::{RenderResult, RenderError};
+
+use ::geometry::{Dimensions, HasDimensions};
+use ::pixels::{PixelBuffer, PixelRead, PixelWrite};
+use ::interpolate::Interpolate;
+
+use super::{FramebufferBase, UnsafeFramebuffer, Framebuffer, Attachments, RenderBuffer};
+use super::renderbuffer::RenderBufferAttachments;
+use super::types::{ColorAttachment, DepthAttachment, StencilAttachment};
+
+/// Standard sample positions for each supported sample count, in sixteenths of a pixel from the pixel center,
+/// with the y-axis pointing down.
+///
+/// These match the standard multisample patterns used by Direct3D and most GPUs.
+pub const SAMPLE_POSITIONS: [&'static [(i8, i8)]; 5] = [
+    &[(0, 0)],
+    &[(4, 4), (-4, -4)],
+    &[(-2, -6), (6, -2), (-6, 2), (2, 6)],
+    &[(1, -3), (-1, 3), (5, 1), (-3, -5), (-5, 5), (-7, -1), (3, 7), (7, -7)],
+    &[(1, 1), (-1, -3), (-3, 2), (4, -1), (-5, -2), (2, 5), (5, 3), (3, -5),
+      (-2, 6), (0, -7), (-4, -6), (-6, 4), (-8, 0), (7, -4), (6, 7), (-7, -8)],
+];
+
+/// Returns the standard sample positions for the given number of samples,
+/// or `None` if it is not one of 1, 2, 4, 8 or 16.
+pub fn sample_positions(samples: u32) -> Option<&'static [(i8, i8)]> {
+    match samples {
+        1 => Some(SAMPLE_POSITIONS[0]),
+        2 => Some(SAMPLE_POSITIONS[1]),
+        4 => Some(SAMPLE_POSITIONS[2]),
+        8 => Some(SAMPLE_POSITIONS[3]),
+        16 => Some(SAMPLE_POSITIONS[4]),
+        _ => None,
+    }
+}
+
+/// Framebuffer storing several color, depth and stencil samples for each pixel.
+///
+/// When rendering into a multisampled framebuffer, triangle coverage and the depth and stencil tests are evaluated for every sample,
+/// but the fragment shader only runs once per pixel. Its color is written to all samples it covers.
+///
+/// Use `resolve` to average the samples into a normal `RenderBuffer`.
+///
+/// Reading or writing pixels directly accesses the first sample or all samples of each pixel, respectively.
+pub struct MultisampleBuffer<A: Attachments> {
+    dimensions: Dimensions,
+    samples: u32,
+    buffer: Vec<RenderBufferAttachments<A>>,
+}
+
+impl<A: Attachments> Clone for MultisampleBuffer<A> {
+    fn clone(&self) -> MultisampleBuffer<A> {
+        MultisampleBuffer {
+            buffer: self.buffer.clone(),
+            ..*self
+        }
+    }
+}
+
+impl<A: Attachments> MultisampleBuffer<A> {
+    /// Create a new `MultisampleBuffer` with the given number of pixels and samples per pixel allocated.
+    ///
+    /// Throws `RenderError::InvalidSampleCount` if the number of samples is not 1, 2, 4, 8 or 16.
+    pub fn with_dimensions(dimensions: Dimensions, samples: u32) -> RenderResult<MultisampleBuffer<A>> {
+        if sample_positions(samples).is_none() {
+            throw!(RenderError::InvalidSampleCount);
+        }
+
+        Ok(MultisampleBuffer {
+            dimensions,
+            samples,
+            buffer: vec![RenderBufferAttachments::default(); dimensions.area() * samples as usize]
+        })
+    }
+
+    /// Returns the standard positions of each sample within a pixel
+    #[inline]
+    pub fn sample_positions(&self) -> &'static [(i8, i8)] {
+        sample_positions(self.samples).unwrap()
+    }
+
+    #[inline(always)]
+    fn sample_index(&self, index: usize, sample: u32) -> usize {
+        index * self.samples as usize + sample as usize
+    }
+
+    #[inline(always)]
+    fn pixel_samples_mut(&mut self, index: usize) -> &mut [RenderBufferAttachments<A>] {
+        let start = index * self.samples as usize;
+
+        &mut self.buffer[start..start + self.samples as usize]
+    }
+
+    /// Resolves the framebuffer into a single-sampled `RenderBuffer` of the same dimensions,
+    /// averaging the color samples of each pixel. Depth and stencil values are taken from the first sample.
+    ///
+    /// Throws `RenderError::InvalidDimensions` if the dimensions of the framebuffers differ.
+    pub fn resolve(&self, target: &mut RenderBuffer<A>) -> RenderResult<()> where A::Color: Interpolate {
+        if self.dimensions != target.dimensions() {
+            throw!(RenderError::InvalidDimensions);
+        }
+
+        let weight = 1.0 / self.samples as f32;
+
+        for (index, samples) in self.buffer.chunks(self.samples as usize).enumerate() {
+            let color = Interpolate::weighted_sum(samples.iter().map(|sample| (weight, &sample.color)));
+
+            unsafe {
+                target.set_pixel_unchecked(index, color);
+                target.set_depth_unchecked(index, samples[0].depth);
+                target.set_stencil_unchecked(index, samples[0].stencil);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<A: Attachments> HasDimensions for MultisampleBuffer<A> {
+    #[inline]
+    fn dimensions(&self) -> Dimensions { self.dimensions }
+}
+
+impl<A: Attachments> PixelBuffer for MultisampleBuffer<A> {
+    type Color = <A as Attachments>::Color;
+}
+
+impl<A: Attachments> PixelRead for MultisampleBuffer<A> {
+    #[inline]
+    unsafe fn get_pixel_unchecked(&self, index: usize) -> Self::Color {
+        self.buffer.get_unchecked(self.sample_index(index, 0)).color
+    }
+}
+
+impl<A: Attachments> PixelWrite for MultisampleBuffer<A> {
+    #[inline]
+    unsafe fn set_pixel_unchecked(&mut self, index: usize, color: Self::Color) {
+        for sample in self.pixel_samples_mut(index) {
+            sample.color = color;
+        }
+    }
+}
+
+impl<A: Attachments> FramebufferBase for MultisampleBuffer<A> {
+    type Attachments = A;
+
+    #[inline(always)]
+    fn samples(&self) -> u32 { self.samples }
+}
+
+impl<A: Attachments> UnsafeFramebuffer for MultisampleBuffer<A> {
+    #[inline]
+    unsafe fn get_depth_unchecked(&self, index: usize) -> DepthAttachment<Self> {
+        self.buffer.get_unchecked(self.sample_index(index, 0)).depth
+    }
+
+    #[inline]
+    unsafe fn set_depth_unchecked(&mut self, index: usize, depth: DepthAttachment<Self>) {
+        for sample in self.pixel_samples_mut(index) {
+            sample.depth = depth;
+        }
+    }
+
+    #[inline]
+    unsafe fn get_stencil_unchecked(&self, index: usize) -> StencilAttachment<Self> {
+        self.buffer.get_unchecked(self.sample_index(index, 0)).stencil
+    }
+
+    #[inline]
+    unsafe fn set_stencil_unchecked(&mut self, index: usize, stencil: StencilAttachment<Self>) {
+        for sample in self.pixel_samples_mut(index) {
+            sample.stencil = stencil;
+        }
+    }
+
+    #[inline]
+    unsafe fn get_sample_color_unchecked(&self, index: usize, sample: u32) -> Self::Color {
+        self.buffer.get_unchecked(self.sample_index(index, sample)).color
+    }
+
+    #[inline]
+    unsafe fn set_sample_color_unchecked(&mut self, index: usize, sample: u32, color: Self::Color) {
+        let index = self.sample_index(index, sample);
+
+        self.buffer.get_unchecked_mut(index).color = color;
+    }
+
+    #[inline]
+    unsafe fn get_sample_depth_unchecked(&self, index: usize, sample: u32) -> DepthAttachment<Self> {
+        self.buffer.get_unchecked(self.sample_index(index, sample)).depth
+    }
+
+    #[inline]
+    unsafe fn set_sample_depth_unchecked(&mut self, index: usize, sample: u32, depth: DepthAttachment<Self>) {
+        let index = self.sample_index(index, sample);
+
+        self.buffer.get_unchecked_mut(index).depth = depth;
+    }
+
+    #[inline]
+    unsafe fn get_sample_stencil_unchecked(&self, index: usize, sample: u32) -> StencilAttachment<Self> {
+        self.buffer.get_unchecked(self.sample_index(index, sample)).stencil
+    }
+
+    #[inline]
+    unsafe fn set_sample_stencil_unchecked(&mut self, index: usize, sample: u32, stencil: StencilAttachment<Self>) {
+        let index = self.sample_index(index, sample);
+
+        self.buffer.get_unchecked_mut(index).stencil = stencil;
+    }
+}
+
+impl<A: Attachments> Framebuffer for MultisampleBuffer<A> {
+    fn clear(&mut self, color: ColorAttachment<Self>) {
+        for mut a in &mut self.buffer {
+            *a = RenderBufferAttachments {
+                color,
+                ..RenderBufferAttachments::default()
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use nalgebra::{Vector4, Point3};
+
+    use ::geometry::{Dimensions, Coordinate, ClipVertex, Viewport};
+    use ::framebuffer::{Framebuffer, UnsafeFramebuffer, RenderBuffer};
+    use ::attachments::predefined::{ColorAttachment, ColorDepthAttachments};
+    use ::pixels::PixelRead;
+    use ::primitive::Triangle;
+    use ::mesh::{Mesh, SimpleVertex};
+    use ::pipeline::{Pipeline, PipelineObject};
+    use ::pipeline::stages::fragment::Fragment;
+
+    use super::*;
+
+    type Attachments = ColorDepthAttachments<Vector4<f32>, f32>;
+
+    #[test]
+    fn test_invalid_samples() {
+        assert!(MultisampleBuffer::<Attachments>::with_dimensions(Dimensions::new(4, 4), 3).is_err());
+
+        let buffer = MultisampleBuffer::<Attachments>::with_dimensions(Dimensions::new(4, 4), 4).unwrap();
+
+        assert!(buffer.resolve(&mut RenderBuffer::with_dimensions(Dimensions::new(2, 2))).is_err());
+    }
+
+    #[test]
+    fn test_msaa_resolve() {
+        let dimensions = Dimensions::new(8, 8);
+
+        // Triangle covering the top-left half of the screen, with the diagonal passing through pixel centers
+        let vertices = vec![(-1.0, 1.0), (1.0, 1.0), (-1.0, -1.0)].into_iter().map(|(x, y)| SimpleVertex {
+            position: Point3::new(x, y, 0.0),
+            data: (),
+        }).collect();
+
+        let buffer = MultisampleBuffer::<Attachments>::with_dimensions(dimensions, 4).unwrap();
+
+        let mut pipeline: Pipeline<_, _> = Pipeline::from_framebuffer(buffer, ());
+
+        let invocations = AtomicUsize::new(0);
+
+        pipeline.render_mesh(Triangle, Arc::new(Mesh { vertices, indices: vec![0, 1, 2] }), None)
+            .run_to_fragment(Viewport::new(dimensions, Coordinate::new(0, 0), 0.0, 1.0), |vertex, _| {
+                ClipVertex::new(vertex.position.to_homogeneous(), ())
+            })
+            .run(|_, _| {
+                invocations.fetch_add(1, Ordering::Relaxed);
+
+                Fragment::Color(Vector4::new(1.0, 1.0, 1.0, 1.0))
+            });
+
+        // Shaded once for every pixel with at least one covered sample, where x + y <= 7
+        assert_eq!(invocations.load(Ordering::Relaxed), 36);
+
+        let mut resolved = RenderBuffer::with_dimensions(dimensions);
+
+        pipeline.framebuffer().resolve(&mut resolved).unwrap();
+
+        let coverage = |x, y| resolved.pixel_ref(Coordinate::new(x, y)).unwrap().get().x;
+
+        assert_eq!(coverage(0, 0), 1.0);
+        assert_eq!(coverage(7, 7), 0.0);
+
+        // Two of the four samples of pixels along the diagonal are inside the triangle
+        assert!((coverage(3, 4) - 0.5).abs() < 1e-6);
+        assert!((coverage(7, 0) - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_msaa_resolve_integer() {
+        let mut buffer = MultisampleBuffer::<ColorAttachment<Vector4<u8>>>::with_dimensions(Dimensions::new(1, 1), 16).unwrap();
+
+        // One sample of 1 and the rest 2, which averages to 1.9375
+        for sample in 0..16 {
+            let value = if sample == 0 { 1 } else { 2 };
+
+            unsafe { buffer.set_sample_color_unchecked(0, sample, Vector4::new(value, value, value, 255)); }
+        }
+
+        let mut resolved = RenderBuffer::with_dimensions(Dimensions::new(1, 1));
+
+        buffer.resolve(&mut resolved).unwrap();
+
+        // Rounded once, rather than truncated after every sample
+        assert_eq!(resolved.pixel_ref(Coordinate::new(0, 0)).unwrap().get(), Vector4::new(2, 2, 2, 255));
+    }
+}
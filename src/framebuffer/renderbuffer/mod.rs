@@ -13,10 +13,10 @@ pub use self::iterator::{RenderBufferIter, RenderBufferIterMut};
 
 /// Interlaced framebuffer for more cache-friendly access
 #[derive(Debug, Clone, Copy)]
-pub ( in ::framebuffer::renderbuffer) struct RenderBufferAttachments<A: Attachments> {
-    color: A::Color,
-    depth: A::Depth,
-    stencil: A::Stencil,
+pub ( in ::framebuffer) struct RenderBufferAttachments<A: Attachments> {
+    pub ( in ::framebuffer) color: A::Color,
+    pub ( in ::framebuffer) depth: A::Depth,
+    pub ( in ::framebuffer) stencil: A::Stencil,
 }
 
 impl<A: Attachments> Default for RenderBufferAttachments<A> {
@@ -0,0 +1,443 @@
+//! Supersampled framebuffer and downsampling filters
+
+use std::f64::consts::PI;
+
+use ::error::{RenderResult, RenderError};
+
+use ::geometry::{Dimensions, HasDimensions};
+use ::pixels::{PixelBuffer, PixelRead, PixelWrite};
+use ::interpolate::Interpolate;
+
+use super::{FramebufferBase, UnsafeFramebuffer, Framebuffer, Attachments, RenderBuffer};
+use super::types::{ColorAttachment, DepthAttachment, StencilAttachment};
+
+/// Returns the jittered offset of the sample position of a pixel from its center, in sixteenths of a pixel.
+///
+/// Offsets are pseudo-random but deterministic for a given seed, and always within half a pixel of the center.
+pub fn jitter_offset(seed: u32, x: u32, y: u32) -> (i8, i8) {
+    // Integer hash of the seed and pixel coordinate
+    let mut h = seed ^ x.wrapping_mul(0x8da6b343) ^ y.wrapping_mul(0xd8163841);
+
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x7feb352d);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x846ca68b);
+    h ^= h >> 16;
+
+    ((h & 15) as i8 - 8, ((h >> 4) & 15) as i8 - 8)
+}
+
+/// Reconstruction filters for downsampling a `SupersampleBuffer`.
+///
+/// All radii are given in pixels of the downsampled framebuffer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DownsampleFilter {
+    /// Unweighted average of the samples within each pixel
+    Box,
+    /// Samples weighted by their distance from the pixel center, with a radius of one pixel
+    Tent,
+    /// Windowed sinc filter with the given number of lobes, usually 2 or 3, which is the radius of the filter.
+    ///
+    /// This gives the sharpest results, but the negative lobes may cause slight ringing around hard edges.
+    Lanczos(u32),
+}
+
+impl Default for DownsampleFilter {
+    fn default() -> DownsampleFilter {
+        DownsampleFilter::Box
+    }
+}
+
+impl DownsampleFilter {
+    /// Radius of the filter
+    pub fn radius(&self) -> f64 {
+        match *self {
+            DownsampleFilter::Box => 0.5,
+            DownsampleFilter::Tent => 1.0,
+            DownsampleFilter::Lanczos(lobes) => lobes as f64,
+        }
+    }
+
+    /// One-dimensional weight of a sample at the given distance from the pixel center
+    pub fn weight(&self, distance: f64) -> f64 {
+        let sinc = |x: f64| if x == 0.0 { 1.0 } else { (PI * x).sin() / (PI * x) };
+
+        match *self {
+            // Half-open, so samples on the boundary between two pixels only count towards one of them
+            DownsampleFilter::Box => if -0.5 <= distance && distance < 0.5 { 1.0 } else { 0.0 },
+            DownsampleFilter::Tent => (1.0 - distance.abs()).max(0.0),
+            DownsampleFilter::Lanczos(lobes) => {
+                let lobes = lobes as f64;
+
+                if distance.abs() < lobes { sinc(distance) * sinc(distance / lobes) } else { 0.0 }
+            }
+        }
+    }
+}
+
+/// Framebuffer rendered at an integer multiple of the resolution of another framebuffer,
+/// which is then downsampled into it for anti-aliasing.
+///
+/// Unlike a `MultisampleBuffer`, every sample is a full pixel with its own fragment shader invocation,
+/// so shading is anti-aliased as well as the edges of primitives.
+///
+/// Optionally, the sample position of each pixel can be jittered within the pixel,
+/// which trades the regular aliasing patterns of near-horizontal and near-vertical edges for noise.
+pub struct SupersampleBuffer<A: Attachments> {
+    scale: u32,
+    jitter: Option<u32>,
+    buffer: RenderBuffer<A>,
+}
+
+impl<A: Attachments> Clone for SupersampleBuffer<A> {
+    fn clone(&self) -> SupersampleBuffer<A> {
+        SupersampleBuffer {
+            buffer: self.buffer.clone(),
+            ..*self
+        }
+    }
+}
+
+impl<A: Attachments> SupersampleBuffer<A> {
+    /// Create a new `SupersampleBuffer` for downsampling into a framebuffer of the given dimensions,
+    /// with `scale * scale` samples per pixel.
+    ///
+    /// Throws `RenderError::InvalidSampleCount` if the scale is zero.
+    pub fn with_dimensions(dimensions: Dimensions, scale: u32) -> RenderResult<SupersampleBuffer<A>> {
+        if scale == 0 {
+            throw!(RenderError::InvalidSampleCount);
+        }
+
+        Ok(SupersampleBuffer {
+            scale,
+            jitter: None,
+            buffer: RenderBuffer::with_dimensions(Dimensions::new(dimensions.width * scale, dimensions.height * scale)),
+        })
+    }
+
+    /// Create a new `SupersampleBuffer` for the given framebuffer, initialized with its attachments
+    /// so anything rendered into it is composited over and depth tested against the existing contents.
+    ///
+    /// Each pixel is copied into all of its samples, so downsampling with `DownsampleFilter::Box` and no jitter
+    /// gives back the original pixels wherever nothing was rendered. Any other filter, or jittered samples,
+    /// blend untouched pixels with their neighbors as well, which blurs the existing contents of the framebuffer.
+    ///
+    /// Throws `RenderError::InvalidSampleCount` if the scale is zero.
+    pub fn from_framebuffer<F>(framebuffer: &F, scale: u32) -> RenderResult<SupersampleBuffer<A>>
+        where F: Framebuffer<Attachments = A> + PixelBuffer<Color = A::Color> {
+        let dimensions = framebuffer.dimensions();
+
+        let mut buffer = SupersampleBuffer::with_dimensions(dimensions, scale)?;
+
+        let width = buffer.dimensions().width as usize;
+
+        for index in 0..buffer.dimensions().area() {
+            let source = (index % width) / scale as usize + (index / width) / scale as usize * dimensions.width as usize;
+
+            unsafe {
+                buffer.set_pixel_unchecked(index, framebuffer.get_pixel_unchecked(source));
+                buffer.set_depth_unchecked(index, framebuffer.get_depth_unchecked(source));
+                buffer.set_stencil_unchecked(index, framebuffer.get_stencil_unchecked(source));
+            }
+        }
+
+        Ok(buffer)
+    }
+
+    /// Number of samples along each axis of a downsampled pixel
+    #[inline]
+    pub fn scale(&self) -> u32 { self.scale }
+
+    /// Seed for the jittered sample positions, or `None` if samples are on a regular grid
+    #[inline]
+    pub fn jitter(&self) -> Option<u32> { self.jitter }
+
+    /// Jitter the sample position of each pixel with the given seed, or sample on a regular grid with `None`.
+    #[inline]
+    pub fn set_jitter(&mut self, jitter: Option<u32>) {
+        self.jitter = jitter;
+    }
+
+    /// Builder version of `set_jitter`
+    #[inline]
+    pub fn with_jitter(mut self, jitter: Option<u32>) -> SupersampleBuffer<A> {
+        self.set_jitter(jitter);
+        self
+    }
+
+    /// Downsamples the color attachment into the given framebuffer with the given filter,
+    /// leaving any other attachments of the target untouched.
+    ///
+    /// Samples are summed in floating-point, so integer colors are only rounded once,
+    /// and overshoot from the negative lobes of `DownsampleFilter::Lanczos` saturates to the range of the color type.
+    ///
+    /// Every pixel of the target is overwritten, including any that nothing was rendered to.
+    /// See `from_framebuffer` for how this affects existing contents with filters other than `DownsampleFilter::Box`.
+    ///
+    /// Throws `RenderError::InvalidDimensions` if the target is not the downsampled size of the framebuffer.
+    pub fn downsample<T>(&self, target: &mut T, filter: DownsampleFilter) -> RenderResult<()>
+        where T: PixelWrite<Color = A::Color>, A::Color: Interpolate {
+        let Dimensions { width, height } = self.buffer.dimensions();
+
+        if target.dimensions() != Dimensions::new(width / self.scale, height / self.scale) {
+            throw!(RenderError::InvalidDimensions);
+        }
+
+        let scale = self.scale as f64;
+
+        // Filter footprint in samples, plus one for jittered samples just outside of it
+        let reach = (filter.radius() * scale).ceil() as i64 + 1;
+
+        let mut weighted = Vec::new();
+        let mut samples = Vec::new();
+
+        for index in 0..target.dimensions().area() {
+            let x = (index % target.dimensions().width as usize) as f64;
+            let y = (index / target.dimensions().width as usize) as f64;
+
+            let (cx, cy) = ((x + 0.5) * scale, (y + 0.5) * scale);
+
+            weighted.clear();
+
+            for sy in (cy as i64 - reach)..(cy as i64 + reach + 1) {
+                if sy < 0 || sy >= height as i64 { continue; }
+
+                for sx in (cx as i64 - reach)..(cx as i64 + reach + 1) {
+                    if sx < 0 || sx >= width as i64 { continue; }
+
+                    let (jx, jy) = match self.jitter {
+                        Some(seed) => jitter_offset(seed, sx as u32, sy as u32),
+                        None => (0, 0),
+                    };
+
+                    let px = sx as f64 + 0.5 + jx as f64 / 16.0;
+                    let py = sy as f64 + 0.5 + jy as f64 / 16.0;
+
+                    let weight = filter.weight((px - cx) / scale) * filter.weight((py - cy) / scale);
+
+                    if weight != 0.0 {
+                        weighted.push((weight, sx as usize + sy as usize * width as usize));
+                    }
+                }
+            }
+
+            let total: f64 = weighted.iter().map(|&(weight, _)| weight).sum();
+
+            // The central lobe of every filter covers the pixel center, so this only happens without any samples
+            if total <= 0.0 {
+                continue;
+            }
+
+            // Weighted sum of the samples, normalized by the total weight of the whole footprint
+            let norm = 1.0 / total;
+
+            samples.clear();
+            samples.extend(weighted.iter().map(|&(weight, sample)| (weight * norm, unsafe { self.buffer.get_pixel_unchecked(sample) })));
+
+            let color = Interpolate::weighted_sum(samples.iter().map(|&(weight, ref color)| (weight, color)));
+
+            unsafe { target.set_pixel_unchecked(index, color); }
+        }
+
+        Ok(())
+    }
+}
+
+impl<A: Attachments> HasDimensions for SupersampleBuffer<A> {
+    #[inline]
+    fn dimensions(&self) -> Dimensions { self.buffer.dimensions() }
+}
+
+impl<A: Attachments> PixelBuffer for SupersampleBuffer<A> {
+    type Color = <A as Attachments>::Color;
+}
+
+impl<A: Attachments> PixelRead for SupersampleBuffer<A> {
+    #[inline]
+    unsafe fn get_pixel_unchecked(&self, index: usize) -> Self::Color {
+        self.buffer.get_pixel_unchecked(index)
+    }
+}
+
+impl<A: Attachments> PixelWrite for SupersampleBuffer<A> {
+    #[inline]
+    unsafe fn set_pixel_unchecked(&mut self, index: usize, color: Self::Color) {
+        self.buffer.set_pixel_unchecked(index, color)
+    }
+}
+
+impl<A: Attachments> FramebufferBase for SupersampleBuffer<A> {
+    type Attachments = A;
+
+    #[inline(always)]
+    fn jitter(&self) -> Option<u32> { self.jitter }
+}
+
+impl<A: Attachments> UnsafeFramebuffer for SupersampleBuffer<A> {
+    #[inline]
+    unsafe fn get_depth_unchecked(&self, index: usize) -> DepthAttachment<Self> {
+        self.buffer.get_depth_unchecked(index)
+    }
+
+    #[inline]
+    unsafe fn set_depth_unchecked(&mut self, index: usize, depth: DepthAttachment<Self>) {
+        self.buffer.set_depth_unchecked(index, depth)
+    }
+
+    #[inline]
+    unsafe fn get_stencil_unchecked(&self, index: usize) -> StencilAttachment<Self> {
+        self.buffer.get_stencil_unchecked(index)
+    }
+
+    #[inline]
+    unsafe fn set_stencil_unchecked(&mut self, index: usize, stencil: StencilAttachment<Self>) {
+        self.buffer.set_stencil_unchecked(index, stencil)
+    }
+}
+
+impl<A: Attachments> Framebuffer for SupersampleBuffer<A> {
+    fn clear(&mut self, color: ColorAttachment<Self>) {
+        self.buffer.clear(color)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use nalgebra::Vector4;
+
+    use ::geometry::{Dimensions, Coordinate};
+    use ::framebuffer::{Framebuffer, RenderBuffer};
+    use ::attachments::predefined::ColorDepthAttachments;
+    use ::pixels::{PixelRead, PixelWrite};
+
+    use super::*;
+
+    type Attachments = ColorDepthAttachments<Vector4<f32>, f32>;
+
+    #[test]
+    fn test_downsample_filters() {
+        let mut buffer = SupersampleBuffer::<Attachments>::with_dimensions(Dimensions::new(4, 4), 4).unwrap();
+
+        assert!(SupersampleBuffer::<Attachments>::with_dimensions(Dimensions::new(4, 4), 0).is_err());
+
+        // Left half of the first pixel is white, and the rest is black
+        for y in 0..4 {
+            for x in 0..2 {
+                buffer.pixel_mut(Coordinate::new(x, y)).unwrap().set(Vector4::new(1.0, 1.0, 1.0, 1.0));
+            }
+        }
+
+        let mut target = RenderBuffer::<Attachments>::with_dimensions(Dimensions::new(4, 4));
+
+        let value = |target: &RenderBuffer<Attachments>, x| target.pixel_ref(Coordinate::new(x, 0)).unwrap().get().x;
+
+        assert!(buffer.downsample(&mut RenderBuffer::<Attachments>::with_dimensions(Dimensions::new(2, 2)), DownsampleFilter::Box).is_err());
+
+        buffer.downsample(&mut target, DownsampleFilter::Box).unwrap();
+
+        assert!((value(&target, 0) - 0.5).abs() < 1e-6);
+        assert_eq!(value(&target, 1), 0.0);
+
+        // The tent filter also weighs in the black samples of the neighboring pixel
+        buffer.downsample(&mut target, DownsampleFilter::Tent).unwrap();
+
+        assert!(value(&target, 0) > 0.25 && value(&target, 0) < 0.5);
+
+        // The negative lobes of Lanczos overshoot next to the edge
+        buffer.downsample(&mut target, DownsampleFilter::Lanczos(2)).unwrap();
+
+        assert!(value(&target, 1) < 0.0);
+
+        // A constant image stays constant under every filter, despite the negative lobes of Lanczos
+        buffer.clear(Vector4::new(0.5, 0.5, 0.5, 1.0));
+
+        for &filter in &[DownsampleFilter::Box, DownsampleFilter::Tent, DownsampleFilter::Lanczos(2), DownsampleFilter::Lanczos(3)] {
+            buffer.set_jitter(Some(7));
+            buffer.downsample(&mut target, filter).unwrap();
+
+            for x in 0..4 {
+                assert!((value(&target, x) - 0.5).abs() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn test_downsample_integer() {
+        type IntegerAttachments = ColorDepthAttachments<Vector4<u8>, f32>;
+
+        let mut buffer = SupersampleBuffer::<IntegerAttachments>::with_dimensions(Dimensions::new(4, 4), 4).unwrap();
+
+        // Left half of the first pixel is white, and the rest is black
+        for y in 0..16 {
+            for x in 0..2 {
+                buffer.pixel_mut(Coordinate::new(x, y)).unwrap().set(Vector4::new(255, 255, 255, 255));
+            }
+        }
+
+        let mut target = RenderBuffer::<IntegerAttachments>::with_dimensions(Dimensions::new(4, 4));
+
+        let value = |target: &RenderBuffer<IntegerAttachments>, x| target.pixel_ref(Coordinate::new(x, 0)).unwrap().get().x;
+
+        // The undershoot next to the edge saturates instead of panicking
+        buffer.downsample(&mut target, DownsampleFilter::Lanczos(2)).unwrap();
+
+        assert_eq!(value(&target, 1), 0);
+
+        buffer.downsample(&mut target, DownsampleFilter::Tent).unwrap();
+
+        assert!(value(&target, 0) > 64 && value(&target, 0) < 128);
+
+        // Partial sums of a full-intensity image would exceed the range of `u8` before the negative lobes are added
+        buffer.clear(Vector4::new(255, 255, 255, 255));
+
+        for &filter in &[DownsampleFilter::Box, DownsampleFilter::Tent, DownsampleFilter::Lanczos(2), DownsampleFilter::Lanczos(3)] {
+            buffer.set_jitter(Some(7));
+            buffer.downsample(&mut target, filter).unwrap();
+
+            for x in 0..4 {
+                assert_eq!(value(&target, x), 255);
+            }
+        }
+    }
+
+    #[test]
+    fn test_downsample_untouched() {
+        let mut target = RenderBuffer::<Attachments>::with_dimensions(Dimensions::new(4, 4));
+
+        for y in 0..4 {
+            for x in 0..4 {
+                let value = ((x + y) % 2) as f32;
+
+                target.pixel_mut(Coordinate::new(x, y)).unwrap().set(Vector4::new(value, value, value, 1.0));
+            }
+        }
+
+        let buffer = SupersampleBuffer::from_framebuffer(&target, 3).unwrap();
+
+        let value = |target: &RenderBuffer<Attachments>| target.pixel_ref(Coordinate::new(1, 1)).unwrap().get().x;
+
+        // Without jitter, the box filter only averages copies of the same pixel
+        buffer.downsample(&mut target, DownsampleFilter::Box).unwrap();
+
+        assert_eq!(value(&target), 0.0);
+
+        // Wider filters blur the checkerboard even though nothing was rendered
+        buffer.downsample(&mut target, DownsampleFilter::Tent).unwrap();
+
+        assert!(value(&target) > 0.0);
+    }
+
+    #[test]
+    fn test_jitter_offset() {
+        for y in 0..16 {
+            for x in 0..16 {
+                let (jx, jy) = jitter_offset(42, x, y);
+
+                assert!(-8 <= jx && jx < 8 && -8 <= jy && jy < 8);
+                assert_eq!(jitter_offset(42, x, y), (jx, jy));
+            }
+        }
+
+        assert!((0..16).any(|x| jitter_offset(42, x, 0) != jitter_offset(43, x, 0)));
+    }
+}
@@ -7,10 +7,12 @@ pub mod clipvertex;
 pub mod screenvertex;
 pub mod clip;
 pub mod line;
+pub mod scissor;
 
 pub use self::dimension::{Dimensions, HasDimensions};
 pub use self::coordinate::Coordinate;
 pub use self::winding::FaceWinding;
 pub use self::clipvertex::{ClipVertex, Viewport};
 pub use self::screenvertex::ScreenVertex;
-pub use self::clip::{ClippingPlane, ALL_CLIPPING_PLANES};
\ No newline at end of file
+pub use self::clip::{ClippingPlane, ALL_CLIPPING_PLANES};
+pub use self::scissor::Scissor;
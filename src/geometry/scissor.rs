@@ -0,0 +1,42 @@
+//! Scissor rectangle
+
+use std::cmp::{min, max};
+
+use super::{Coordinate, Dimensions};
+
+/// Rectangle of pixels rendering is restricted to, similar to `glScissor` in OpenGL.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Scissor {
+    /// Top-left corner of the rectangle
+    pub offset: Coordinate,
+    /// Size of the rectangle
+    pub dimensions: Dimensions,
+}
+
+impl Scissor {
+    pub fn new(dimensions: Dimensions, offset: Coordinate) -> Scissor {
+        Scissor { offset, dimensions }
+    }
+
+    /// Intersects the scissor rectangle with a rectangle given by its inclusive top-left and bottom-right corners,
+    /// returning the corners of the intersection, or `None` if they don't overlap.
+    pub fn intersect(&self, rect: (Coordinate, Coordinate)) -> Option<(Coordinate, Coordinate)> {
+        if self.dimensions.width == 0 || self.dimensions.height == 0 {
+            return None;
+        }
+
+        let (start, end) = rect;
+
+        let top_left = Coordinate::new(max(start.x, self.offset.x),
+                                       max(start.y, self.offset.y));
+
+        let bottom_right = Coordinate::new(min(end.x, self.offset.x.saturating_add(self.dimensions.width - 1)),
+                                           min(end.y, self.offset.y.saturating_add(self.dimensions.height - 1)));
+
+        if top_left.x <= bottom_right.x && top_left.y <= bottom_right.y {
+            Some((top_left, bottom_right))
+        } else {
+            None
+        }
+    }
+}
@@ -5,7 +5,8 @@ use num_traits::{Float, PrimInt};
 use ::behavior::ThreadSafeCopyable;
 use ::geometry::{Coordinate, Dimensions, HasDimensions};
 use ::pixels::{PixelBuffer, PixelRead, PixelWrite};
-use ::color::{Color, ColorAlpha, AlphaMultiply};
+use ::color::{Color, ColorAlpha, ColorMask, AlphaMultiply};
+use ::interpolate::Interpolate;
 
 impl<T: Primitive> Color for Rgb<T> where T: ColorAlpha {
     type Alpha = ();
@@ -23,6 +24,16 @@ impl<T: Primitive> Color for Rgb<T> where T: ColorAlpha {
 
     #[inline]
     fn get_alpha(&self) -> () { () }
+
+    fn mask(self, previous: Self, mask: ColorMask) -> Self {
+        Rgb {
+            data: [
+                ColorMask::select(mask.red, self.data[0], previous.data[0]),
+                ColorMask::select(mask.green, self.data[1], previous.data[1]),
+                ColorMask::select(mask.blue, self.data[2], previous.data[2]),
+            ]
+        }
+    }
 }
 
 impl<T: Primitive> Color for Luma<T> where T: ColorAlpha {
@@ -41,6 +52,10 @@ impl<T: Primitive> Color for Luma<T> where T: ColorAlpha {
 
     #[inline]
     fn get_alpha(&self) -> () { () }
+
+    fn mask(self, previous: Self, mask: ColorMask) -> Self {
+        Luma { data: [ColorMask::select(mask.red, self.data[0], previous.data[0])] }
+    }
 }
 
 impl<T: Primitive> Color for Rgba<T> where T: AlphaMultiply + ColorAlpha {
@@ -77,6 +92,17 @@ impl<T: Primitive> Color for Rgba<T> where T: AlphaMultiply + ColorAlpha {
     fn get_alpha(&self) -> T {
         self.data[3]
     }
+
+    fn mask(self, previous: Self, mask: ColorMask) -> Self {
+        Rgba {
+            data: [
+                ColorMask::select(mask.red, self.data[0], previous.data[0]),
+                ColorMask::select(mask.green, self.data[1], previous.data[1]),
+                ColorMask::select(mask.blue, self.data[2], previous.data[2]),
+                ColorMask::select(mask.alpha, self.data[3], previous.data[3]),
+            ]
+        }
+    }
 }
 
 impl<T: Primitive> Color for LumaA<T> where T: AlphaMultiply + ColorAlpha {
@@ -104,4 +130,56 @@ impl<T: Primitive> Color for LumaA<T> where T: AlphaMultiply + ColorAlpha {
     fn get_alpha(&self) -> T {
         self.data[1]
     }
-}
\ No newline at end of file
+
+    fn mask(self, previous: Self, mask: ColorMask) -> Self {
+        LumaA {
+            data: [
+                ColorMask::select(mask.red, self.data[0], previous.data[0]),
+                ColorMask::select(mask.alpha, self.data[1], previous.data[1]),
+            ]
+        }
+    }
+}
+
+macro_rules! impl_image_pixel_interpolate {
+    ($($name:ident: $channels:expr),+) => {
+        $(
+            impl<T: Primitive> Interpolate for $name<T> where T: Interpolate {
+                #[inline]
+                fn barycentric_interpolate<R: Float>(u: R, ux: &Self, v: R, vx: &Self, w: R, wx: &Self) -> Self {
+                    let mut data = [T::zero(); $channels];
+
+                    for i in 0..$channels {
+                        data[i] = Interpolate::barycentric_interpolate(u, &ux.data[i], v, &vx.data[i], w, &wx.data[i]);
+                    }
+
+                    $name { data }
+                }
+
+                #[inline]
+                fn linear_interpolate<R: Float>(t: R, x1: &Self, x2: &Self) -> Self {
+                    let mut data = [T::zero(); $channels];
+
+                    for i in 0..$channels {
+                        data[i] = Interpolate::linear_interpolate(t, &x1.data[i], &x2.data[i]);
+                    }
+
+                    $name { data }
+                }
+
+                #[inline]
+                fn weighted_sum<'a, R: Float, I>(values: I) -> Self where Self: 'a, I: Iterator<Item = (R, &'a Self)> + Clone {
+                    let mut data = [T::zero(); $channels];
+
+                    for i in 0..$channels {
+                        data[i] = Interpolate::weighted_sum(values.clone().map(|(weight, value)| (weight, &value.data[i])));
+                    }
+
+                    $name { data }
+                }
+            }
+        )+
+    }
+}
+
+impl_image_pixel_interpolate!(Rgb: 3, Luma: 1, Rgba: 4, LumaA: 2);
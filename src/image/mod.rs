@@ -5,10 +5,12 @@ pub mod pixelbuffer;
 
 #[cfg(test)]
 mod test {
+    use nalgebra::Vector2;
+
     use super::*;
-    use super::image::{Rgba, RgbaImage};
+    use super::image::{Rgba, RgbaImage, Luma, GrayImage};
 
-    use ::texture::TextureRead;
+    use ::texture::{TextureRead, Filter, Edge};
     use ::attachments::predefined::EmptyAttachments;
 
     fn assert_texture<T: TextureRead>(_: T) {}
@@ -19,4 +21,12 @@ mod test {
 
         assert_texture(t)
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_image_sample() {
+        let t = GrayImage::from_raw(2, 1, vec![0, 200]).unwrap();
+
+        assert_eq!(t.sample(Vector2::new(0.75, 0.5), Filter::Nearest, Edge::Clamp).unwrap(), Luma { data: [200] });
+        assert_eq!(t.sample(Vector2::new(0.5, 0.5), Filter::Bilinear, Edge::Clamp).unwrap(), Luma { data: [100] });
+    }
+}
@@ -22,6 +22,7 @@
 //! * Simple yet flexible Mesh representation.
 //! * Define your own vertex attributes.
 //! * Built-in compatibility with the `image` crate, using the `image_compat` cargo feature.
+//! * Packet fragment shaders, which shade each 2x2 quad of fragments at once, using the `packet_shaders` cargo feature.
 //!
 //! ### Planned Features:
 //!
@@ -93,12 +94,18 @@ pub mod prelude {
     pub use ::primitive::{Primitive, Point, Line, Triangle, PrimitiveRef, PrimitiveMut};
     pub use ::mesh::{Vertex, SimpleVertex, Mesh};
     pub use ::pixels::{PixelBuffer, PixelRead, PixelWrite, PartialPixelBuffer};
-    pub use ::framebuffer::{Framebuffer, RenderBuffer, Attachments};
+    pub use ::framebuffer::{Framebuffer, RenderBuffer, MultisampleBuffer, SupersampleBuffer, DownsampleFilter, Attachments};
     pub use ::interpolate::Interpolate;
     pub use ::pipeline::{Pipeline, PipelineObject,
                          VertexShader, GeometryShader, FragmentShader,
                          PrimitiveStorage};
-    pub use ::pipeline::stages::fragment::Fragment;
+    pub use ::pipeline::stages::fragment::{Fragment, FragmentOutput, DepthBias};
+    pub use ::pipeline::stages::input::FragmentInput;
+
+    #[cfg(feature = "packet_shaders")]
+    pub use ::pipeline::stages::input::FragmentPacket;
+    #[cfg(feature = "packet_shaders")]
+    pub use ::numeric::packet::Packet;
 }
 
 include!("macros.rs");
@@ -37,30 +37,60 @@
 /// note that the `u` and `v` in the `Interpolate::barycentric_interpolate` arguments are mostly unrelated to the `uv` normal. They're both Interpolate coordinates,
 /// but for different things.
 ///
+/// Members can also be given an interpolation qualifier after `pub`, similar to GLSL:
+///
+/// ```ignore
+/// declare_uniforms!(
+///     pub struct MyUniforms {
+///         pub normal: Vector4<f32>,
+///         /// Taken from the first vertex of each primitive
+///         pub flat material: u32,
+///         /// Interpolated linearly in screen-space
+///         pub noperspective screen_uv: Vector2<f32>,
+///     }
+/// );
+/// ```
+///
+/// `flat` members are copied from the first vertex of each primitive instead of being interpolated, so they must be `Clone`,
+/// while `noperspective` members are interpolated with screen-space barycentric coordinates even when
+/// perspective-correct interpolation is enabled. Members without a qualifier are interpolated as usual.
+///
 /// For now, the struct itself must be `pub` and all the members must be `pub`, but hopefully that can change in the future.
 #[macro_export]
 macro_rules! declare_uniforms {
     ($(#[$($struct_attrs:tt)*])* pub struct $name:ident {
-        $(
-            $(#[$($field_attrs:tt)*])*
-            pub $field:ident: $t:ty,
-        )*
+        $($fields:tt)*
     }) => {
-        $(#[$($struct_attrs)*])*
+        declare_uniforms!(@fields [$(#[$($struct_attrs)*])*] $name [] $($fields)*);
+    };
+
+    (@fields $attrs:tt $name:ident [$($done:tt)*] $(#[$($field_attrs:tt)*])* pub flat $field:ident: $t:ty, $($rest:tt)*) => {
+        declare_uniforms!(@fields $attrs $name [$($done)* { flat $field [$(#[$($field_attrs)*])*] $t }] $($rest)*);
+    };
+
+    (@fields $attrs:tt $name:ident [$($done:tt)*] $(#[$($field_attrs:tt)*])* pub noperspective $field:ident: $t:ty, $($rest:tt)*) => {
+        declare_uniforms!(@fields $attrs $name [$($done)* { noperspective $field [$(#[$($field_attrs)*])*] $t }] $($rest)*);
+    };
+
+    (@fields $attrs:tt $name:ident [$($done:tt)*] $(#[$($field_attrs:tt)*])* pub $field:ident: $t:ty, $($rest:tt)*) => {
+        declare_uniforms!(@fields $attrs $name [$($done)* { smooth $field [$(#[$($field_attrs)*])*] $t }] $($rest)*);
+    };
+
+    (@fields [$($attrs:tt)*] $name:ident [$({ $qualifier:ident $field:ident [$($field_attrs:tt)*] $t:ty })*]) => {
+        $($attrs)*
         pub struct $name {
             $(
-                $(#[$($field_attrs)*])*
+                $($field_attrs)*
                 pub $field: $t
             ),*
         }
 
+        #[allow(unused_variables)]
         impl $crate::interpolate::Interpolate for $name {
             fn barycentric_interpolate<N: $crate::numeric::Float>(u: N, ux: &Self, v: N, vx: &Self, w: N, wx: &Self) -> Self {
                 $name {
                     $(
-                        $field: $crate::interpolate::Interpolate::barycentric_interpolate(u, &ux.$field,
-                                                                                          v, &vx.$field,
-                                                                                          w, &wx.$field)
+                        $field: declare_uniforms!(@barycentric $qualifier (u, v, w), (u, v, w), &ux.$field, &vx.$field, &wx.$field)
                     ),*
                 }
             }
@@ -68,10 +98,53 @@ macro_rules! declare_uniforms {
             fn linear_interpolate<N: $crate::numeric::Float>(t: N, x1: &Self, x2: &Self) -> Self {
                 $name {
                     $(
-                          $field: $crate::interpolate::Interpolate::linear_interpolate(t, &x1.$field, &x2.$field)
+                        $field: declare_uniforms!(@linear $qualifier t, t, &x1.$field, &x2.$field)
+                    ),*
+                }
+            }
+
+            fn fragment_barycentric_interpolate<N: $crate::numeric::Float>(perspective: (N, N, N), screen: (N, N, N),
+                                                                          x1: &Self, x2: &Self, x3: &Self) -> Self {
+                $name {
+                    $(
+                        $field: declare_uniforms!(@barycentric $qualifier perspective, screen, &x1.$field, &x2.$field, &x3.$field)
+                    ),*
+                }
+            }
+
+            fn fragment_linear_interpolate<N: $crate::numeric::Float>(perspective: N, screen: N, x1: &Self, x2: &Self) -> Self {
+                $name {
+                    $(
+                        $field: declare_uniforms!(@linear $qualifier perspective, screen, &x1.$field, &x2.$field)
                     ),*
                 }
             }
         }
     };
-}
\ No newline at end of file
+
+    (@barycentric smooth $perspective:expr, $screen:expr, $x1:expr, $x2:expr, $x3:expr) => {
+        $crate::interpolate::Interpolate::fragment_barycentric_interpolate($perspective, $screen, $x1, $x2, $x3)
+    };
+
+    (@barycentric noperspective $perspective:expr, $screen:expr, $x1:expr, $x2:expr, $x3:expr) => {{
+        let (u, v, w) = $screen;
+
+        $crate::interpolate::Interpolate::barycentric_interpolate(u, $x1, v, $x2, w, $x3)
+    }};
+
+    (@barycentric flat $perspective:expr, $screen:expr, $x1:expr, $x2:expr, $x3:expr) => {
+        ::std::clone::Clone::clone($x1)
+    };
+
+    (@linear smooth $perspective:expr, $screen:expr, $x1:expr, $x2:expr) => {
+        $crate::interpolate::Interpolate::fragment_linear_interpolate($perspective, $screen, $x1, $x2)
+    };
+
+    (@linear noperspective $perspective:expr, $screen:expr, $x1:expr, $x2:expr) => {
+        $crate::interpolate::Interpolate::linear_interpolate($screen, $x1, $x2)
+    };
+
+    (@linear flat $perspective:expr, $screen:expr, $x1:expr, $x2:expr) => {
+        ::std::clone::Clone::clone($x1)
+    };
+}
@@ -2,7 +2,7 @@
 
 use std::ops::{Add, Mul};
 
-use num_traits::{Float, NumCast};
+use num_traits::{Float, NumCast, Bounded};
 
 /// Describes a type that can be interpolated with barycentric coordinates.
 ///
@@ -18,6 +18,44 @@ pub trait Interpolate {
 
     /// Simple linear interpolation
     fn linear_interpolate<R: Float>(t: R, x1: &Self, x2: &Self) -> Self;
+
+    /// Interpolate the three values of a triangle for a fragment, given both its perspective-correct
+    /// and screen-space barycentric coordinates, which is used by the rasterizer for uniforms.
+    ///
+    /// By default this interpolates with the perspective-correct coordinates,
+    /// but `declare_uniforms!` uses the screen-space coordinates for `noperspective` members.
+    #[inline]
+    fn fragment_barycentric_interpolate<R: Float>(perspective: (R, R, R), _screen: (R, R, R), x1: &Self, x2: &Self, x3: &Self) -> Self where Self: Sized {
+        let (u, v, w) = perspective;
+
+        Self::barycentric_interpolate(u, x1, v, x2, w, x3)
+    }
+
+    /// Interpolate the two values of a line for a fragment, given both its perspective-correct
+    /// and screen-space parameters along the line, as with `fragment_barycentric_interpolate`.
+    #[inline]
+    fn fragment_linear_interpolate<R: Float>(perspective: R, _screen: R, x1: &Self, x2: &Self) -> Self where Self: Sized {
+        Self::linear_interpolate(perspective, x1, x2)
+    }
+
+    /// Sum any number of values multiplied by their weights, such as for averaging or filtering samples.
+    ///
+    /// By default this accumulates the values one at a time with `barycentric_interpolate`,
+    /// but primitive types, vectors, matrices and image pixels sum in floating-point and only convert the result,
+    /// so integer values are rounded once instead of truncated at every step.
+    /// Integer results outside of the range of the type saturate to its minimum or maximum.
+    ///
+    /// Panics if there are no values.
+    #[inline]
+    fn weighted_sum<'a, R: Float, I>(values: I) -> Self where Self: Sized + 'a, I: Iterator<Item = (R, &'a Self)> + Clone {
+        let mut values = values;
+
+        let (weight, first) = values.next().expect("No values to sum");
+
+        let sum = Self::barycentric_interpolate(weight, first, R::zero(), first, R::zero(), first);
+
+        values.fold(sum, |sum, (weight, value)| Self::barycentric_interpolate(R::one(), &sum, weight, value, R::zero(), value))
+    }
 }
 
 /// Convenience method for interpolating three values with barycentric coordinates.
@@ -40,8 +78,19 @@ impl Interpolate for () {
     fn linear_interpolate<R: Float>(_: R, _: &Self, _: &Self) -> Self { () }
 }
 
+/// Rounds a floating-point sum to an integer type, saturating if it is out of range
+#[inline(always)]
+fn round_saturating<T: NumCast + Bounded, R: Float>(sum: R) -> T {
+    <T as NumCast>::from(sum.round()).unwrap_or_else(|| if sum > R::zero() { T::max_value() } else { T::min_value() })
+}
+
+#[inline(always)]
+fn cast_float<T: NumCast, R: Float>(sum: R) -> T {
+    <T as NumCast>::from(sum).unwrap()
+}
+
 macro_rules! impl_primitive_interpolate {
-    ($($t:ty),+) => {
+    ($convert:ident: $($t:ty),+) => {
         $(
             impl Interpolate for $t {
                 #[inline(always)]
@@ -56,12 +105,18 @@ macro_rules! impl_primitive_interpolate {
                      <$t as NumCast>::from((R::one() - t) * R::from(*x1).unwrap() +
                                                        t  * R::from(*x2).unwrap()).unwrap()
                 }
+
+                #[inline]
+                fn weighted_sum<'a, R: Float, I>(values: I) -> $t where I: Iterator<Item = (R, &'a $t)> + Clone {
+                    $convert(values.fold(R::zero(), |sum, (weight, value)| sum + weight * R::from(*value).unwrap()))
+                }
             }
         )+
     }
 }
 
-impl_primitive_interpolate!(i8, i16, i32, i64, u8, u16, u32, u64, isize, usize, f32, f64);
+impl_primitive_interpolate!(round_saturating: i8, i16, i32, i64, u8, u16, u32, u64, isize, usize);
+impl_primitive_interpolate!(cast_float: f32, f64);
 
 use alga::general::Real;
 
@@ -187,6 +242,21 @@ macro_rules! nalgebra_matrix_uniforms {
                     res
                 }
             }
+
+            #[inline]
+            fn weighted_sum<'a, R: Float, I>(values: I) -> Self where Self: 'a, I: Iterator<Item = (R, &'a Self)> + Clone {
+                unsafe {
+                    let mut res = Self::new_uninitialized();
+
+                    $(
+                        *res.get_unchecked_mut($irow, $icol) = $crate::interpolate::Interpolate::weighted_sum(
+                            values.clone().map(|(weight, value)| (weight, value.get_unchecked($irow, $icol)))
+                        );
+                    )*
+
+                    res
+                }
+            }
         }
     )*}
 }
@@ -4,6 +4,9 @@ use nalgebra::Scalar;
 pub mod interpolate;
 pub mod utils;
 
+#[cfg(feature = "packet_shaders")]
+pub mod packet;
+
 use self::interpolate::Interpolate;
 
 pub use num_traits::Float;
@@ -0,0 +1,130 @@
+//! Packets of values processed together, one for each pixel in a 2x2 quad
+//!
+//! Operations on packets are written as simple loops over fixed-size arrays of any type, without explicit SIMD intrinsics,
+//! so whether they are compiled to vector instructions is left entirely to the optimizer.
+//!
+//! Only available with the `packet_shaders` cargo feature.
+
+use std::ops::{Add, Sub, Mul, Div, Neg};
+
+/// Number of lanes in a packet, which is the number of pixels in a 2x2 quad
+pub const LANES: usize = 4;
+
+/// Fixed-size group of values, one for each pixel in a 2x2 quad,
+/// in the order top-left, top-right, bottom-left, bottom-right.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Packet<T>(pub [T; LANES]);
+
+impl<T: Copy> Packet<T> {
+    /// Creates a packet with every lane set to the same value
+    #[inline(always)]
+    pub fn splat(value: T) -> Packet<T> {
+        Packet([value; LANES])
+    }
+
+    /// Creates a packet from the value of each lane given by `f`
+    #[inline(always)]
+    pub fn generate<F>(f: F) -> Packet<T> where F: Fn(usize) -> T {
+        Packet([f(0), f(1), f(2), f(3)])
+    }
+
+    /// Returns the value of the given lane
+    #[inline(always)]
+    pub fn lane(&self, lane: usize) -> T {
+        self.0[lane]
+    }
+
+    /// Applies `f` to every lane
+    #[inline(always)]
+    pub fn map<U: Copy, F>(self, f: F) -> Packet<U> where F: Fn(T) -> U {
+        Packet([f(self.0[0]), f(self.0[1]), f(self.0[2]), f(self.0[3])])
+    }
+
+    /// Combines the lanes of two packets with `f`
+    #[inline(always)]
+    pub fn zip<U: Copy, R: Copy, F>(self, other: Packet<U>, f: F) -> Packet<R> where F: Fn(T, U) -> R {
+        Packet([f(self.0[0], other.0[0]), f(self.0[1], other.0[1]),
+                f(self.0[2], other.0[2]), f(self.0[3], other.0[3])])
+    }
+
+    /// Returns a bitmask of the lanes where `f` is true, with the first lane in the lowest bit
+    #[inline(always)]
+    pub fn mask<F>(self, f: F) -> u32 where F: Fn(T) -> bool {
+        let mut mask = 0;
+
+        for lane in 0..LANES {
+            mask |= (f(self.0[lane]) as u32) << lane;
+        }
+
+        mask
+    }
+
+    /// Chooses the lanes of `a` where the bitmask is set, and the lanes of `b` elsewhere
+    #[inline(always)]
+    pub fn select(mask: u32, a: Packet<T>, b: Packet<T>) -> Packet<T> {
+        Packet::generate(|lane| if mask & (1 << lane) != 0 { a.0[lane] } else { b.0[lane] })
+    }
+}
+
+impl<T: Copy + PartialOrd> Packet<T> {
+    /// Bitmask of the lanes greater than the same lane of `other`
+    #[inline(always)]
+    pub fn gt(self, other: Packet<T>) -> u32 {
+        let mut mask = 0;
+
+        for lane in 0..LANES {
+            mask |= ((self.0[lane] > other.0[lane]) as u32) << lane;
+        }
+
+        mask
+    }
+}
+
+macro_rules! impl_packet_op {
+    ($op:ident, $f:ident) => {
+        impl<T: Copy + $op<Output = T>> $op for Packet<T> {
+            type Output = Packet<T>;
+
+            #[inline(always)]
+            fn $f(self, rhs: Packet<T>) -> Packet<T> {
+                Packet([$op::$f(self.0[0], rhs.0[0]), $op::$f(self.0[1], rhs.0[1]),
+                        $op::$f(self.0[2], rhs.0[2]), $op::$f(self.0[3], rhs.0[3])])
+            }
+        }
+    }
+}
+
+impl_packet_op!(Add, add);
+impl_packet_op!(Sub, sub);
+impl_packet_op!(Mul, mul);
+impl_packet_op!(Div, div);
+
+impl<T: Copy + Neg<Output = T>> Neg for Packet<T> {
+    type Output = Packet<T>;
+
+    #[inline(always)]
+    fn neg(self) -> Packet<T> {
+        self.map(Neg::neg)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_packet_ops() {
+        let a = Packet([1.0f32, 2.0, 3.0, 4.0]);
+        let b = Packet::splat(2.0f32);
+
+        assert_eq!(a + b, Packet([3.0, 4.0, 5.0, 6.0]));
+        assert_eq!(a * b - b, Packet([0.0, 2.0, 4.0, 6.0]));
+        assert_eq!(-a / b, Packet([-0.5, -1.0, -1.5, -2.0]));
+
+        assert_eq!(a.gt(b), 0b1100);
+        assert_eq!(a.mask(|x| x == 2.0), 0b0010);
+
+        assert_eq!(Packet::select(0b0101, a, b), Packet([1.0, 2.0, 3.0, 2.0]));
+        assert_eq!(Packet::generate(|lane| lane as i64 * 3), Packet([0, 3, 6, 9]));
+    }
+}
@@ -16,9 +16,11 @@ use ::framebuffer::nullbuffer::NullFramebuffer;
 pub mod storage;
 pub mod types;
 pub mod stages;
+pub mod supersample;
 
 pub use self::storage::PrimitiveStorage;
 pub use self::stages::{VertexShader, GeometryShader, FragmentShader};
+pub use self::supersample::SupersamplePipeline;
 
 use self::types::StencilValue;
 
@@ -8,18 +8,23 @@ use nalgebra::coordinates::XYZW;
 
 use ::error::RenderResult;
 
-use ::numeric::utils::min;
-use ::color::{Color, ColorAlpha};
+use ::color::{Color, ColorAlpha, ColorMask};
 use ::color::blend::Blend;
 use ::pixels::{PixelRead, PixelWrite};
 use ::framebuffer::{UnsafeFramebuffer, Framebuffer};
-use ::attachments::depth::Depth;
-use ::stencil::StencilConfig;
+use ::attachments::depth::{Depth, DepthTest};
+use ::stencil::{StencilConfig, StencilState};
 use ::primitive::Primitive;
+use ::numeric::FloatScalar;
 use ::mesh::{Vertex, Mesh};
-use ::geometry::{Dimensions, HasDimensions, Coordinate, ScreenVertex, FaceWinding};
+use ::geometry::{Dimensions, HasDimensions, ScreenVertex, FaceWinding, Scissor};
 use ::interpolate::Interpolate;
 use ::pipeline::storage::SeparableScreenPrimitiveStorage;
+use ::pipeline::stages::input::{FragmentInput, QuadContext};
+
+#[cfg(feature = "packet_shaders")]
+use ::pipeline::stages::input::FragmentPacket;
+use ::pipeline::stages::rasterization::binning::{Tiles, Primitives, Bins, BinnedPrimitive, bin_primitives};
 
 use ::pipeline::PipelineObject;
 
@@ -28,6 +33,34 @@ use ::pipeline::types::{PipelineUniforms, Pixel, StencilValue};
 
 pub const DEFAULT_TILE_SIZE: Dimensions = Dimensions { width: 128, height: 128 };
 
+/// Passes fragments that are nearer to the camera than, or as near as, what has already been rendered
+pub const DEFAULT_DEPTH_TEST: DepthTest = DepthTest::GreaterThanEq;
+
+/// Offset applied to the interpolated depth of fragments before the depth test, similar to `glPolygonOffset`.
+///
+/// Positive values move fragments away from the camera, and negative values move them nearer.
+/// Depth written by fragment shaders with `FragmentOutput` is not offset.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DepthBias<N> {
+    /// Constant offset in screen-space depth units, where the entire depth range is one unit
+    pub constant: N,
+    /// Offset scaled by the largest change in screen-space depth per pixel across the primitive,
+    /// which avoids shadow acne on surfaces at steep angles to the light
+    pub slope: N,
+}
+
+impl<N> DepthBias<N> {
+    pub fn new(constant: N, slope: N) -> DepthBias<N> {
+        DepthBias { constant, slope }
+    }
+}
+
+impl<N: Zero> Default for DepthBias<N> {
+    fn default() -> DepthBias<N> {
+        DepthBias { constant: N::zero(), slope: N::zero() }
+    }
+}
+
 /// Fragment shader stage.
 ///
 /// The fragment shader is responsible for determining the color of pixels where the underlying geometry has been projected onto.
@@ -35,9 +68,10 @@ pub const DEFAULT_TILE_SIZE: Dimensions = Dimensions { width: 128, height: 128 }
 /// and lines (pairs of vertices considered as endpoints for lines).
 ///
 /// The fragment shader runs several tests before executing the given shader program, including a depth test.
-/// If the depth of the geometry (from the camera), is farther away than geometry that has already been rendered,
+/// By default, if the depth of the geometry (from the camera), is farther away than geometry that has already been rendered,
 /// the shader program isn't run at all, since it wouldn't be visible anyway. Additionally,
 /// if the geometry is nearer than an existing fragment, the existing fragment is overwritten.
+/// The depth test and depth writes can be configured for each draw with `depth_test` and `depth_write`.
 ///
 /// Uniforms passed from the vertex shader are interpolating inside the triangles using Interpolate interpolation,
 /// which is why it must satisfy the [`Interpolate`](../uniform/trait.Interpolate.html) trait, which can be automatically implemented for many types using the
@@ -50,8 +84,15 @@ pub struct FragmentShader<'a, P: 'a, V: Vertex, T, K, B> where P: PipelineObject
     pub ( in ::pipeline) indexed_vertices: Arc<Option<Vec<ScreenVertex<V::Scalar, K>>>>,
     pub ( in ::pipeline) generated_primitives: Arc<SeparableScreenPrimitiveStorage<V::Scalar, K>>,
     pub ( in ::pipeline) cull_faces: Option<FaceWinding>,
+    pub ( in ::pipeline) front_face: FaceWinding,
     pub ( in ::pipeline) blend: B,
     pub ( in ::pipeline) antialiased_lines: bool,
+    pub ( in ::pipeline) perspective_correct: bool,
+    pub ( in ::pipeline) depth_test: Option<DepthTest>,
+    pub ( in ::pipeline) depth_write: bool,
+    pub ( in ::pipeline) depth_bias: DepthBias<V::Scalar>,
+    pub ( in ::pipeline) scissor: Option<Scissor>,
+    pub ( in ::pipeline) color_mask: ColorMask,
     pub ( in ::pipeline) tile_size: Dimensions,
 }
 
@@ -65,6 +106,140 @@ pub enum Fragment<C> where C: Color {
     Color(C)
 }
 
+/// Fragment returned by fragment shaders that replace the depth of the fragment or the stencil reference value,
+/// similar to writing `gl_FragDepth` or `gl_FragStencilRefARB` in GLSL.
+///
+/// Returning this from a fragment shader switches the pipeline from early to late depth and stencil testing,
+/// so the shader runs on every covered pixel and the tests are performed with its outputs afterwards.
+/// Discarded fragments are never tested, so they don't update the depth or stencil buffers either.
+///
+/// The switch is decided by the return type of the shader alone, so it applies even if the shader leaves both
+/// `depth` and `stencil` as `None`. Late testing shades every occluded fragment that early tests would have skipped,
+/// which can multiply the cost of expensive shaders in scenes with a lot of overdraw,
+/// so shaders that never replace the depth or stencil value should return a plain `Fragment` instead.
+#[derive(Debug, Clone, Copy)]
+pub struct FragmentOutput<C, N, S> where C: Color {
+    /// Color of the fragment, or whether it was discarded
+    pub fragment: Fragment<C>,
+    /// Screen-space depth of the fragment, in the same units as the `z` component of the interpolated position,
+    /// or `None` to use the interpolated depth.
+    pub depth: Option<N>,
+    /// Stencil reference value of the fragment, or `None` to use the stencil value given for the mesh.
+    pub stencil: Option<S>,
+}
+
+impl<C, N, S> FragmentOutput<C, N, S> where C: Color {
+    /// Create a new `FragmentOutput` with the interpolated depth and stencil value of the mesh.
+    ///
+    /// This still disables early depth and stencil tests, unlike returning the `Fragment` directly.
+    pub fn new(fragment: Fragment<C>) -> FragmentOutput<C, N, S> {
+        FragmentOutput { fragment, depth: None, stencil: None }
+    }
+
+    pub fn with_depth(self, depth: N) -> FragmentOutput<C, N, S> {
+        FragmentOutput { depth: Some(depth), ..self }
+    }
+
+    pub fn with_stencil(self, stencil: S) -> FragmentOutput<C, N, S> {
+        FragmentOutput { stencil: Some(stencil), ..self }
+    }
+}
+
+/// Types that can be returned from fragment shaders, which are `Fragment` and `FragmentOutput`
+pub trait ShaderOutput<C, N, S>: Send where C: Color {
+    /// Whether the depth and stencil tests have to run after the fragment shader
+    fn late_tests() -> bool;
+
+    /// Returns the fragment along with any replacement depth and stencil reference value
+    fn into_output(self) -> FragmentOutput<C, N, S>;
+}
+
+impl<C, N, S> ShaderOutput<C, N, S> for Fragment<C> where C: Color {
+    #[inline(always)]
+    fn late_tests() -> bool { false }
+
+    #[inline(always)]
+    fn into_output(self) -> FragmentOutput<C, N, S> { FragmentOutput::new(self) }
+}
+
+impl<C, N, S> ShaderOutput<C, N, S> for FragmentOutput<C, N, S> where C: Color, N: Send, S: Send {
+    #[inline(always)]
+    fn late_tests() -> bool { true }
+
+    #[inline(always)]
+    fn into_output(self) -> FragmentOutput<C, N, S> { self }
+}
+
+/// Fragment shader program run by the rasterizers on 2x2 quads of fragments,
+/// which either shades each fragment individually or, with the `packet_shaders` cargo feature, entire packets of fragments at once.
+pub trait QuadShader<P, N, K>: Send + Sync where P: PipelineObject, N: FloatScalar {
+    /// Whether the depth and stencil tests have to run after the fragment shader
+    fn late_tests() -> bool;
+
+    /// Shades every lane of the quad with nonzero coverage, returning `None` for the other lanes
+    fn shade(&self, quad: [&ScreenVertex<N, K>; 4], context: &QuadContext<N>, coverage: [u32; 4], uniforms: &PipelineUniforms<P>)
+             -> [Option<FragmentOutput<Pixel<P>, N, StencilValue<P>>>; 4];
+
+    /// Shades a single lane of the quad, given its nonzero coverage
+    #[inline]
+    fn shade_lane(&self, quad: [&ScreenVertex<N, K>; 4], context: &QuadContext<N>, lane: usize, coverage: u32, uniforms: &PipelineUniforms<P>)
+                  -> FragmentOutput<Pixel<P>, N, StencilValue<P>> {
+        let mut lanes = [0; 4];
+
+        lanes[lane] = coverage;
+
+        self.shade(quad, context, lanes, uniforms)[lane].take().expect("Fragment was not shaded")
+    }
+}
+
+/// Quad shader running a fragment shader on each fragment individually
+pub struct PerFragment<S>(S);
+
+impl<P, N, K, S, R> QuadShader<P, N, K> for PerFragment<S> where P: PipelineObject,
+                                                                 N: FloatScalar,
+                                                                 S: Fn(&FragmentInput<N, K>, &PipelineUniforms<P>) -> R + Send + Sync,
+                                                                 R: ShaderOutput<Pixel<P>, N, StencilValue<P>> {
+    #[inline(always)]
+    fn late_tests() -> bool { R::late_tests() }
+
+    #[inline]
+    fn shade(&self, quad: [&ScreenVertex<N, K>; 4], context: &QuadContext<N>, coverage: [u32; 4], uniforms: &PipelineUniforms<P>)
+             -> [Option<FragmentOutput<Pixel<P>, N, StencilValue<P>>>; 4] {
+        let mut outputs = [None, None, None, None];
+
+        for lane in 0..4 {
+            if coverage[lane] != 0 {
+                outputs[lane] = Some((self.0)(&FragmentInput::new(quad, context, lane, coverage[lane]), uniforms).into_output());
+            }
+        }
+
+        outputs
+    }
+}
+
+/// Quad shader running a packet fragment shader on every fragment of the quad at once
+#[cfg(feature = "packet_shaders")]
+pub struct PerPacket<S>(S);
+
+#[cfg(feature = "packet_shaders")]
+impl<P, N, K, S, R> QuadShader<P, N, K> for PerPacket<S> where P: PipelineObject,
+                                                               N: FloatScalar,
+                                                               S: Fn(&FragmentPacket<N, K>, &PipelineUniforms<P>) -> [R; 4] + Send + Sync,
+                                                               R: ShaderOutput<Pixel<P>, N, StencilValue<P>> {
+    #[inline(always)]
+    fn late_tests() -> bool { R::late_tests() }
+
+    #[inline]
+    fn shade(&self, quad: [&ScreenVertex<N, K>; 4], context: &QuadContext<N>, coverage: [u32; 4], uniforms: &PipelineUniforms<P>)
+             -> [Option<FragmentOutput<Pixel<P>, N, StencilValue<P>>>; 4] {
+        let [a, b, c, d] = (self.0)(&FragmentPacket::new(quad, context, coverage), uniforms);
+
+        let output = |lane: usize, output: R| if coverage[lane] != 0 { Some(output.into_output()) } else { None };
+
+        [output(0, a), output(1, b), output(2, c), output(3, d)]
+    }
+}
+
 impl<'a, P: 'a, V, T, K, B> Deref for FragmentShader<'a, P, V, T, K, B>
     where P: PipelineObject, V: Vertex, B: Blend<Pixel<P>> {
     type Target = B;
@@ -90,6 +265,24 @@ impl<'a, P: 'a, V, T, K, B> FragmentShader<'a, P, V, T, K, B> where P: PipelineO
         }
     }
 
+    /// Sets the winding order of front-facing triangles, which is `Clockwise` by default.
+    ///
+    /// Triangles that are counter-clockwise in normalized device coordinates, as with OpenGL, are `Clockwise` here,
+    /// so the default matches meshes that cull their back faces with `cull_faces(Some(FaceWinding::CounterClockwise))`.
+    ///
+    /// Back-facing triangles use the back face stencil state of the stencil configuration,
+    /// while lines and points are always front-facing.
+    pub fn front_face(&mut self, winding: FaceWinding) {
+        self.front_face = winding;
+    }
+
+    pub fn with_front_face(self, winding: FaceWinding) -> Self {
+        FragmentShader {
+            front_face: winding,
+            ..self
+        }
+    }
+
     /// Enables drawing antialiased lines for `Line` primitives
     /// primitives using Xiaolin Wu's algorithm,
     /// otherwise Bresenham's Algorithm is used.
@@ -104,6 +297,94 @@ impl<'a, P: 'a, V, T, K, B> FragmentShader<'a, P, V, T, K, B> where P: PipelineO
         }
     }
 
+    /// Enables perspective-correct interpolation of uniforms for `Triangle` and `Line` primitives,
+    /// which is the default. Disabling it interpolates uniforms linearly in screen-space,
+    /// like the `noperspective` qualifier in GLSL.
+    ///
+    /// Screen-space positions are always interpolated linearly, and points have nothing to interpolate.
+    pub fn perspective_correct(&mut self, enable: bool) {
+        self.perspective_correct = enable;
+    }
+
+    pub fn with_perspective_correct(self, enable: bool) -> Self {
+        FragmentShader {
+            perspective_correct: enable,
+            ..self
+        }
+    }
+
+    /// Sets the test used to compare the depth of each fragment against the depth buffer,
+    /// where larger depth values are nearer to the camera. The default is `DepthTest::GreaterThanEq`.
+    ///
+    /// Passing `None` disables the depth test entirely, so every fragment passes and depth is never written,
+    /// like disabling `GL_DEPTH_TEST` in OpenGL. Use `DepthTest::Always` to pass every fragment but still write depth.
+    pub fn depth_test(&mut self, test: Option<DepthTest>) {
+        self.depth_test = test;
+    }
+
+    pub fn with_depth_test(self, test: Option<DepthTest>) -> Self {
+        FragmentShader {
+            depth_test: test,
+            ..self
+        }
+    }
+
+    /// Enables writing the depth of fragments that pass the depth test to the depth buffer, which is the default.
+    ///
+    /// Disabling it is useful for transparent geometry, which should be hidden behind opaque geometry
+    /// but not hide anything itself.
+    pub fn depth_write(&mut self, enable: bool) {
+        self.depth_write = enable;
+    }
+
+    pub fn with_depth_write(self, enable: bool) -> Self {
+        FragmentShader {
+            depth_write: enable,
+            ..self
+        }
+    }
+
+    /// Sets the constant and slope-scaled depth bias of all primitives, which is zero by default.
+    ///
+    /// This is useful for rendering decals and wireframe overlays on top of coplanar geometry without z-fighting,
+    /// and for avoiding self-shadowing artifacts when rendering shadow maps.
+    pub fn depth_bias(&mut self, bias: DepthBias<V::Scalar>) {
+        self.depth_bias = bias;
+    }
+
+    pub fn with_depth_bias(self, bias: DepthBias<V::Scalar>) -> Self {
+        FragmentShader {
+            depth_bias: bias,
+            ..self
+        }
+    }
+
+    /// Restricts rendering to the pixels within the scissor rectangle, or disables the scissor test with `None`,
+    /// which is the default.
+    pub fn scissor(&mut self, scissor: Option<Scissor>) {
+        self.scissor = scissor;
+    }
+
+    pub fn with_scissor(self, scissor: Option<Scissor>) -> Self {
+        FragmentShader {
+            scissor,
+            ..self
+        }
+    }
+
+    /// Sets which channels of the color attachment are written, after blending.
+    /// Channels that are not written keep their previous value. By default, all channels are written.
+    pub fn color_mask(&mut self, mask: ColorMask) {
+        self.color_mask = mask;
+    }
+
+    pub fn with_color_mask(self, mask: ColorMask) -> Self {
+        FragmentShader {
+            color_mask: mask,
+            ..self
+        }
+    }
+
     pub fn tile_size(&mut self, tile_size: Dimensions) {
         self.tile_size = tile_size;
     }
@@ -128,8 +409,15 @@ impl<'a, P: 'a, V, T, K, B> FragmentShader<'a, P, V, T, K, B> where P: PipelineO
             indexed_vertices: self.indexed_vertices.clone(),
             generated_primitives: self.generated_primitives.clone(),
             cull_faces: self.cull_faces.clone(),
+            front_face: self.front_face,
             blend: self.blend.clone(),
             antialiased_lines: self.antialiased_lines,
+            perspective_correct: self.perspective_correct,
+            depth_test: self.depth_test,
+            depth_write: self.depth_write,
+            depth_bias: self.depth_bias,
+            scissor: self.scissor,
+            color_mask: self.color_mask,
             tile_size: self.tile_size,
         }
     }
@@ -147,8 +435,15 @@ impl<'a, P: 'a, V, T, K, O> FragmentShader<'a, P, V, T, K, O> where P: PipelineO
             indexed_vertices: self.indexed_vertices,
             generated_primitives: self.generated_primitives,
             cull_faces: self.cull_faces,
+            front_face: self.front_face,
             blend: blend,
             antialiased_lines: self.antialiased_lines,
+            perspective_correct: self.perspective_correct,
+            depth_test: self.depth_test,
+            depth_write: self.depth_write,
+            depth_bias: self.depth_bias,
+            scissor: self.scissor,
+            color_mask: self.color_mask,
             tile_size: self.tile_size,
         }
     }
@@ -165,8 +460,64 @@ impl<'a, P: 'a, V, T, K, B> FragmentShader<'a, P, V, T, K, B> where P: PipelineO
                                                                     T: Primitive,
                                                                     K: Send + Sync + Interpolate,
                                                                     B: Blend<Pixel<P>> {
-    pub fn run<S>(self, fragment_shader: S)
-        where S: Fn(&ScreenVertex<V::Scalar, K>, &PipelineUniforms<P>) -> Fragment<Pixel<P>> + Send + Sync {
+    /// Executes the fragment shader on every pixel covered by the geometry,
+    /// given the interpolated screen-space vertex of each fragment.
+    ///
+    /// The shader can return either a `Fragment`, or a `FragmentOutput` to replace the depth or stencil reference value of the fragment.
+    pub fn run<S, R>(self, fragment_shader: S)
+        where S: Fn(&ScreenVertex<V::Scalar, K>, &PipelineUniforms<P>) -> R + Send + Sync,
+              R: ShaderOutput<Pixel<P>, V::Scalar, StencilValue<P>> {
+        self.run_with_input(move |input, uniforms| fragment_shader(input.vertex(), uniforms))
+    }
+
+    /// Executes the fragment shader on every pixel covered by the geometry,
+    /// given a [`FragmentInput`](../input/struct.FragmentInput.html) for each fragment.
+    ///
+    /// Besides the interpolated screen-space vertex, the fragment input provides screen-space derivatives
+    /// of any value computed from the interpolated vertices, such as for selecting mipmap levels:
+    ///
+    /// ```ignore
+    /// fragment_shader.run_with_input(|input, global_uniforms| {
+    ///     let uv = input.uniforms.uv;
+    ///
+    ///     let dx = input.dfdx(|v| v.uniforms.uv);
+    ///     let dy = input.dfdy(|v| v.uniforms.uv);
+    ///
+    ///     Fragment::Color(global_uniforms.texture.sample_grad(uv, dx, dy, Filter::Trilinear, Edge::Wrap).unwrap())
+    /// });
+    /// ```
+    ///
+    /// It also provides the pixel coordinate, primitive ID, barycentric coordinates and facing of the fragment.
+    pub fn run_with_input<S, R>(self, fragment_shader: S)
+        where S: Fn(&FragmentInput<V::Scalar, K>, &PipelineUniforms<P>) -> R + Send + Sync,
+              R: ShaderOutput<Pixel<P>, V::Scalar, StencilValue<P>> {
+        self.run_quads(PerFragment(fragment_shader))
+    }
+
+    /// Executes the fragment shader on 2x2 quads of pixels covered by the geometry,
+    /// given a [`FragmentPacket`](../input/struct.FragmentPacket.html) of the four fragments in each quad.
+    ///
+    /// The shader returns the output of every fragment in the packet, but only the outputs of lanes with nonzero coverage are used.
+    /// Since every lane is computed at once, shaders can use [`Packet`](../../../numeric/packet/struct.Packet.html)
+    /// arithmetic to process all four fragments together:
+    ///
+    /// ```ignore
+    /// fragment_shader.run_packet(|packet, global_uniforms| {
+    ///     let intensity = packet.gather(|v| v.uniforms.normal.dot(&global_uniforms.light)).map(|i| i.max(0.0));
+    ///
+    ///     Packet::generate(|lane| Fragment::Color(Vector4::new(1.0, 1.0, 1.0, 1.0) * intensity.lane(lane))).0
+    /// });
+    /// ```
+    ///
+    /// Only available with the `packet_shaders` cargo feature.
+    #[cfg(feature = "packet_shaders")]
+    pub fn run_packet<S, R>(self, fragment_shader: S)
+        where S: Fn(&FragmentPacket<V::Scalar, K>, &PipelineUniforms<P>) -> [R; 4] + Send + Sync,
+              R: ShaderOutput<Pixel<P>, V::Scalar, StencilValue<P>> {
+        self.run_quads(PerPacket(fragment_shader))
+    }
+
+    fn run_quads<S>(self, fragment_shader: S) where S: QuadShader<P, V::Scalar, K> {
         let FragmentShader {
             pipeline,
             mesh,
@@ -174,8 +525,15 @@ impl<'a, P: 'a, V, T, K, B> FragmentShader<'a, P, V, T, K, B> where P: PipelineO
             stencil_value,
             generated_primitives,
             cull_faces,
+            front_face,
             blend,
             antialiased_lines,
+            perspective_correct,
+            depth_test,
+            depth_write,
+            depth_bias,
+            scissor,
+            color_mask,
             tile_size,
             ..
         } = self;
@@ -185,39 +543,14 @@ impl<'a, P: 'a, V, T, K, B> FragmentShader<'a, P, V, T, K, B> where P: PipelineO
 
         let dimensions = pipeline.framebuffer().dimensions();
 
-        let tiles = {
-            let mut tiles = Vec::new();
+        let tiles = Tiles::new(dimensions, tile_size, scissor);
 
-            let xmax = dimensions.width - 1;
-            let ymax = dimensions.height - 1;
+        let primitives = Primitives::new((*indexed_vertices).as_ref().map(|vertices| &vertices[..]),
+                                         &mesh.indices, T::num_vertices(), &generated_primitives);
 
-            let mut y = 0;
-
-            while y < ymax {
-                let mut x = 0;
-
-                let next_y = min(y + tile_size.height, ymax);
-
-                while x < xmax {
-                    let next_x = min(x + tile_size.width, xmax);
-
-                    tiles.push((
-                        Coordinate::new(x, y),
-                        Coordinate::new(next_x, next_y)
-                    ));
-
-                    x = next_x;
-                }
-
-                y = next_y;
-            }
-
-            tiles
-        };
-
-        // Fetch stencil test and operation before tile loop
-        let stencil_test = pipeline.stencil_config().get_test();
-        let stencil_op = pipeline.stencil_config().get_op();
+        // Resolve stencil state of both faces before tile loop
+        let stencil = StencilState::new(pipeline.stencil_config(), stencil_value);
+        let back_stencil = StencilState::back(pipeline.stencil_config(), stencil_value);
 
         /// There is simply no way around this right now. The only reason I'm comfortable doing it is because
         /// all the code using the pipeline is my own and not available to the user.
@@ -237,6 +570,22 @@ impl<'a, P: 'a, V, T, K, B> FragmentShader<'a, P, V, T, K, B> where P: PipelineO
 
         let thread_count = pool.thread_count();
 
+        // Sort primitives into the tiles they overlap, so each tile only rasterizes nearby primitives
+        let mut bins = Bins::new(&tiles, thread_count as usize);
+
+        {
+            let primitives = &primitives;
+            let tiles = &tiles;
+
+            pool.scoped(|scope| {
+                for (bins, start, end) in bins.split(primitives.len()) {
+                    scope.execute(move || {
+                        bin_primitives(primitives, (start, end), tiles, dimensions, bins);
+                    });
+                }
+            });
+        }
+
         let i = AtomicUsize::new(0);
 
         pool.scoped(|scope| {
@@ -251,64 +600,39 @@ impl<'a, P: 'a, V, T, K, B> FragmentShader<'a, P, V, T, K, B> where P: PipelineO
                         let i = i.fetch_add(1, Ordering::Relaxed);
 
                         if i < tiles.len() {
-                            let tile = tiles[i];
+                            let tile = match tiles.get(i) {
+                                Some(tile) => tile,
+                                None => continue,
+                            };
 
-                            let mut args: RasterArguments<P, V> = RasterArguments {
+                            let args: RasterArguments<P, V> = RasterArguments {
                                 dimensions,
                                 tile: tile,
                                 bounds: ((cast(tile.0.x).unwrap(), cast(tile.0.y).unwrap()),
-                                         (cast(tile.1.x).unwrap(), cast(tile.1.y).unwrap())),
-                                stencil_value,
-                                stencil_test,
-                                stencil_op,
+                                         (cast(tile.1.x + 1).unwrap(), cast(tile.1.y + 1).unwrap())),
+                                stencil,
+                                back_stencil,
                                 antialiased_lines,
+                                perspective_correct,
+                                depth_test,
+                                depth_write,
+                                depth_bias,
+                                color_mask,
                                 cull_faces,
+                                front_face,
                             };
 
-                            if T::is_triangle() {
-                                if let Some(ref indexed_vertices) = *indexed_vertices {
-                                    for triangle in mesh.indices.chunks(3) {
-                                        let a = &indexed_vertices[triangle[0]];
-                                        let b = &indexed_vertices[triangle[1]];
-                                        let c = &indexed_vertices[triangle[2]];
-
-                                        rasterize_triangle(&args, pipeline, &blend, &fragment_shader, a, b, c);
-                                    }
+                            bins.for_each(i, |id, primitive| match primitive {
+                                BinnedPrimitive::Triangle(a, b, c) => {
+                                    rasterize_triangle(&args, pipeline, &blend, &fragment_shader, id, a, b, c)
                                 }
-                            }
-
-                            for triangle in generated_primitives.tris.chunks(3) {
-                                rasterize_triangle(&args, pipeline, &blend, &fragment_shader, &triangle[0], &triangle[1], &triangle[2]);
-                            }
-
-                            if T::is_line() {
-                                if let Some(ref indexed_vertices) = *indexed_vertices {
-                                    for line in mesh.indices.chunks(2) {
-                                        let start = &indexed_vertices[line[0]];
-                                        let end = &indexed_vertices[line[1]];
-
-                                        rasterize_line(&args, pipeline, &blend, &fragment_shader, start, end);
-                                    }
+                                BinnedPrimitive::Line(start, end) => {
+                                    rasterize_line(&args, pipeline, &blend, &fragment_shader, id, start, end)
                                 }
-                            }
-
-                            for line in generated_primitives.lines.chunks(2) {
-                                rasterize_line(&args, pipeline, &blend, &fragment_shader, &line[0], &line[1]);
-                            }
-
-                            if T::is_point() {
-                                if let Some(ref indexed_vertices) = *indexed_vertices {
-                                    for index in &mesh.indices {
-                                        let point = &indexed_vertices[*index];
-
-                                        rasterize_point(&args, pipeline, &blend, &fragment_shader, point);
-                                    }
+                                BinnedPrimitive::Point(point) => {
+                                    rasterize_point(&args, pipeline, &blend, &fragment_shader, id, point)
                                 }
-                            }
-
-                            for point in &generated_primitives.points {
-                                rasterize_point(&args, pipeline, &blend, &fragment_shader, point);
-                            }
+                            });
                         } else {
                             break;
                         }
@@ -10,11 +10,12 @@ use ::parallel::{TrustedThreadSafe, CACHE_LINE_SIZE, Mapper};
 
 use ::primitive::{Primitive, PrimitiveRef, Point, Line, Triangle};
 use ::mesh::{Vertex, Mesh};
-use ::geometry::{ClipVertex, Viewport, ScreenVertex, ALL_CLIPPING_PLANES, ClippingPlane};
+use ::geometry::{ClipVertex, Viewport, ScreenVertex, FaceWinding, ALL_CLIPPING_PLANES, ClippingPlane};
 use ::interpolate::Interpolate;
+use ::color::ColorMask;
 use ::pipeline::storage::{PrimitiveStorage, SeparablePrimitiveStorage, SeparableScreenPrimitiveStorage};
 use ::pipeline::{PipelineObject, FragmentShader};
-use ::pipeline::stages::fragment::DEFAULT_TILE_SIZE;
+use ::pipeline::stages::fragment::{DEFAULT_TILE_SIZE, DEFAULT_DEPTH_TEST, DepthBias};
 
 use ::pipeline::types::{PipelineUniforms, StencilValue};
 
@@ -122,8 +123,15 @@ impl<'a, P: 'a, V, T, K> GeometryShader<'a, P, V, T, K> where P: PipelineObject,
             indexed_vertices: Arc::new(indexed_screen_vertices),
             generated_primitives: Arc::new(generated_primitives),
             cull_faces: None,
+            front_face: FaceWinding::Clockwise,
             blend: (),
             antialiased_lines: false,
+            perspective_correct: true,
+            depth_test: Some(DEFAULT_DEPTH_TEST),
+            depth_write: true,
+            depth_bias: DepthBias::default(),
+            scissor: None,
+            color_mask: ColorMask::all(),
             tile_size: DEFAULT_TILE_SIZE,
         }
     }
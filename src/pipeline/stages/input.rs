@@ -0,0 +1,247 @@
+//! Fragment shader inputs
+
+use std::ops::{Deref, Sub};
+
+use num_traits::Float;
+use nalgebra::Vector3;
+
+use ::numeric::FloatScalar;
+use ::geometry::{Coordinate, ScreenVertex};
+
+#[cfg(feature = "packet_shaders")]
+use ::numeric::packet::Packet;
+
+/// Primitive and position of a 2x2 pixel quad being shaded, which is shared by every fragment of the quad
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QuadContext<N: FloatScalar> {
+    /// Top-left pixel of the quad
+    pub ( in ::pipeline) origin: Coordinate,
+    /// Position of the primitive within the draw
+    pub ( in ::pipeline) primitive_id: usize,
+    pub ( in ::pipeline) front_facing: bool,
+    /// Screen-space barycentric coordinates of each lane
+    pub ( in ::pipeline) barycentrics: [Vector3<N>; 4],
+}
+
+impl<N: FloatScalar> QuadContext<N> {
+    #[inline]
+    pub ( in ::pipeline) fn new(origin: Coordinate, primitive_id: usize, front_facing: bool, barycentrics: [Vector3<N>; 4]) -> QuadContext<N> {
+        QuadContext { origin, primitive_id, front_facing, barycentrics }
+    }
+
+    /// Pixel coordinate of the given lane
+    #[inline]
+    pub ( in ::pipeline) fn pixel(&self, lane: usize) -> Coordinate {
+        Coordinate::new(self.origin.x + (lane as u32 & 1), self.origin.y + (lane as u32 >> 1))
+    }
+}
+
+/// Input to a fragment shader, containing the interpolated screen-space vertex of the fragment
+/// along with the rest of its 2x2 pixel quad, which is used to compute screen-space derivatives.
+///
+/// Pixels in the quad that are not covered by the primitive are still interpolated as "helper" pixels,
+/// so derivatives are available right up to the edges of primitives, but they are never shaded themselves.
+///
+/// `FragmentInput` dereferences to the `ScreenVertex` of the fragment being shaded.
+pub struct FragmentInput<'a, N: FloatScalar + 'a, K: 'a> {
+    quad: [&'a ScreenVertex<N, K>; 4],
+    context: &'a QuadContext<N>,
+    lane: usize,
+    coverage: u32,
+}
+
+impl<'a, N: FloatScalar + 'a, K: 'a> Clone for FragmentInput<'a, N, K> {
+    fn clone(&self) -> FragmentInput<'a, N, K> {
+        FragmentInput { ..*self }
+    }
+}
+
+impl<'a, N: FloatScalar + 'a, K: 'a> Copy for FragmentInput<'a, N, K> {}
+
+impl<'a, N: FloatScalar + 'a, K: 'a> Deref for FragmentInput<'a, N, K> {
+    type Target = ScreenVertex<N, K>;
+
+    #[inline]
+    fn deref(&self) -> &ScreenVertex<N, K> { self.quad[self.lane] }
+}
+
+impl<'a, N: FloatScalar + 'a, K: 'a> FragmentInput<'a, N, K> {
+    #[inline]
+    pub ( in ::pipeline) fn new(quad: [&'a ScreenVertex<N, K>; 4], context: &'a QuadContext<N>, lane: usize, coverage: u32) -> FragmentInput<'a, N, K> {
+        debug_assert!(lane < 4);
+
+        FragmentInput { quad, context, lane, coverage }
+    }
+
+    /// The interpolated screen-space vertex of the fragment being shaded
+    #[inline]
+    pub fn vertex(&self) -> &'a ScreenVertex<N, K> { self.quad[self.lane] }
+
+    /// Position of the fragment within its quad, in the order top-left, top-right, bottom-left, bottom-right.
+    #[inline]
+    pub fn lane(&self) -> usize { self.lane }
+
+    /// Mask of the samples of the pixel covered by the fragment that passed the depth and stencil tests,
+    /// similar to `gl_SampleMaskIn` in GLSL. Only the first bit is used for framebuffers that aren't multisampled.
+    ///
+    /// When the fragment shader returns a `FragmentOutput`, the tests run after shading, so this is every covered sample.
+    #[inline]
+    pub fn coverage(&self) -> u32 { self.coverage }
+
+    /// Integer coordinate of the pixel being shaded, similar to `gl_FragCoord.xy` in GLSL without the half-pixel offset.
+    #[inline]
+    pub fn pixel(&self) -> Coordinate { self.context.pixel(self.lane) }
+
+    /// Whether the primitive is a front-facing triangle, similar to `gl_FrontFacing` in GLSL.
+    ///
+    /// Front faces are given by the winding order set with `FragmentShader::front_face`,
+    /// and lines and points are always front-facing.
+    #[inline]
+    pub fn front_facing(&self) -> bool { self.context.front_facing }
+
+    /// Position of the primitive within the draw, similar to `gl_PrimitiveID` in GLSL.
+    ///
+    /// Primitives are numbered in the order they are rasterized, which is triangles, then lines, then points,
+    /// with the primitives of the mesh before those generated by the geometry shader for each kind.
+    #[inline]
+    pub fn primitive_id(&self) -> usize { self.context.primitive_id }
+
+    /// Screen-space barycentric coordinates of the fragment within its triangle, without perspective correction,
+    /// which are the weights of the first, second and third vertex. These always sum to one.
+    ///
+    /// Helper pixels and samples outside of the primitive are clamped onto it, so these are never negative.
+    ///
+    /// Lines only weight their first and second vertex, and points only their single vertex.
+    #[inline]
+    pub fn barycentric(&self) -> Vector3<N> { self.context.barycentrics[self.lane] }
+
+    /// Returns the interpolated screen-space vertex of the given lane in the quad, which may be a helper pixel.
+    #[inline]
+    pub fn quad_vertex(&self, lane: usize) -> &'a ScreenVertex<N, K> { self.quad[lane] }
+
+    /// Computes the derivative of a value along the screen-space x axis, similar to `dFdx` in GLSL.
+    ///
+    /// The value is computed from the vertices of both pixels in the same row of the quad,
+    /// and the derivative is the difference between them.
+    #[inline]
+    pub fn dfdx<T, F>(&self, f: F) -> T where F: Fn(&ScreenVertex<N, K>) -> T, T: Sub<Output = T> {
+        let row = self.lane & 2;
+
+        f(self.quad[row + 1]) - f(self.quad[row])
+    }
+
+    /// Computes the derivative of a value along the screen-space y axis, similar to `dFdy` in GLSL.
+    ///
+    /// The value is computed from the vertices of both pixels in the same column of the quad,
+    /// and the derivative is the difference between them.
+    #[inline]
+    pub fn dfdy<T, F>(&self, f: F) -> T where F: Fn(&ScreenVertex<N, K>) -> T, T: Sub<Output = T> {
+        let column = self.lane & 1;
+
+        f(self.quad[column + 2]) - f(self.quad[column])
+    }
+
+    /// Computes the sum of the absolute derivatives of a scalar value along both screen-space axes,
+    /// similar to `fwidth` in GLSL.
+    #[inline]
+    pub fn fwidth<T, F>(&self, f: F) -> T where F: Fn(&ScreenVertex<N, K>) -> T, T: Float {
+        self.dfdx(&f).abs() + self.dfdy(&f).abs()
+    }
+}
+
+/// Input to a packet fragment shader, containing the interpolated screen-space vertices of all four fragments
+/// in a 2x2 pixel quad, which are shaded together.
+///
+/// Lanes are in the order top-left, top-right, bottom-left, bottom-right, matching the lanes of a `Packet`.
+/// Lanes with zero coverage are helper pixels, whose outputs are ignored.
+///
+/// Only available with the `packet_shaders` cargo feature.
+#[cfg(feature = "packet_shaders")]
+pub struct FragmentPacket<'a, N: FloatScalar + 'a, K: 'a> {
+    quad: [&'a ScreenVertex<N, K>; 4],
+    context: &'a QuadContext<N>,
+    coverage: [u32; 4],
+}
+
+#[cfg(feature = "packet_shaders")]
+impl<'a, N: FloatScalar + 'a, K: 'a> FragmentPacket<'a, N, K> {
+    #[inline]
+    pub ( in ::pipeline) fn new(quad: [&'a ScreenVertex<N, K>; 4], context: &'a QuadContext<N>, coverage: [u32; 4]) -> FragmentPacket<'a, N, K> {
+        FragmentPacket { quad, context, coverage }
+    }
+
+    /// Returns the interpolated screen-space vertex of the given lane
+    #[inline]
+    pub fn vertex(&self, lane: usize) -> &'a ScreenVertex<N, K> { self.quad[lane] }
+
+    /// Mask of the samples of the given lane that passed the depth and stencil tests, as with `FragmentInput::coverage`,
+    /// which is zero for helper pixels.
+    #[inline]
+    pub fn coverage(&self, lane: usize) -> u32 { self.coverage[lane] }
+
+    /// Integer coordinate of the pixel of the given lane, as with `FragmentInput::pixel`
+    #[inline]
+    pub fn pixel(&self, lane: usize) -> Coordinate { self.context.pixel(lane) }
+
+    /// Whether the primitive is a front-facing triangle, as with `FragmentInput::front_facing`
+    #[inline]
+    pub fn front_facing(&self) -> bool { self.context.front_facing }
+
+    /// Position of the primitive within the draw, as with `FragmentInput::primitive_id`
+    #[inline]
+    pub fn primitive_id(&self) -> usize { self.context.primitive_id }
+
+    /// Screen-space barycentric coordinates of every lane, as with `FragmentInput::barycentric`
+    #[inline]
+    pub fn barycentric(&self) -> Packet<Vector3<N>> { Packet(self.context.barycentrics) }
+
+    /// Bitmask of the lanes that are shaded, with the first lane in the lowest bit
+    #[inline]
+    pub fn active(&self) -> u32 {
+        Packet(self.coverage).mask(|coverage| coverage != 0)
+    }
+
+    /// Returns the `FragmentInput` of the given lane, for computing screen-space derivatives
+    #[inline]
+    pub fn input(&self, lane: usize) -> FragmentInput<'a, N, K> {
+        FragmentInput::new(self.quad, self.context, lane, self.coverage[lane])
+    }
+
+    /// Computes a value from the vertex of every lane, returning them as a packet
+    #[inline(always)]
+    pub fn gather<T: Copy, F>(&self, f: F) -> Packet<T> where F: Fn(&ScreenVertex<N, K>) -> T {
+        Packet::generate(|lane| f(self.quad[lane]))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use nalgebra::{Vector3, Vector4};
+
+    use ::geometry::{Coordinate, ScreenVertex};
+
+    use super::*;
+
+    #[test]
+    fn test_derivatives() {
+        // Quad where the uniform increases by 1 per pixel along x and 4 per pixel along y
+        let quad: Vec<_> = (0..4).map(|i| ScreenVertex {
+            position: Vector4::new((i & 1) as f32, (i >> 1) as f32, 0.0, 1.0),
+            uniforms: (i & 1) as f32 + (i >> 1) as f32 * 4.0,
+        }).collect();
+
+        let context = QuadContext::new(Coordinate::new(6, 2), 3, true, [Vector3::new(1.0, 0.0, 0.0); 4]);
+
+        for lane in 0..4 {
+            let input = FragmentInput::new([&quad[0], &quad[1], &quad[2], &quad[3]], &context, lane, 1);
+
+            assert_eq!(input.uniforms, quad[lane].uniforms);
+
+            assert_eq!(input.dfdx(|v| v.uniforms), 1.0);
+            assert_eq!(input.dfdy(|v| v.uniforms), 4.0);
+            assert_eq!(input.fwidth(|v| -v.uniforms), 5.0);
+
+            assert_eq!(input.pixel(), Coordinate::new(6 + (lane as u32 & 1), 2 + (lane as u32 >> 1)));
+        }
+    }
+}
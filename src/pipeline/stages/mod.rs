@@ -5,7 +5,9 @@ pub mod rasterization;
 pub mod vertex;
 pub mod geometry;
 pub mod fragment;
+pub mod input;
 
 pub use self::vertex::VertexShader;
 pub use self::geometry::GeometryShader;
-pub use self::fragment::FragmentShader;
\ No newline at end of file
+pub use self::fragment::FragmentShader;
+pub use self::input::FragmentInput;
\ No newline at end of file
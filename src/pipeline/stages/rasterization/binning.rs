@@ -0,0 +1,418 @@
+//! Binning of screen-space primitives into the tiles they overlap
+//!
+//! Before rasterization, every primitive is sorted into a list for each tile its bounding box overlaps,
+//! so rasterizing a tile only visits the primitives that can actually cover any of its pixels.
+
+use std::cmp::min;
+
+use num_traits::cast;
+
+use ::numeric::FloatScalar;
+use ::geometry::{Dimensions, Coordinate, ScreenVertex, Scissor};
+use ::pipeline::storage::SeparableScreenPrimitiveStorage;
+
+/// Non-overlapping tiles covering the entire framebuffer, stored in row-major order.
+///
+/// Each tile is given by its inclusive top-left and bottom-right pixels.
+/// Tiles outside the scissor rectangle are `None`, and tiles partially inside it are shrunk to fit.
+pub struct Tiles {
+    tile_size: Dimensions,
+    columns: u32,
+    tiles: Vec<Option<(Coordinate, Coordinate)>>,
+}
+
+impl Tiles {
+    pub fn new(dimensions: Dimensions, tile_size: Dimensions, scissor: Option<Scissor>) -> Tiles {
+        // Empty tiles would never cover anything
+        let tile_size = Dimensions::new(tile_size.width.max(1), tile_size.height.max(1));
+
+        let columns = (dimensions.width + tile_size.width - 1) / tile_size.width;
+        let rows = (dimensions.height + tile_size.height - 1) / tile_size.height;
+
+        let mut tiles = Vec::with_capacity(columns as usize * rows as usize);
+
+        for row in 0..rows {
+            let y = row * tile_size.height;
+
+            for column in 0..columns {
+                let x = column * tile_size.width;
+
+                let tile = (Coordinate::new(x, y),
+                            Coordinate::new(min(x + tile_size.width, dimensions.width) - 1,
+                                            min(y + tile_size.height, dimensions.height) - 1));
+
+                // Only keep the part of each tile within the scissor rectangle
+                tiles.push(match scissor {
+                    Some(scissor) => scissor.intersect(tile),
+                    None => Some(tile),
+                });
+            }
+        }
+
+        Tiles { tile_size, columns, tiles }
+    }
+
+    /// Total number of tiles, including those outside the scissor rectangle
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+
+    /// Returns the bounds of the tile at the given index, or `None` if it's outside the scissor rectangle
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<(Coordinate, Coordinate)> {
+        self.tiles[index]
+    }
+
+    /// Calls `f` with the index of every tile overlapping the given inclusive range of pixels
+    pub fn overlapping<F>(&self, start: Coordinate, end: Coordinate, mut f: F) where F: FnMut(usize) {
+        for row in (start.y / self.tile_size.height)..(end.y / self.tile_size.height + 1) {
+            for column in (start.x / self.tile_size.width)..(end.x / self.tile_size.width + 1) {
+                let index = row as usize * self.columns as usize + column as usize;
+
+                if self.tiles[index].is_some() {
+                    f(index);
+                }
+            }
+        }
+    }
+}
+
+/// Reference to a screen-space primitive sorted into a tile
+pub enum BinnedPrimitive<'a, N: FloatScalar + 'a, K: 'a> {
+    Point(&'a ScreenVertex<N, K>),
+    Line(&'a ScreenVertex<N, K>, &'a ScreenVertex<N, K>),
+    Triangle(&'a ScreenVertex<N, K>, &'a ScreenVertex<N, K>, &'a ScreenVertex<N, K>),
+}
+
+impl<'a, N: FloatScalar + 'a, K: 'a> Clone for BinnedPrimitive<'a, N, K> {
+    fn clone(&self) -> Self { *self }
+}
+
+impl<'a, N: FloatScalar + 'a, K: 'a> Copy for BinnedPrimitive<'a, N, K> {}
+
+/// Every screen-space primitive of a draw, in the order they are rasterized:
+/// triangles, then lines, then points, with indexed primitives before generated primitives of each kind.
+pub struct Primitives<'a, N: FloatScalar + 'a, K: 'a> {
+    indexed_vertices: &'a [ScreenVertex<N, K>],
+    indices: &'a [usize],
+    /// Number of vertices of each indexed primitive
+    indexed_vertices_per_primitive: usize,
+    generated: &'a SeparableScreenPrimitiveStorage<N, K>,
+}
+
+impl<'a, N: FloatScalar + 'a, K: 'a> Primitives<'a, N, K> {
+    pub fn new(indexed_vertices: Option<&'a [ScreenVertex<N, K>]>,
+               indices: &'a [usize],
+               indexed_vertices_per_primitive: usize,
+               generated: &'a SeparableScreenPrimitiveStorage<N, K>) -> Primitives<'a, N, K> {
+        match indexed_vertices {
+            Some(indexed_vertices) => Primitives { indexed_vertices, indices, indexed_vertices_per_primitive, generated },
+            None => Primitives { indexed_vertices: &[], indices: &[], indexed_vertices_per_primitive, generated },
+        }
+    }
+
+    /// Number of indexed and generated primitives with the given number of vertices
+    #[inline]
+    fn counts(&self, vertices: usize) -> (usize, usize) {
+        let indexed = if self.indexed_vertices_per_primitive == vertices { self.indices.len() / vertices } else { 0 };
+
+        let generated = match vertices {
+            3 => self.generated.tris.len() / 3,
+            2 => self.generated.lines.len() / 2,
+            _ => self.generated.points.len(),
+        };
+
+        (indexed, generated)
+    }
+
+    pub fn len(&self) -> usize {
+        (1..4).map(|vertices| {
+            let (indexed, generated) = self.counts(vertices);
+            indexed + generated
+        }).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the primitive at the given position in rasterization order
+    pub fn get(&self, mut index: usize) -> BinnedPrimitive<'a, N, K> {
+        let Primitives { indexed_vertices: vertices, indices, generated, .. } = *self;
+
+        for kind in (1..4).rev() {
+            let (indexed, generated_count) = self.counts(kind);
+
+            if index < indexed {
+                let i = &indices[index * kind..];
+
+                return match kind {
+                    3 => BinnedPrimitive::Triangle(&vertices[i[0]], &vertices[i[1]], &vertices[i[2]]),
+                    2 => BinnedPrimitive::Line(&vertices[i[0]], &vertices[i[1]]),
+                    _ => BinnedPrimitive::Point(&vertices[i[0]]),
+                };
+            }
+
+            index -= indexed;
+
+            if index < generated_count {
+                return match kind {
+                    3 => BinnedPrimitive::Triangle(&generated.tris[index * 3], &generated.tris[index * 3 + 1], &generated.tris[index * 3 + 2]),
+                    2 => BinnedPrimitive::Line(&generated.lines[index * 2], &generated.lines[index * 2 + 1]),
+                    _ => BinnedPrimitive::Point(&generated.points[index]),
+                };
+            }
+
+            index -= generated_count;
+        }
+
+        panic!("Primitive index out of bounds");
+    }
+}
+
+/// Returns the inclusive range of pixels from `start` to `end` expanded by `margin` pixels,
+/// clamped to the framebuffer, or `None` if it's entirely outside of it.
+fn pixel_range<N: FloatScalar>(start: N, end: N, margin: N, limit: u32) -> Option<(u32, u32)> {
+    let (start, end) = (start - margin, end + margin);
+
+    // Also rejects NaN
+    if !(start <= end) || end < N::zero() || start >= cast(limit).unwrap() {
+        return None;
+    }
+
+    let start = if start < N::zero() { 0 } else { cast(start).unwrap() };
+    let end = if end >= cast(limit).unwrap() { limit - 1 } else { cast(end).unwrap() };
+
+    Some((start, end))
+}
+
+/// Returns the inclusive range of pixels a primitive can cover, or `None` if it's entirely outside the framebuffer.
+pub fn bounding_box<'a, N: FloatScalar, K>(primitive: BinnedPrimitive<'a, N, K>, dimensions: Dimensions) -> Option<(Coordinate, Coordinate)> {
+    let (min_x, min_y, max_x, max_y, margin) = match primitive {
+        BinnedPrimitive::Point(point) => {
+            let (x, y) = (point.position.x, point.position.y);
+
+            (x, y, x, y, N::zero())
+        }
+        BinnedPrimitive::Line(start, end) => {
+            let (x1, y1, x2, y2) = (start.position.x, start.position.y, end.position.x, end.position.y);
+
+            // Anti-aliased lines can cover pixels next to them
+            (x1.min(x2), y1.min(y2), x1.max(x2), y1.max(y2), N::one())
+        }
+        BinnedPrimitive::Triangle(a, b, c) => {
+            let (x1, y1, x2, y2, x3, y3) = (a.position.x, a.position.y, b.position.x, b.position.y, c.position.x, c.position.y);
+
+            // Multisampled and jittered sample positions can be up to half a pixel from the pixel center
+            (x1.min(x2).min(x3), y1.min(y2).min(y3), x1.max(x2).max(x3), y1.max(y2).max(y3), N::one())
+        }
+    };
+
+    match (pixel_range(min_x, max_x, margin, dimensions.width), pixel_range(min_y, max_y, margin, dimensions.height)) {
+        (Some((x1, x2)), Some((y1, y2))) => Some((Coordinate::new(x1, y1), Coordinate::new(x2, y2))),
+        _ => None,
+    }
+}
+
+/// Lists of primitives overlapping each tile, along with their position in rasterization order.
+///
+/// Primitives are binned in parallel by splitting them into contiguous ranges, one per thread,
+/// so each thread fills its own list for every tile and no synchronization is needed.
+/// Iterating over the lists of a tile in thread order preserves the original order of primitives.
+pub struct Bins<'a, N: FloatScalar + 'a, K: 'a> {
+    bins: Vec<Vec<Vec<(usize, BinnedPrimitive<'a, N, K>)>>>,
+}
+
+impl<'a, N: FloatScalar + 'a, K: Send + Sync + 'a> Bins<'a, N, K> {
+    /// Creates empty bins for the given number of threads
+    pub fn new(tiles: &Tiles, threads: usize) -> Bins<'a, N, K> {
+        Bins {
+            bins: (0..threads).map(|_| (0..tiles.len()).map(|_| Vec::new()).collect()).collect()
+        }
+    }
+
+    /// Returns the bins of each thread, along with the range of primitives it should bin
+    pub fn split(&mut self, primitives: usize) -> Vec<(&mut Vec<Vec<(usize, BinnedPrimitive<'a, N, K>)>>, usize, usize)> {
+        let threads = self.bins.len();
+
+        self.bins.iter_mut().enumerate().map(|(thread, bins)| {
+            (bins, thread * primitives / threads, (thread + 1) * primitives / threads)
+        }).collect()
+    }
+
+    /// Calls `f` with the position and primitive of every primitive overlapping the tile at the given index,
+    /// in their original order
+    #[inline]
+    pub fn for_each<F>(&self, tile: usize, mut f: F) where F: FnMut(usize, BinnedPrimitive<'a, N, K>) {
+        for bins in &self.bins {
+            for &(index, primitive) in &bins[tile] {
+                f(index, primitive);
+            }
+        }
+    }
+}
+
+/// Sorts the given range of primitives into the bins of every tile they overlap
+pub fn bin_primitives<'a, N, K>(primitives: &Primitives<'a, N, K>,
+                                range: (usize, usize),
+                                tiles: &Tiles,
+                                dimensions: Dimensions,
+                                bins: &mut Vec<Vec<(usize, BinnedPrimitive<'a, N, K>)>>) where N: FloatScalar {
+    for index in range.0..range.1 {
+        let primitive = primitives.get(index);
+
+        if let Some((start, end)) = bounding_box(primitive, dimensions) {
+            tiles.overlapping(start, end, |tile| bins[tile].push((index, primitive)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use nalgebra::Vector4;
+
+    use ::geometry::{Dimensions, Coordinate, ScreenVertex, Scissor};
+    use ::pipeline::storage::SeparableScreenPrimitiveStorage;
+
+    use super::*;
+
+    #[test]
+    fn test_tiles() {
+        let tiles = Tiles::new(Dimensions::new(10, 7), Dimensions::new(4, 4), None);
+
+        assert_eq!(tiles.len(), 6);
+
+        // Tiles cover every pixel exactly once
+        let mut covered = vec![0; 70];
+
+        for i in 0..tiles.len() {
+            let (start, end) = tiles.get(i).unwrap();
+
+            for y in start.y..end.y + 1 {
+                for x in start.x..end.x + 1 {
+                    covered[y as usize * 10 + x as usize] += 1;
+                }
+            }
+        }
+
+        assert!(covered.iter().all(|&c| c == 1));
+
+        assert_eq!(tiles.get(5), Some((Coordinate::new(8, 4), Coordinate::new(9, 6))));
+
+        // Single rows and columns of pixels still have tiles
+        assert_eq!(Tiles::new(Dimensions::new(1, 1), Dimensions::new(4, 4), None).get(0),
+                   Some((Coordinate::new(0, 0), Coordinate::new(0, 0))));
+
+        let tiles = Tiles::new(Dimensions::new(10, 7), Dimensions::new(4, 4), Some(Scissor::new(Dimensions::new(2, 2), Coordinate::new(3, 3))));
+
+        assert_eq!(tiles.get(0), Some((Coordinate::new(3, 3), Coordinate::new(3, 3))));
+        assert_eq!(tiles.get(2), None);
+        assert_eq!(tiles.get(4), Some((Coordinate::new(4, 4), Coordinate::new(4, 4))));
+
+        let mut overlapping = Vec::new();
+
+        tiles.overlapping(Coordinate::new(0, 0), Coordinate::new(9, 6), |tile| overlapping.push(tile));
+
+        assert_eq!(overlapping, vec![0, 1, 3, 4]);
+    }
+
+    #[test]
+    fn test_binning() {
+        let vertex = |x: f32, y: f32| ScreenVertex { position: Vector4::new(x, y, 0.0, 1.0), uniforms: () };
+
+        let indexed_vertices = vec![vertex(0.5, 0.5), vertex(2.5, 0.5), vertex(0.5, 2.5), vertex(13.5, 13.5)];
+
+        let mut generated = SeparableScreenPrimitiveStorage::default();
+
+        generated.tris.extend(vec![vertex(-4.0, -4.0), vertex(20.0, -4.0), vertex(-4.0, 20.0)]);
+        generated.points.push(vertex(12.5, 3.5));
+        generated.points.push(vertex(-1.0, 3.5));
+
+        let primitives = Primitives::new(Some(&indexed_vertices), &[0, 1, 2, 3, 1, 2], 3, &generated);
+
+        assert_eq!(primitives.len(), 5);
+
+        let dimensions = Dimensions::new(16, 16);
+        let tiles = Tiles::new(dimensions, Dimensions::new(8, 8), None);
+
+        let mut bins = Bins::new(&tiles, 2);
+
+        for (bins, start, end) in bins.split(primitives.len()) {
+            bin_primitives(&primitives, (start, end), &tiles, dimensions, bins);
+        }
+
+        let count = |tile| {
+            let mut count = 0;
+            bins.for_each(tile, |_, _| count += 1);
+            count
+        };
+
+        // The large generated triangle covers every tile, the small indexed triangle only the first tile,
+        // the other indexed triangle every tile, and the point on screen only the second tile.
+        assert_eq!(count(0), 3);
+        assert_eq!(count(1), 3);
+        assert_eq!(count(2), 2);
+        assert_eq!(count(3), 2);
+
+        // Primitives keep their order
+        let mut order = Vec::new();
+
+        bins.for_each(1, |index, primitive| order.push((index, match primitive {
+            BinnedPrimitive::Triangle(a, ..) => a.position.x,
+            BinnedPrimitive::Line(a, _) => a.position.x,
+            BinnedPrimitive::Point(a) => a.position.x,
+        })));
+
+        assert_eq!(order, vec![(1, 13.5), (2, -4.0), (3, 12.5)]);
+    }
+
+    #[test]
+    fn test_tiled_rendering() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        use nalgebra::Point3;
+
+        use ::framebuffer::RenderBuffer;
+        use ::attachments::predefined::ColorDepthAttachments;
+        use ::primitive::{Primitive, Triangle, Line};
+        use ::mesh::{Mesh, SimpleVertex};
+        use ::pipeline::Pipeline;
+        use ::pipeline::stages::fragment::Fragment;
+        use ::pipeline::stages::rasterization::test::{fullscreen_quad, draw};
+
+        let dimensions = Dimensions::new(16, 16);
+
+        // Counts how many times the fragment shader runs with tiles that don't evenly divide the framebuffer
+        fn invocations<T: Primitive>(primitive: T, mesh: Mesh<SimpleVertex<f32, ()>>) -> usize {
+            let mut pipeline: Pipeline<_, RenderBuffer<ColorDepthAttachments<Vector4<f32>, f32>>> =
+                Pipeline::from_framebuffer(RenderBuffer::with_dimensions(Dimensions::new(16, 16)), ());
+
+            let count = AtomicUsize::new(0);
+
+            draw(&mut pipeline, primitive, Arc::new(mesh), None)
+                .with_tile_size(Dimensions::new(3, 5))
+                .run(|_, _| {
+                    count.fetch_add(1, Ordering::Relaxed);
+
+                    Fragment::Color(Vector4::new(1.0, 1.0, 1.0, 1.0))
+                });
+
+            count.load(Ordering::Relaxed)
+        }
+
+        // Every pixel is shaded exactly once, even on the edges between tiles
+        assert_eq!(invocations(Triangle, fullscreen_quad(|_, _| 0.0)), dimensions.area());
+
+        let vertex = |x: f32, y: f32| SimpleVertex { position: Point3::new(x, y, 0.0), data: () };
+
+        // Horizontal line through the centers of the fifth row of pixels
+        assert_eq!(invocations(Line, Mesh { vertices: vec![vertex(-15.0 / 16.0, 7.0 / 16.0), vertex(15.0 / 16.0, 7.0 / 16.0)], indices: vec![0, 1] }), 16);
+    }
+}
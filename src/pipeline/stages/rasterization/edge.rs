@@ -0,0 +1,127 @@
+//! Fixed-point edge functions for watertight triangle rasterization
+
+use std::cmp::{min, max};
+
+use num_traits::cast;
+
+use ::numeric::FloatScalar;
+
+#[cfg(feature = "packet_shaders")]
+use ::numeric::packet::Packet;
+
+/// Number of fractional bits screen-space vertex positions are snapped to
+pub const SUBPIXEL_BITS: u32 = 8;
+
+/// Size of one pixel in fixed-point units
+pub const SUBPIXEL_SCALE: i64 = 1 << SUBPIXEL_BITS;
+
+/// Largest magnitude of a snapped coordinate, which keeps edge function products within an `i64`.
+///
+/// This is about two million pixels in either direction.
+const MAX_FIXED: i64 = 1 << 29;
+
+/// Largest magnitude of a screen-space coordinate that triangles are rasterized at without clipping.
+///
+/// This is half of the range of snapped coordinates, so rounding errors from clipping can't push vertices out of it.
+pub const GUARD_BAND: f64 = (MAX_FIXED / SUBPIXEL_SCALE / 2) as f64;
+
+/// Snaps a screen-space coordinate to fixed-point, or returns `None` if it lies outside of the fixed-point range.
+#[inline]
+pub fn snap<N: FloatScalar>(value: N) -> Option<i64> {
+    let scaled = (value * N::from(SUBPIXEL_SCALE).unwrap()).round();
+
+    match cast::<_, i64>(scaled) {
+        Some(fixed) if -MAX_FIXED <= fixed && fixed <= MAX_FIXED => Some(fixed),
+        _ => None,
+    }
+}
+
+/// Returns the fixed-point position of the center of a pixel
+#[inline]
+pub fn pixel_center(x: u32, y: u32) -> (i64, i64) {
+    (x as i64 * SUBPIXEL_SCALE + SUBPIXEL_SCALE / 2,
+     y as i64 * SUBPIXEL_SCALE + SUBPIXEL_SCALE / 2)
+}
+
+/// Edge function of a triangle edge in fixed-point, oriented so that it is positive inside the triangle.
+///
+/// Points exactly on the edge are only considered inside for top and left edges,
+/// so pixels on an edge shared by two triangles are only covered by one of them.
+#[derive(Debug, Clone, Copy)]
+pub struct EdgeFunction {
+    a: i64,
+    b: i64,
+    c: i64,
+    top_left: bool,
+}
+
+impl EdgeFunction {
+    /// Creates the edge function for the edge from `p0` to `p1`.
+    ///
+    /// `orientation` is the sign of the area of the triangle, which is used to flip the edge
+    /// so that the inside is always positive, regardless of winding order.
+    pub fn new(p0: (i64, i64), p1: (i64, i64), orientation: i64) -> EdgeFunction {
+        let dx = (p1.0 - p0.0) * orientation;
+        let dy = (p1.1 - p0.1) * orientation;
+
+        EdgeFunction {
+            a: -dy,
+            b: dx,
+            c: dy * p0.0 - dx * p0.1,
+            // With y pointing down, left edges go up and top edges go right
+            top_left: dy < 0 || (dy == 0 && dx > 0),
+        }
+    }
+
+    /// Evaluates the edge function at the given fixed-point position,
+    /// which is twice the signed area of the triangle formed by the edge and the position.
+    #[inline]
+    pub fn evaluate(&self, x: i64, y: i64) -> i64 {
+        self.a * x + self.b * y + self.c
+    }
+
+    /// Change in value between horizontally adjacent pixels
+    #[inline]
+    pub fn step_x(&self) -> i64 {
+        self.a * SUBPIXEL_SCALE
+    }
+
+    /// Change in value between vertically adjacent pixels
+    #[inline]
+    pub fn step_y(&self) -> i64 {
+        self.b * SUBPIXEL_SCALE
+    }
+
+    /// Change in value between two positions offset by the given fixed-point distance
+    #[inline]
+    pub fn delta(&self, dx: i64, dy: i64) -> i64 {
+        self.a * dx + self.b * dy
+    }
+
+    /// Given the value at the center of the top-left pixel of a square block of pixels,
+    /// returns the lowest and highest values at all positions in the block within `margin` of a pixel center.
+    #[inline]
+    pub fn block_range(&self, value: i64, size: u32, margin: i64) -> (i64, i64) {
+        let n = size as i64 - 1;
+
+        let (dx, dy) = (self.step_x() * n, self.step_y() * n);
+
+        let margin = (self.a.abs() + self.b.abs()) * margin;
+
+        (value + min(dx, 0) + min(dy, 0) - margin, value + max(dx, 0) + max(dy, 0) + margin)
+    }
+
+    /// Tests whether the result of `evaluate` is inside of the edge, according to the top-left fill rule.
+    #[inline]
+    pub fn test(&self, value: i64) -> bool {
+        if self.top_left { value >= 0 } else { value > 0 }
+    }
+
+    /// Tests a packet of values at once, returning a bitmask of the lanes inside of the edge.
+    #[cfg(feature = "packet_shaders")]
+    #[inline(always)]
+    pub fn test_packet(&self, values: Packet<i64>) -> u32 {
+        // Values exactly on top-left edges are inside, which is the same as testing against -1
+        values.gt(Packet::splat(if self.top_left { -1 } else { 0 }))
+    }
+}
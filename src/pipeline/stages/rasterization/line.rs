@@ -1,119 +1,150 @@
 use super::RasterArguments;
 
-use num_traits::{Float, Zero, NumCast, cast};
+use num_traits::{Float, Zero, One, NumCast, cast};
+use nalgebra::Vector3;
 use nalgebra::coordinates::XYZW;
 
 use ::color::{Color, ColorAlpha};
 use ::color::blend::Blend;
-use ::pixels::{PixelRead, PixelWrite};
-use ::framebuffer::UnsafeFramebuffer;
-use ::attachments::depth::Depth;
+use ::framebuffer::FramebufferBase;
 use ::mesh::{Vertex, Mesh};
 use ::geometry::{HasDimensions, Coordinate, ScreenVertex, FaceWinding};
 use ::interpolate::Interpolate;
 
 use ::pipeline::PipelineObject;
 
-use ::framebuffer::types::DepthAttachment;
-use ::pipeline::types::{PipelineUniforms, Pixel};
+use ::pipeline::types::Pixel;
 
-use ::pipeline::stages::fragment::Fragment;
+use ::pipeline::stages::fragment::{Fragment, QuadShader};
+use ::pipeline::stages::input::QuadContext;
 
 pub fn rasterize_line<P, V, K, B, F>(args: &RasterArguments<P, V>,
                                      pipeline: &mut P,
                                      blend: B,
-                                     fragment_shader: F,
+                                     fragment_shader: &F,
+                                     primitive_id: usize,
                                      start: &ScreenVertex<V::Scalar, K>,
                                      end: &ScreenVertex<V::Scalar, K>)
     where P: PipelineObject,
           V: Vertex,
           K: Send + Sync + Interpolate,
           B: Blend<Pixel<P>>,
-          F: Fn(&ScreenVertex<V::Scalar, K>, &PipelineUniforms<P>) -> Fragment<Pixel<P>> + Send + Sync {
+          F: QuadShader<P, V::Scalar, K> {
     let RasterArguments {
         dimensions,
         tile,
         bounds,
-        stencil_value,
-        stencil_test,
-        stencil_op,
         antialiased_lines,
+        perspective_correct,
         cull_faces,
+        ..
     } = *args;
 
     let (uniforms, framebuffer, _) = pipeline.all_mut();
 
     use ::geometry::line::liang_barsky_iterative;
 
-    let XYZW { x: x1, y: y1, .. } = *start.position;
-    let XYZW { x: x2, y: y2, .. } = *end.position;
+    let XYZW { x: x1, y: y1, z: z1, w: w1 } = *start.position;
+    let XYZW { x: x2, y: y2, z: z2, w: w2 } = *end.position;
 
-    if let Some(((x1, y1), (x2, y2))) = liang_barsky_iterative((x1, y1), (x2, y2), bounds) {
-        let d = (x1 - x2).hypot(y1 - y2);
+    // Change in screen-space depth per pixel along the line, for slope-scaled depth bias
+    let depth_offset = {
+        let pixels = (x2 - x1).abs().max((y2 - y1).abs());
+
+        args.depth_offset(if pixels == Zero::zero() { Zero::zero() } else { (z2 - z1) / pixels })
+    };
+
+    // Squared length of the entire line, since parameters are relative to the original vertices
+    let length_squared = (x2 - x1) * (x2 - x1) + (y2 - y1) * (y2 - y1);
+
+    if let Some(((cx1, cy1), (cx2, cy2))) = liang_barsky_iterative((x1, y1), (x2, y2), bounds) {
+
+        // Parameter along the line of the center of any pixel, projected onto the line
+        // and clamped to its endpoints, so neighboring pixels past the ends don't extrapolate
+        let parameter = |x: i64, y: i64| -> V::Scalar {
+            if length_squared == Zero::zero() {
+                return Zero::zero();
+            }
+
+            // Real screen position should be in the center of the pixel.
+            let (xf, yf) = (cast::<_, V::Scalar>(x).unwrap() + NumCast::from(0.5).unwrap(),
+                            cast::<_, V::Scalar>(y).unwrap() + NumCast::from(0.5).unwrap());
+
+            let t = ((xf - x1) * (x2 - x1) + (yf - y1) * (y2 - y1)) / length_squared;
+
+            t.max(Zero::zero()).min(One::one())
+        };
+
+        // Parameter for interpolating uniforms, which undoes the perspective division if enabled
+        let uniform_parameter = |t: V::Scalar| -> V::Scalar {
+            if perspective_correct {
+                // Screen-space w is 1/w in clip-space, which is linear in screen-space
+                let (s, w) = ((<V::Scalar as One>::one() - t) * w1, t * w2);
+
+                let sum = s + w;
+
+                if sum > Zero::zero() { w / sum } else { t }
+            } else { t }
+        };
+
+        // Lines cover every sample of the pixels they pass through
+        let full_coverage = ((1u64 << framebuffer.samples()) - 1) as u32;
 
         let rasterize_fragment = |x: i64, y: i64, alpha: f64| {
-            if x >= 0 && y >= 0 {
+            // Lines are clipped to the tile bounds, but may still touch pixels just outside the tile
+            if tile.0.x as i64 <= x && x <= tile.1.x as i64 && tile.0.y as i64 <= y && y <= tile.1.y as i64 {
                 let coord = Coordinate::new(x as u32, y as u32);
 
                 let index = coord.into_index(dimensions);
 
-                // Get stencil buffer value for this pixel
-                let framebuffer_stencil_value = unsafe { framebuffer.get_stencil_unchecked(index) };
+                let t = parameter(x, y);
 
-                // perform stencil test
-                if stencil_test.test(framebuffer_stencil_value, stencil_value) {
-                    // Calculate new stencil value
-                    let new_stencil_value = stencil_op.op(framebuffer_stencil_value, stencil_value);
+                let position = Interpolate::linear_interpolate(t, &start.position, &end.position);
 
-                    // Set stencil value for this pixel
-                    unsafe { framebuffer.set_stencil_unchecked(index, new_stencil_value); }
+                let z = position.z - depth_offset;
 
-                    // Real screen position should be in the center of the pixel.
-                    let (xf, yf) = (cast::<_, V::Scalar>(x).unwrap() + NumCast::from(0.5).unwrap(),
-                                    cast::<_, V::Scalar>(y).unwrap() + NumCast::from(0.5).unwrap());
+                args.shade_samples(framebuffer, &blend, index, full_coverage, F::late_tests(), |_| z, |coverage| {
+                    // Interpolate the other pixels in the quad for screen-space derivatives
+                    let (qx, qy) = (x & !1, y & !1);
 
-                    let t = (x1 - xf).hypot(y1 - yf) / d;
+                    let lane = ((y - qy) * 2 + (x - qx)) as usize;
 
-                    let position = Interpolate::linear_interpolate(t, &start.position, &end.position);
+                    let parameters = [parameter(qx, qy), parameter(qx + 1, qy), parameter(qx, qy + 1), parameter(qx + 1, qy + 1)];
 
-                    let z = position.z;
+                    let vertex = |t: V::Scalar| {
+                        ScreenVertex {
+                            position: Interpolate::linear_interpolate(t, &start.position, &end.position),
+                            uniforms: Interpolate::fragment_linear_interpolate(uniform_parameter(t), t, &start.uniforms, &end.uniforms),
+                        }
+                    };
 
-                    if z < Zero::zero() {
-                        let d: DepthAttachment<P::Framebuffer> = Depth::from_scalar(z);
+                    let vertices = [vertex(parameters[0]), vertex(parameters[1]), vertex(parameters[2]), vertex(parameters[3])];
 
-                        let dt = unsafe { framebuffer.get_depth_unchecked(index) };
+                    let barycentric = |t: V::Scalar| Vector3::new(<V::Scalar as One>::one() - t, t, Zero::zero());
 
-                        // Check if point is in front of other geometry
-                        if d >= dt {
-                            // Perform fragment shading
-                            let fragment = fragment_shader(&ScreenVertex {
-                                position,
-                                uniforms: Interpolate::linear_interpolate(t, &start.uniforms, &end.uniforms)
-                            }, &uniforms);
+                    let context = QuadContext::new(Coordinate::new(qx as u32, qy as u32), primitive_id, true,
+                                                   [barycentric(parameters[0]), barycentric(parameters[1]),
+                                                    barycentric(parameters[2]), barycentric(parameters[3])]);
 
-                            match fragment {
-                                Fragment::Discard => (),
-                                Fragment::Color(c) => {
-                                    let p = unsafe { framebuffer.get_pixel_unchecked(index) };
+                    // Perform fragment shading
+                    let mut output = fragment_shader.shade_lane([&vertices[0], &vertices[1], &vertices[2], &vertices[3]],
+                                                                &context, lane, coverage, &uniforms);
 
-                                    unsafe {
-                                        framebuffer.set_pixel_unchecked(index, blend.blend(c.mul_alpha(ColorAlpha::from_scalar(alpha)), p));
-                                        framebuffer.set_depth_unchecked(index, d);
-                                    }
-                                }
-                            }
-                        }
+                    if let Fragment::Color(c) = output.fragment {
+                        output.fragment = Fragment::Color(c.mul_alpha(ColorAlpha::from_scalar(alpha)));
                     }
-                }
+
+                    output
+                });
             }
         };
 
         if antialiased_lines {
-            draw_line_xiaolin_wu(cast(x1).unwrap(), cast(y1).unwrap(),
-                                 cast(x2).unwrap(), cast(y2).unwrap(), rasterize_fragment);
+            draw_line_xiaolin_wu(cast(cx1).unwrap(), cast(cy1).unwrap(),
+                                 cast(cx2).unwrap(), cast(cy2).unwrap(), rasterize_fragment);
         } else {
-            draw_line_bresenham(cast(x1).unwrap(), cast(y1).unwrap(),
-                                cast(x2).unwrap(), cast(y2).unwrap(), rasterize_fragment)
+            draw_line_bresenham(cast(cx1).unwrap(), cast(cy1).unwrap(),
+                                cast(cx2).unwrap(), cast(cy2).unwrap(), rasterize_fragment)
         }
     }
 }
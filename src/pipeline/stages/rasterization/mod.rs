@@ -1,27 +1,844 @@
 pub mod point;
 pub mod line;
 pub mod triangle;
+pub mod edge;
+pub mod binning;
 
-use ::stencil::{StencilTest, StencilOp};
+use num_traits::{Float, Zero};
+
+use ::stencil::{StencilOp, StencilState};
+use ::color::{Color, ColorMask};
+use ::color::blend::Blend;
+use ::framebuffer::{FramebufferBase, UnsafeFramebuffer};
+use ::attachments::depth::{Depth, DepthTest};
 use ::mesh::{Vertex, Mesh};
 use ::geometry::{Dimensions, Coordinate, FaceWinding};
 
 use ::pipeline::PipelineObject;
 
+use ::framebuffer::types::DepthAttachment;
 use ::pipeline::types::{Pixel, StencilValue};
+use ::pipeline::stages::fragment::{Fragment, FragmentOutput, DepthBias};
+
+/// Maximum number of samples per pixel of multisampled framebuffers
+pub const MAX_SAMPLES: usize = 16;
+
+/// Depths of each sample of a pixel
+pub type SampleDepths<P> = [DepthAttachment<<P as PipelineObject>::Framebuffer>; MAX_SAMPLES];
 
 #[derive(Clone, Copy)]
 pub struct RasterArguments<P, V> where P: PipelineObject, V: Vertex {
     pub dimensions: Dimensions,
+    /// Inclusive top-left and bottom-right pixels of the tile being rasterized
     pub tile: (Coordinate, Coordinate),
+    /// Screen-space bounds of the tile, where the bottom-right bound is exclusive
     pub bounds: ((V::Scalar, V::Scalar), (V::Scalar, V::Scalar)),
-    pub stencil_value: StencilValue<P>,
-    pub stencil_test: StencilTest,
-    pub stencil_op: StencilOp,
+    /// Stencil state of the draw, including the reference value of the mesh or stencil configuration
+    pub stencil: StencilState<StencilValue<P>>,
+    /// Stencil state of back-facing triangles, which replaces `stencil` when rasterizing them
+    pub back_stencil: StencilState<StencilValue<P>>,
     pub antialiased_lines: bool,
+    pub perspective_correct: bool,
+    pub depth_test: Option<DepthTest>,
+    pub depth_write: bool,
+    pub depth_bias: DepthBias<V::Scalar>,
+    pub color_mask: ColorMask,
     pub cull_faces: Option<FaceWinding>,
+    pub front_face: FaceWinding,
+}
+
+impl<P, V> RasterArguments<P, V> where P: PipelineObject, V: Vertex {
+    /// Returns the depth bias of a primitive, given the largest change in its screen-space depth per pixel,
+    /// which is subtracted from interpolated depths.
+    #[inline]
+    pub fn depth_offset(&self, slope: V::Scalar) -> V::Scalar {
+        self.depth_bias.constant + self.depth_bias.slope * slope.abs()
+    }
+
+    /// Performs the stencil test with the given stencil reference value, then the depth test,
+    /// on each sample of a pixel in the coverage mask, given the screen-space depth of each sample.
+    ///
+    /// Samples failing either test are updated with the stencil fail or depth fail operation right away,
+    /// while the pass operation is left to `write_samples`, since the fragment may still be discarded.
+    ///
+    /// Returns the mask of samples that passed both tests, along with their depths.
+    #[inline]
+    pub fn test_samples<D>(&self, framebuffer: &mut P::Framebuffer, index: usize, coverage: u32,
+                           stencil_value: StencilValue<P>, depth: D) -> (u32, SampleDepths<P>)
+        where D: Fn(u32) -> V::Scalar {
+        let mut passed = 0;
+        let mut depths = [Depth::far(); MAX_SAMPLES];
+
+        for sample in 0..framebuffer.samples() {
+            if coverage & (1 << sample) == 0 {
+                continue;
+            }
+
+            let z = depth(sample);
+
+            // Samples behind the screen are clipped, so they don't touch the stencil buffer either
+            if !(z < Zero::zero()) {
+                continue;
+            }
+
+            // Get stencil buffer value for this sample
+            let framebuffer_stencil_value = unsafe { framebuffer.get_sample_stencil_unchecked(index, sample) };
+
+            // perform stencil test
+            if !self.stencil.test(framebuffer_stencil_value, stencil_value) {
+                self.stencil_op(framebuffer, index, sample, self.stencil.fail_op, stencil_value);
+
+                continue;
+            }
+
+            let d: DepthAttachment<P::Framebuffer> = Depth::from_scalar(z);
+
+            // Check if sample is in front of other geometry, or whatever the depth test requires
+            let depth_passed = match self.depth_test {
+                Some(test) => test.test(unsafe { framebuffer.get_sample_depth_unchecked(index, sample) }, d),
+                None => true,
+            };
+
+            if depth_passed {
+                passed |= 1 << sample;
+                depths[sample as usize] = d;
+            } else {
+                self.stencil_op(framebuffer, index, sample, self.stencil.depth_fail_op, stencil_value);
+            }
+        }
+
+        (passed, depths)
+    }
+
+    /// Performs a stencil operation on a sample with the given stencil reference value, respecting the write mask
+    #[inline]
+    pub fn stencil_op(&self, framebuffer: &mut P::Framebuffer, index: usize, sample: u32, op: StencilOp, stencil_value: StencilValue<P>) {
+        if op != StencilOp::Keep {
+            unsafe {
+                let previous = framebuffer.get_sample_stencil_unchecked(index, sample);
+
+                framebuffer.set_sample_stencil_unchecked(index, sample, self.stencil.op(op, previous, stencil_value));
+            }
+        }
+    }
+
+    /// Tests, shades and writes the samples of a pixel in the coverage mask, given the screen-space depth of each sample.
+    ///
+    /// `shader` is given the mask of samples the fragment covers. When the fragment shader output requires late tests,
+    /// it runs before the depth and stencil tests, with its replacement depth and stencil reference values used for them.
+    /// Otherwise, it only runs if any samples passed the tests.
+    #[inline]
+    pub fn shade_samples<B, D, S>(&self, framebuffer: &mut P::Framebuffer, blend: &B, index: usize, coverage: u32,
+                                  late_tests: bool, depth: D, shader: S)
+        where B: Blend<Pixel<P>>,
+              D: Fn(u32) -> V::Scalar,
+              S: FnOnce(u32) -> FragmentOutput<Pixel<P>, V::Scalar, StencilValue<P>> {
+        if late_tests {
+            let FragmentOutput { fragment, depth: fragment_depth, stencil } = shader(coverage);
+
+            if let Fragment::Color(color) = fragment {
+                let stencil_value = stencil.unwrap_or(self.stencil.reference);
+
+                let (passed, depths) = self.test_samples(framebuffer, index, coverage, stencil_value, |sample| {
+                    fragment_depth.unwrap_or_else(|| depth(sample))
+                });
+
+                self.write_samples(framebuffer, blend, index, passed, &depths, stencil_value, color);
+            }
+        } else {
+            let (passed, depths) = self.test_samples(framebuffer, index, coverage, self.stencil.reference, depth);
+
+            if passed != 0 {
+                if let Fragment::Color(color) = shader(passed).fragment {
+                    self.write_samples(framebuffer, blend, index, passed, &depths, self.stencil.reference, color);
+                }
+            }
+        }
+    }
+
+    /// Tests, shades and writes the pixels of a 2x2 quad, given the pixel index and coverage mask of each lane
+    /// and the screen-space depth of each sample of each lane.
+    ///
+    /// Unlike `shade_samples`, every lane is tested before `shader` runs once for the entire quad,
+    /// given the mask of samples each lane covers. Lanes with no samples left are not shaded.
+    #[inline]
+    pub fn shade_quad<B, D, S>(&self, framebuffer: &mut P::Framebuffer, blend: &B, indices: [usize; 4], coverage: [u32; 4],
+                               late_tests: bool, depth: D, shader: S)
+        where B: Blend<Pixel<P>>,
+              D: Fn(usize, u32) -> V::Scalar,
+              S: FnOnce([u32; 4]) -> [Option<FragmentOutput<Pixel<P>, V::Scalar, StencilValue<P>>>; 4] {
+        if late_tests {
+            let outputs = shader(coverage);
+
+            for lane in 0..4 {
+                if let Some(FragmentOutput { fragment: Fragment::Color(color), depth: fragment_depth, stencil }) = outputs[lane] {
+                    let stencil_value = stencil.unwrap_or(self.stencil.reference);
+
+                    let (passed, depths) = self.test_samples(framebuffer, indices[lane], coverage[lane], stencil_value, |sample| {
+                        fragment_depth.unwrap_or_else(|| depth(lane, sample))
+                    });
+
+                    self.write_samples(framebuffer, blend, indices[lane], passed, &depths, stencil_value, color);
+                }
+            }
+        } else {
+            let mut passed = [0; 4];
+            let mut depths = [[Depth::far(); MAX_SAMPLES]; 4];
+
+            for lane in 0..4 {
+                if coverage[lane] != 0 {
+                    let (lane_passed, lane_depths) = self.test_samples(framebuffer, indices[lane], coverage[lane],
+                                                                       self.stencil.reference, |sample| depth(lane, sample));
+
+                    passed[lane] = lane_passed;
+                    depths[lane] = lane_depths;
+                }
+            }
+
+            if passed.iter().any(|&passed| passed != 0) {
+                let outputs = shader(passed);
+
+                for lane in 0..4 {
+                    if let Some(FragmentOutput { fragment: Fragment::Color(color), .. }) = outputs[lane] {
+                        self.write_samples(framebuffer, blend, indices[lane], passed[lane], &depths[lane], self.stencil.reference, color);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Blends a fragment color into each sample of a pixel in the mask, and writes the channels enabled in the color mask,
+    /// along with their depths if depth writes are enabled.
+    ///
+    /// The samples are also updated with the stencil pass operation, using the given stencil reference value.
+    #[inline]
+    pub fn write_samples<B>(&self, framebuffer: &mut P::Framebuffer, blend: &B, index: usize, mask: u32,
+                            depths: &SampleDepths<P>, stencil_value: StencilValue<P>, color: Pixel<P>) where B: Blend<Pixel<P>> {
+        // Depth is never written when depth testing is disabled
+        let depth_write = self.depth_write && self.depth_test.is_some();
+
+        let mask_colors = !self.color_mask.is_all();
+
+        for sample in 0..framebuffer.samples() {
+            if mask & (1 << sample) != 0 {
+                unsafe {
+                    let p = framebuffer.get_sample_color_unchecked(index, sample);
+
+                    let color = blend.blend(color, p);
+
+                    framebuffer.set_sample_color_unchecked(index, sample, if mask_colors { color.mask(p, self.color_mask) } else { color });
+
+                    if depth_write {
+                        framebuffer.set_sample_depth_unchecked(index, sample, depths[sample as usize]);
+                    }
+                }
+
+                self.stencil_op(framebuffer, index, sample, self.stencil.pass_op, stencil_value);
+            }
+        }
+    }
 }
 
 pub use self::triangle::rasterize_triangle;
 pub use self::line::rasterize_line;
-pub use self::point::rasterize_point;
\ No newline at end of file
+pub use self::point::rasterize_point;
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use nalgebra::{Vector2, Vector3, Vector4, Point3, Perspective3};
+
+    use ::geometry::{Dimensions, HasDimensions, Coordinate, ClipVertex, Viewport, Scissor, FaceWinding};
+    use ::color::ColorMask;
+    use ::framebuffer::{Framebuffer, RenderBuffer};
+    use ::attachments::DepthTest;
+    use ::attachments::predefined::{ColorDepthAttachments, ColorDepthStencilAttachments};
+    use ::stencil::{GenericStencilConfig, StencilTest, StencilOp};
+    use ::pixels::PixelRead;
+    use ::primitive::{Primitive, Triangle, Line};
+    use ::mesh::{Mesh, SimpleVertex};
+    use ::interpolate::Interpolate;
+    use ::pipeline::{Pipeline, PipelineObject};
+    use ::pipeline::types::StencilValue;
+    use ::pipeline::stages::fragment::{FragmentShader, Fragment, FragmentOutput, DepthBias};
+
+    const SIZE: u32 = 32;
+
+    /// Near and far edges of a floor plane at y = -1, which is mapped to UV coordinates
+    const NEAR: f32 = 1.5;
+    const FAR: f32 = 20.0;
+
+    type Buffer = RenderBuffer<ColorDepthAttachments<Vector4<f32>, f32>>;
+
+    /// Quad covering the whole screen as two counter-clockwise triangles, with the given depth at each corner
+    pub fn fullscreen_quad<D: Fn(f32, f32) -> f32>(depth: D) -> Mesh<SimpleVertex<f32, ()>> {
+        let vertices = vec![(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)].into_iter().map(|(x, y)| SimpleVertex {
+            position: Point3::new(x, y, depth(x, y)),
+            data: (),
+        }).collect();
+
+        Mesh { vertices, indices: vec![0, 1, 2, 2, 1, 3] }
+    }
+
+    /// Starts rendering a mesh over the whole framebuffer, with its vertex positions already in normalized device coordinates
+    pub fn draw<'a, T, K, U, F, S>(pipeline: &'a mut Pipeline<U, F, S>, primitive: T, mesh: Arc<Mesh<SimpleVertex<f32, K>>>,
+                                   stencil: Option<StencilValue<Pipeline<U, F, S>>>) -> FragmentShader<'a, Pipeline<U, F, S>, SimpleVertex<f32, K>, T, K, ()>
+        where Pipeline<U, F, S>: PipelineObject, T: Primitive, K: Clone + Send + Sync + Interpolate {
+        let viewport = Viewport::new(pipeline.framebuffer().dimensions(), Coordinate::new(0, 0), 0.0, 1.0);
+
+        pipeline.render_mesh(primitive, mesh, stencil)
+            .run_to_fragment(viewport, |vertex, _| ClipVertex::new(vertex.position.to_homogeneous(), vertex.data.clone()))
+    }
+
+    /// Renders the given floor vertices with their UV coordinates as colors
+    fn render<T: Primitive>(primitive: T, vertices: Vec<SimpleVertex<f32, Vector2<f32>>>, indices: Vec<usize>, perspective_correct: bool) -> Buffer {
+        let dimensions = Dimensions::new(SIZE, SIZE);
+
+        // 90 degree field of view, so the projection does not scale x or y
+        let projection = Perspective3::new(1.0, ::std::f32::consts::FRAC_PI_2, 0.1, 100.0).to_homogeneous();
+
+        let mut pipeline: Pipeline<_, _> = Pipeline::from_framebuffer(Buffer::with_dimensions(dimensions), projection);
+
+        pipeline.render_mesh(primitive, Arc::new(Mesh { vertices, indices }), None)
+            .run_to_fragment(Viewport::new(dimensions, Coordinate::new(0, 0), 0.0, 1.0), |vertex, projection| {
+                ClipVertex::new(projection * vertex.position.to_homogeneous(), vertex.data)
+            })
+            .with_perspective_correct(perspective_correct)
+            .run(|vertex, _| Fragment::Color(Vector4::new(vertex.uniforms.x, vertex.uniforms.y, 0.0, 1.0)));
+
+        pipeline.framebuffer().clone()
+    }
+
+    fn floor_vertex(x: f32, z: f32) -> SimpleVertex<f32, Vector2<f32>> {
+        SimpleVertex {
+            position: Point3::new(x, -1.0, -z),
+            data: Vector2::new((x + 1.0) / 2.0, (z - NEAR) / (FAR - NEAR)),
+        }
+    }
+
+    /// Projects the center of a pixel back onto the floor and returns its UV coordinates,
+    /// clamped to the floor like the attributes of pixels centered just past the end of a primitive
+    fn analytic_uv(x: u32, y: u32) -> Vector2<f32> {
+        let half = SIZE as f32 / 2.0;
+
+        let ndc_x = (x as f32 + 0.5) / half - 1.0;
+        let ndc_y = 1.0 - (y as f32 + 0.5) / half;
+
+        // Distance along the view ray (ndc_x, ndc_y, -1) to the floor
+        let s = -1.0 / ndc_y;
+
+        let uv = Vector2::new((s * ndc_x + 1.0) / 2.0, (s - NEAR) / (FAR - NEAR));
+
+        uv.map(|c| c.max(0.0).min(1.0))
+    }
+
+    /// Returns the number of shaded pixels and the largest errors of each UV coordinate from the analytic UV coordinates
+    fn max_error(buffer: &Buffer) -> (usize, Vector2<f32>) {
+        let mut shaded = 0;
+        let mut error = Vector2::new(0.0f32, 0.0);
+
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                let color = buffer.pixel_ref(Coordinate::new(x, y)).unwrap().get();
+
+                if color.w == 1.0 {
+                    let uv = analytic_uv(x, y);
+
+                    shaded += 1;
+                    error.x = error.x.max((color.x - uv.x).abs());
+                    error.y = error.y.max((color.y - uv.y).abs());
+                }
+            }
+        }
+
+        (shaded, error)
+    }
+
+    fn floor_triangles(perspective_correct: bool) -> Buffer {
+        render(Triangle, vec![floor_vertex(-1.0, NEAR), floor_vertex(1.0, NEAR),
+                              floor_vertex(-1.0, FAR), floor_vertex(1.0, FAR)],
+               vec![0, 1, 2, 2, 1, 3], perspective_correct)
+    }
+
+    fn floor_line(perspective_correct: bool) -> Buffer {
+        // Straight down the center of the screen
+        render(Line, vec![floor_vertex(0.0, NEAR), floor_vertex(0.0, FAR)], vec![0, 1], perspective_correct)
+    }
+
+    #[test]
+    fn test_perspective_correct_triangle() {
+        let (shaded, error) = max_error(&floor_triangles(true));
+
+        assert!(shaded > 50);
+        assert!(error.x < 1e-3 && error.y < 1e-3, "error: {:?}", error);
+
+        let (_, error) = max_error(&floor_triangles(false));
+
+        assert!(error.y > 0.05, "error: {:?}", error);
+    }
+
+    #[test]
+    fn test_perspective_correct_line() {
+        // Pixel centers are half a pixel to the side of the line, so only compare along it
+        let (shaded, error) = max_error(&floor_line(true));
+
+        assert!(shaded > 5);
+        assert!(error.y < 1e-3, "error: {:?}", error);
+
+        let (_, error) = max_error(&floor_line(false));
+
+        assert!(error.y > 0.05, "error: {:?}", error);
+    }
+
+    /// Draws a quad covering the whole screen at the given normalized depth, and returns the resulting color of the center pixel
+    fn draw_quad(pipeline: &mut Pipeline<(), Buffer>, z: f32, color: f32, test: Option<DepthTest>, write: bool) -> f32 {
+        draw(pipeline, Triangle, Arc::new(fullscreen_quad(|_, _| z)), None)
+            .with_depth_test(test)
+            .with_depth_write(write)
+            .run(|_, _| Fragment::Color(Vector4::new(color, 0.0, 0.0, 1.0)));
+
+        pipeline.framebuffer().pixel_ref(Coordinate::new(SIZE / 2, SIZE / 2)).unwrap().get().x
+    }
+
+    #[test]
+    fn test_depth_state() {
+        let default = Some(DepthTest::GreaterThanEq);
+
+        let mut pipeline: Pipeline<_, _> = Pipeline::from_framebuffer(Buffer::with_dimensions(Dimensions::new(SIZE, SIZE)), ());
+
+        // Nearer geometry hides farther geometry by default
+        assert_eq!(draw_quad(&mut pipeline, 0.0, 1.0, default, true), 1.0);
+        assert_eq!(draw_quad(&mut pipeline, 0.5, 2.0, default, true), 1.0);
+
+        // Decals at exactly the same depth
+        assert_eq!(draw_quad(&mut pipeline, 0.0, 3.0, Some(DepthTest::Equal), true), 3.0);
+        assert_eq!(draw_quad(&mut pipeline, -0.5, 4.0, Some(DepthTest::Equal), true), 3.0);
+
+        // Transparent geometry is tested, but does not hide what is rendered after it
+        assert_eq!(draw_quad(&mut pipeline, 0.5, 5.0, default, false), 3.0);
+        assert_eq!(draw_quad(&mut pipeline, -0.5, 6.0, default, false), 6.0);
+        assert_eq!(draw_quad(&mut pipeline, -0.25, 7.0, default, true), 7.0);
+
+        // Disabled depth testing passes everything and never writes depth
+        assert_eq!(draw_quad(&mut pipeline, -0.75, 8.0, None, true), 8.0);
+        assert_eq!(draw_quad(&mut pipeline, -0.5, 9.0, default, true), 9.0);
+
+        // Reversed depth, where smaller depth values are nearer
+        pipeline.framebuffer_mut().clear_depth(0.0);
+
+        assert_eq!(draw_quad(&mut pipeline, 0.5, 10.0, Some(DepthTest::LessThan), true), 10.0);
+        assert_eq!(draw_quad(&mut pipeline, -0.5, 11.0, Some(DepthTest::LessThan), true), 10.0);
+    }
+
+    #[test]
+    fn test_fragment_depth_output() {
+        let dimensions = Dimensions::new(SIZE, SIZE);
+        let half = SIZE as f32 / 2.0;
+
+        let mesh = Arc::new(fullscreen_quad(|_, _| 0.0));
+
+        let buffer = RenderBuffer::<ColorDepthStencilAttachments<Vector4<f32>, f32, u8>>::with_dimensions(dimensions);
+
+        let mut pipeline: Pipeline<_, _, GenericStencilConfig> = Pipeline::from_framebuffer(buffer, ());
+
+        *pipeline.stencil_config_mut() = GenericStencilConfig::new(StencilTest::Always, StencilOp::Replace);
+
+        // The left half is moved nearer with a different stencil reference value for each quadrant,
+        // and the right half is discarded, which must not touch the depth or stencil buffers
+        draw(&mut pipeline, Triangle, mesh.clone(), Some(1))
+            .run(|vertex, _| {
+                if vertex.position.x < half {
+                    FragmentOutput::new(Fragment::Color(Vector4::new(1.0, 0.0, 0.0, 1.0)))
+                        .with_depth(-0.25)
+                        .with_stencil(if vertex.position.y < half { 2 } else { 3 })
+                } else {
+                    FragmentOutput::new(Fragment::Discard)
+                }
+            });
+
+        {
+            let stencil = |x, y| pipeline.framebuffer().attachments(Coordinate::new(x, y)).unwrap().get_stencil();
+
+            assert_eq!(stencil(0, 0), 2);
+            assert_eq!(stencil(0, SIZE - 1), 3);
+            assert_eq!(stencil(SIZE - 1, 0), 0);
+        }
+
+        // Interpolated depth of -0.5 is behind the left half, but in front of the untouched right half
+        draw(&mut pipeline, Triangle, mesh, None)
+            .run(|_, _| Fragment::Color(Vector4::new(2.0, 0.0, 0.0, 1.0)));
+
+        let color = |x, y| pipeline.framebuffer().pixel_ref(Coordinate::new(x, y)).unwrap().get().x;
+
+        assert_eq!(color(0, 0), 1.0);
+        assert_eq!(color(SIZE - 1, 0), 2.0);
+    }
+
+    #[test]
+    fn test_depth_bias() {
+        let dimensions = Dimensions::new(SIZE, SIZE);
+
+        let render = |pipeline: &mut Pipeline<(), Buffer>, slope: f32, color: f32, test: DepthTest, bias: DepthBias<f32>| {
+            // Depth changes by slope / 2 across the screen
+            draw(pipeline, Triangle, Arc::new(fullscreen_quad(|x, _| x * slope / 2.0)), None)
+                .with_depth_test(Some(test))
+                .with_depth_bias(bias)
+                .run(|_, _| Fragment::Color(Vector4::new(color, 0.0, 0.0, 1.0)));
+
+            let color = |x| pipeline.framebuffer().pixel_ref(Coordinate::new(x, SIZE / 2)).unwrap().get().x;
+
+            (color(1), color(SIZE - 2))
+        };
+
+        for &slope in &[0.0, 0.5] {
+            let mut pipeline: Pipeline<_, _> = Pipeline::from_framebuffer(Buffer::with_dimensions(dimensions), ());
+
+            assert_eq!(render(&mut pipeline, slope, 1.0, DepthTest::GreaterThanEq, DepthBias::default()), (1.0, 1.0));
+
+            // Coplanar geometry only passes a strict test when pulled towards the camera
+            assert_eq!(render(&mut pipeline, slope, 2.0, DepthTest::GreaterThan, DepthBias::default()), (1.0, 1.0));
+            assert_eq!(render(&mut pipeline, slope, 3.0, DepthTest::GreaterThan, DepthBias::new(-1e-3, 0.0)), (3.0, 3.0));
+            assert_eq!(render(&mut pipeline, slope, 4.0, DepthTest::GreaterThanEq, DepthBias::new(1e-3, 0.0)), (3.0, 3.0));
+
+            // Slope-scaled bias only affects sloped geometry, where each pixel changes depth by 1/128
+            let sloped = render(&mut pipeline, slope, 5.0, DepthTest::GreaterThan, DepthBias::new(2e-3, -1.0));
+
+            assert_eq!(sloped, if slope == 0.0 { (3.0, 3.0) } else { (5.0, 5.0) });
+        }
+    }
+
+    #[test]
+    fn test_scissor_color_mask() {
+        let dimensions = Dimensions::new(SIZE, SIZE);
+
+        let render = |pipeline: &mut Pipeline<(), Buffer>, color: Vector4<f32>, scissor: Option<Scissor>, mask: ColorMask| {
+            draw(pipeline, Triangle, Arc::new(fullscreen_quad(|_, _| 0.0)), None)
+                .with_scissor(scissor)
+                .with_color_mask(mask)
+                .run(|_, _| Fragment::Color(color));
+        };
+
+        let mut pipeline: Pipeline<_, _> = Pipeline::from_framebuffer(Buffer::with_dimensions(dimensions), ());
+
+        pipeline.framebuffer_mut().clear(Vector4::new(0.0, 0.0, 0.0, 0.5));
+
+        let scissor = Scissor::new(Dimensions::new(8, 4), Coordinate::new(4, 6));
+
+        render(&mut pipeline, Vector4::new(1.0, 1.0, 1.0, 1.0), Some(scissor), ColorMask::rgb());
+
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                let inside = 4 <= x && x < 12 && 6 <= y && y < 10;
+
+                let color = pipeline.framebuffer().pixel_ref(Coordinate::new(x, y)).unwrap().get();
+
+                assert_eq!(color, if inside { Vector4::new(1.0, 1.0, 1.0, 0.5) } else { Vector4::new(0.0, 0.0, 0.0, 0.5) });
+            }
+        }
+
+        // Empty scissor rectangles don't render anything
+        render(&mut pipeline, Vector4::new(1.0, 1.0, 1.0, 1.0), Some(Scissor::new(Dimensions::new(0, 4), Coordinate::new(0, 0))), ColorMask::all());
+
+        render(&mut pipeline, Vector4::new(0.25, 0.25, 0.25, 0.25), None, ColorMask::alpha_only());
+
+        assert_eq!(pipeline.framebuffer().pixel_ref(Coordinate::new(0, 0)).unwrap().get(), Vector4::new(0.0, 0.0, 0.0, 0.25));
+        assert_eq!(pipeline.framebuffer().pixel_ref(Coordinate::new(4, 6)).unwrap().get(), Vector4::new(1.0, 1.0, 1.0, 0.25));
+
+        render(&mut pipeline, Vector4::new(0.5, 0.5, 0.5, 0.5), None, ColorMask::new(false, true, false, false));
+
+        assert_eq!(pipeline.framebuffer().pixel_ref(Coordinate::new(0, 0)).unwrap().get(), Vector4::new(0.0, 0.5, 0.0, 0.25));
+    }
+
+    #[cfg(feature = "packet_shaders")]
+    #[test]
+    fn test_packet_shader() {
+        use ::numeric::packet::Packet;
+
+        let dimensions = Dimensions::new(SIZE, SIZE);
+
+        let projection = Perspective3::new(1.0, ::std::f32::consts::FRAC_PI_2, 0.1, 100.0).to_homogeneous();
+
+        let mut pipeline: Pipeline<_, _> = Pipeline::from_framebuffer(Buffer::with_dimensions(dimensions), projection);
+
+        let vertices = vec![floor_vertex(-1.0, NEAR), floor_vertex(1.0, NEAR), floor_vertex(-1.0, FAR), floor_vertex(1.0, FAR)];
+
+        pipeline.render_mesh(Triangle, Arc::new(Mesh { vertices, indices: vec![0, 1, 2, 2, 1, 3] }), None)
+            .run_to_fragment(Viewport::new(dimensions, Coordinate::new(0, 0), 0.0, 1.0), |vertex, projection| {
+                ClipVertex::new(projection * vertex.position.to_homogeneous(), vertex.data)
+            })
+            .run_packet(|packet, _| {
+                assert_ne!(packet.active(), 0);
+
+                let u = packet.gather(|v| v.uniforms.x);
+                let v = packet.gather(|v| v.uniforms.y);
+
+                // Round trip through packet arithmetic
+                let (u, v) = (u * Packet::splat(2.0) - u, v + v - v);
+
+                Packet::generate(|lane| Fragment::Color(Vector4::new(u.lane(lane), v.lane(lane), 0.0, 1.0))).0
+            });
+
+        // Shading entire quads at once gives the same result as shading each fragment
+        let expected = floor_triangles(true);
+
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                let coord = Coordinate::new(x, y);
+
+                assert_eq!(pipeline.framebuffer().pixel_ref(coord).unwrap().get(), expected.pixel_ref(coord).unwrap().get());
+            }
+        }
+    }
+
+    #[test]
+    fn test_stencil_state() {
+        let dimensions = Dimensions::new(SIZE, SIZE);
+        let half = SIZE as f32 / 2.0;
+
+        let mesh = Arc::new(fullscreen_quad(|_, _| 0.0));
+
+        let buffer = RenderBuffer::<ColorDepthStencilAttachments<Vector4<f32>, f32, u8>>::with_dimensions(dimensions);
+
+        let mut pipeline: Pipeline<_, _, GenericStencilConfig> = Pipeline::from_framebuffer(buffer, ());
+
+        // Occluder covering the left half
+        draw(&mut pipeline, Triangle, mesh.clone(), None)
+            .run(move |vertex, _| if vertex.position.x < half {
+                FragmentOutput::new(Fragment::Color(Vector4::new(1.0, 0.0, 0.0, 1.0))).with_depth(-0.25)
+            } else {
+                FragmentOutput::new(Fragment::Discard)
+            });
+
+        let stencil = |pipeline: &Pipeline<_, RenderBuffer<_>, _>, x, y| pipeline.framebuffer().attachments(Coordinate::new(x, y)).unwrap().get_stencil();
+
+        assert_eq!(stencil(&pipeline, 0, 0), 0);
+
+        // Interpolated depth of -0.5 fails the depth test behind the occluder, and passes elsewhere.
+        // The reference value is truncated to the stencil type.
+        *pipeline.stencil_config_mut() = GenericStencilConfig::new(StencilTest::Always, StencilOp::Replace)
+            .with_ops(StencilOp::Keep, StencilOp::Increment { wrap: false }, StencilOp::Replace)
+            .with_reference(Some(0x1F0));
+
+        let draw = |pipeline: &mut Pipeline<_, _, _>, discard: bool, depth_test: Option<DepthTest>| {
+            draw(pipeline, Triangle, mesh.clone(), Some(7))
+                .with_depth_test(depth_test)
+                .run(move |_, _| if discard { Fragment::Discard } else { Fragment::Color(Vector4::new(2.0, 0.0, 0.0, 1.0)) });
+        };
+
+        draw(&mut pipeline, false, Some(DepthTest::GreaterThanEq));
+
+        assert_eq!(stencil(&pipeline, 0, 0), 1);
+        assert_eq!(stencil(&pipeline, SIZE - 1, 0), 0xF0);
+
+        // Discarded fragments don't perform the pass operation
+        *pipeline.stencil_config_mut() = GenericStencilConfig::new(StencilTest::Always, StencilOp::Zero);
+
+        draw(&mut pipeline, true, Some(DepthTest::GreaterThanEq));
+
+        assert_eq!(stencil(&pipeline, 0, 0), 1);
+        assert_eq!(stencil(&pipeline, SIZE - 1, 0), 0xF0);
+
+        // Only the low bits are compared and written
+        *pipeline.stencil_config_mut() = GenericStencilConfig::new(StencilTest::Equal, StencilOp::Keep)
+            .with_ops(StencilOp::Invert, StencilOp::Keep, StencilOp::Keep)
+            .with_reference(Some(0x31))
+            .with_read_mask(0x0F)
+            .with_write_mask(0x0F);
+
+        draw(&mut pipeline, false, None);
+
+        assert_eq!(stencil(&pipeline, 0, 0), 1);
+        assert_eq!(stencil(&pipeline, SIZE - 1, 0), 0xFF);
+
+        let color = |x, y| pipeline.framebuffer().pixel_ref(Coordinate::new(x, y)).unwrap().get().x;
+
+        assert_eq!(color(0, 0), 2.0);
+        assert_eq!(color(SIZE - 1, 0), 2.0);
+    }
+
+    #[test]
+    fn test_two_sided_stencil() {
+        let dimensions = Dimensions::new(SIZE, SIZE);
+
+        // The same full-screen quad twice, first clockwise and then counter-clockwise in screen-space
+        let mesh = Arc::new(Mesh { indices: vec![0, 1, 2, 2, 1, 3, 0, 2, 1, 2, 3, 1], ..fullscreen_quad(|_, _| 0.0) });
+
+        let buffer = RenderBuffer::<ColorDepthStencilAttachments<Vector4<f32>, f32, u8>>::with_dimensions(dimensions);
+
+        let mut pipeline: Pipeline<_, _, GenericStencilConfig> = Pipeline::from_framebuffer(buffer, ());
+
+        let draw = |pipeline: &mut Pipeline<_, _, _>, front_face: FaceWinding| {
+            draw(pipeline, Triangle, mesh.clone(), None)
+                .with_depth_test(None)
+                .with_front_face(front_face)
+                .run(|_, _| Fragment::Color(Vector4::new(1.0, 0.0, 0.0, 1.0)));
+        };
+
+        let stencil = |pipeline: &Pipeline<_, RenderBuffer<_>, _>| pipeline.framebuffer().attachments(Coordinate::new(SIZE / 2, SIZE / 2)).unwrap().get_stencil();
+
+        *pipeline.stencil_config_mut() = GenericStencilConfig::new(StencilTest::Always, StencilOp::Increment { wrap: false })
+            .with_back_ops(StencilOp::Keep, StencilOp::Keep, StencilOp::Replace)
+            .with_reference(Some(7));
+
+        draw(&mut pipeline, FaceWinding::Clockwise);
+
+        // Incremented by the front face, then replaced by the back face
+        assert_eq!(stencil(&pipeline), 7);
+
+        draw(&mut pipeline, FaceWinding::CounterClockwise);
+
+        // Replaced by the back face, then incremented by the front face
+        assert_eq!(stencil(&pipeline), 8);
+
+        *pipeline.stencil_config_mut() = (*pipeline.stencil_config())
+            .with_back_test(StencilTest::Never)
+            .with_back_ops(StencilOp::Zero, StencilOp::Keep, StencilOp::Keep);
+
+        draw(&mut pipeline, FaceWinding::CounterClockwise);
+
+        // Zeroed by the failing back face, then incremented by the front face
+        assert_eq!(stencil(&pipeline), 1);
+    }
+
+    #[test]
+    fn test_fragment_input_context() {
+        let dimensions = Dimensions::new(SIZE, SIZE);
+
+        // The first triangle covers the bottom-left half of the screen and is clockwise in screen-space,
+        // the second covers the top-right half and is counter-clockwise.
+        let mesh = Arc::new(Mesh { indices: vec![0, 1, 2, 2, 3, 1], ..fullscreen_quad(|_, _| 0.0) });
+
+        let mut pipeline: Pipeline<_, _> = Pipeline::from_framebuffer(Buffer::with_dimensions(dimensions), ());
+
+        draw(&mut pipeline, Triangle, mesh, None)
+            .run_with_input(|input, _| {
+                let pixel = input.pixel();
+
+                assert_eq!(pixel, Coordinate::new(input.position.x as u32, input.position.y as u32));
+
+                let barycentric: Vector3<f32> = input.barycentric();
+
+                assert!((barycentric.x + barycentric.y + barycentric.z - 1.0).abs() < 1e-5);
+
+                Fragment::Color(Vector4::new(input.primitive_id() as f32, input.front_facing() as u8 as f32, barycentric.x, barycentric.y))
+            });
+
+        let color = |x, y| pipeline.framebuffer().pixel_ref(Coordinate::new(x, y)).unwrap().get();
+
+        let bottom_left = color(0, SIZE - 1);
+        let top_right = color(SIZE - 1, 0);
+
+        assert_eq!((bottom_left.x, bottom_left.y), (0.0, 1.0));
+        assert_eq!((top_right.x, top_right.y), (1.0, 0.0));
+
+        // The bottom-left pixel is nearest to the first vertex of its triangle, and the top-right pixel to the second
+        assert!(bottom_left.z > 0.9);
+        assert!(top_right.w > 0.9);
+    }
+
+    #[test]
+    fn test_clamped_uniforms() {
+        let dimensions = Dimensions::new(SIZE, SIZE);
+
+        // Sliver across the screen, so most quads have helper lanes far outside of it
+        let vertices = vec![(-1.0, -1.0, 255, 0, 0), (1.0, -0.9, 0, 255, 0), (1.0, -0.85, 0, 0, 255)].into_iter().map(|(x, y, r, g, b)| SimpleVertex {
+            position: Point3::new(x, y, 0.0),
+            data: Vector4::<u8>::new(r, g, b, 255),
+        }).collect();
+
+        let mut pipeline: Pipeline<_, _> = Pipeline::from_framebuffer(Buffer::with_dimensions(dimensions), ());
+
+        draw(&mut pipeline, Triangle, Arc::new(Mesh { vertices, indices: vec![0, 1, 2] }), None)
+            .with_faces_culled(None)
+            .run_with_input(|input, _| {
+                // Integer uniforms would overflow if any lane were extrapolated beyond the vertices
+                for lane in 0..4 {
+                    let uniforms = input.quad_vertex(lane).uniforms;
+
+                    let sum = uniforms.x as u32 + uniforms.y as u32 + uniforms.z as u32;
+
+                    assert!(253 <= sum && sum <= 255, "lane {}: {:?}", lane, uniforms);
+                }
+
+                let barycentric: Vector3<f32> = input.barycentric();
+
+                assert!(barycentric.iter().all(|&weight| weight >= 0.0));
+
+                Fragment::Color(Vector4::new(1.0, 0.0, 0.0, 1.0))
+            });
+
+        let mut shaded = 0;
+
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                if pipeline.framebuffer().pixel_ref(Coordinate::new(x, y)).unwrap().get().x == 1.0 {
+                    shaded += 1;
+                }
+            }
+        }
+
+        assert!(shaded > 10);
+    }
+
+    #[test]
+    fn test_front_facing() {
+        let dimensions = Dimensions::new(SIZE, SIZE);
+
+        // Bottom-left half of the quad, which is counter-clockwise in normalized device coordinates as with OpenGL,
+        // and so clockwise in screen-space
+        let mesh = Arc::new(Mesh { indices: vec![0, 1, 2], ..fullscreen_quad(|_, _| 0.0) });
+
+        let mut pipeline: Pipeline<_, _> = Pipeline::from_framebuffer(Buffer::with_dimensions(dimensions), ());
+
+        let mut render = |red: f32, cull: Option<FaceWinding>| {
+            draw(&mut pipeline, Triangle, mesh.clone(), None)
+                .with_depth_test(None)
+                .with_faces_culled(cull)
+                .run_with_input(move |input, _| Fragment::Color(Vector4::new(red, input.front_facing() as u8 as f32, 0.0, 1.0)));
+
+            pipeline.framebuffer().pixel_ref(Coordinate::new(0, SIZE - 1)).unwrap().get()
+        };
+
+        assert_eq!(render(1.0, None), Vector4::new(1.0, 1.0, 0.0, 1.0));
+
+        // Culling clockwise faces skips the triangle, while culling counter-clockwise faces doesn't
+        assert_eq!(render(2.0, Some(FaceWinding::Clockwise)), Vector4::new(1.0, 1.0, 0.0, 1.0));
+        assert_eq!(render(3.0, Some(FaceWinding::CounterClockwise)), Vector4::new(3.0, 1.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_guard_band() {
+        let dimensions = Dimensions::new(SIZE, SIZE);
+
+        // Far enough that the vertices can't be snapped to fixed-point, so the triangle is clipped to the guard band
+        let far = 200000.0;
+
+        let vertices = vec![(-1.0, -1.0), (far, -1.0), (-1.0, far)].into_iter().map(|(x, y)| SimpleVertex {
+            position: Point3::new(x, y, 0.0),
+            data: x,
+        }).collect();
+
+        let mut pipeline: Pipeline<_, _> = Pipeline::from_framebuffer(Buffer::with_dimensions(dimensions), ());
+
+        draw(&mut pipeline, Triangle, Arc::new(Mesh { vertices, indices: vec![0, 1, 2] }), None)
+            .with_depth_test(None)
+            .run_with_input(move |input, _| {
+                let barycentric = input.barycentric();
+
+                // Barycentric coordinates are still relative to the whole triangle
+                Fragment::Color(Vector4::new(input.vertex().uniforms, barycentric.y * (far + 1.0) - 1.0, 0.0, 1.0))
+            });
+
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                let color = pipeline.framebuffer().pixel_ref(Coordinate::new(x, y)).unwrap().get();
+
+                let expected = (x as f32 + 0.5) / SIZE as f32 * 2.0 - 1.0;
+
+                assert_eq!(color.w, 1.0);
+                assert!((color.x - expected).abs() < 1e-3);
+                assert!((color.y - expected).abs() < 1e-3);
+            }
+        }
+    }
+}
@@ -1,42 +1,41 @@
 use super::RasterArguments;
 
-use num_traits::{One, Zero, NumCast, cast};
+use num_traits::{Zero, cast};
+use nalgebra::Vector3;
 use nalgebra::coordinates::XYZW;
 
 use ::color::blend::Blend;
-use ::pixels::{PixelRead, PixelWrite};
-use ::framebuffer::UnsafeFramebuffer;
-use ::attachments::depth::Depth;
+use ::framebuffer::FramebufferBase;
 use ::mesh::Vertex;
 use ::geometry::{Coordinate, ScreenVertex, FaceWinding};
 use ::interpolate::Interpolate;
 
 use ::pipeline::PipelineObject;
 
-use ::framebuffer::types::DepthAttachment;
-use ::pipeline::types::{PipelineUniforms, Pixel};
+use ::pipeline::types::Pixel;
 
-use ::pipeline::stages::fragment::Fragment;
+use ::pipeline::stages::fragment::QuadShader;
+use ::pipeline::stages::input::QuadContext;
 
 pub fn rasterize_point<P, V, K, B, F>(args: &RasterArguments<P, V>,
                                       pipeline: &mut P,
                                       blend: B,
-                                      fragment_shader: F,
+                                      fragment_shader: &F,
+                                      primitive_id: usize,
                                       point: &ScreenVertex<V::Scalar, K>)
     where P: PipelineObject,
           V: Vertex,
           K: Send + Sync + Interpolate,
           B: Blend<Pixel<P>>,
-          F: Fn(&ScreenVertex<V::Scalar, K>, &PipelineUniforms<P>) -> Fragment<Pixel<P>> + Send + Sync {
+          F: QuadShader<P, V::Scalar, K> {
     let RasterArguments {
         dimensions,
         tile,
         bounds,
-        stencil_value,
-        stencil_test,
-        stencil_op,
         antialiased_lines,
+        perspective_correct,
         cull_faces,
+        ..
     } = *args;
 
     let (uniforms, framebuffer, _) = pipeline.all_mut();
@@ -48,40 +47,18 @@ pub fn rasterize_point<P, V, K, B, F>(args: &RasterArguments<P, V>,
 
         let index = coord.into_index(dimensions);
 
-        // Get stencil buffer value for this pixel
-        let framebuffer_stencil_value = unsafe { framebuffer.get_stencil_unchecked(index) };
+        // Points cover every sample of their pixel
+        let full_coverage = ((1u64 << framebuffer.samples()) - 1) as u32;
 
-        // perform stencil test
-        if stencil_test.test(framebuffer_stencil_value, stencil_value) {
-            // Calculate new stencil value
-            let new_stencil_value = stencil_op.op(framebuffer_stencil_value, stencil_value);
+        // Points have no slope
+        let z = z - args.depth_offset(Zero::zero());
 
-            // Set stencil value for this pixel
-            unsafe { framebuffer.set_stencil_unchecked(index, new_stencil_value); }
+        args.shade_samples(framebuffer, &blend, index, full_coverage, F::late_tests(), |_| z, |coverage| {
+            // Perform fragment shading
+            // Points have no area, so every pixel in the quad is the same and derivatives are zero
+            let context = QuadContext::new(coord, primitive_id, true, [Vector3::x(); 4]);
 
-            if z < Zero::zero() {
-                let d: DepthAttachment<P::Framebuffer> = Depth::from_scalar(z);
-
-                let dt = unsafe { framebuffer.get_depth_unchecked(index) };
-
-                // Check if point is in front of other geometry
-                if d >= dt {
-                    // Perform fragment shading
-                    let fragment = fragment_shader(point, &uniforms);
-
-                    match fragment {
-                        Fragment::Discard => (),
-                        Fragment::Color(c) => {
-                            let p = unsafe { framebuffer.get_pixel_unchecked(index) };
-
-                            unsafe {
-                                framebuffer.set_pixel_unchecked(index, blend.blend(c, p));
-                                framebuffer.set_depth_unchecked(index, d);
-                            }
-                        }
-                    }
-                }
-            }
-        }
+            fragment_shader.shade_lane([point; 4], &context, 0, coverage, &uniforms)
+        });
     }
 }
\ No newline at end of file
@@ -1,29 +1,70 @@
-use super::RasterArguments;
+use super::{RasterArguments, MAX_SAMPLES};
+use super::edge::{EdgeFunction, SUBPIXEL_SCALE, GUARD_BAND, snap, pixel_center};
 
-use num_traits::{Float, One, Zero, NumCast, cast};
+use std::mem;
+
+use num_traits::{Float, Zero, One, cast};
+use smallvec::SmallVec;
+use nalgebra::Vector3;
 use nalgebra::coordinates::XYZW;
 
+use ::numeric::FloatScalar;
 use ::numeric::utils::min;
 use ::color::ColorAlpha;
 use ::color::blend::Blend;
-use ::pixels::{PixelRead, PixelWrite};
-use ::framebuffer::UnsafeFramebuffer;
-use ::attachments::depth::Depth;
+use ::framebuffer::FramebufferBase;
+use ::framebuffer::multisample::sample_positions;
+use ::framebuffer::supersample::jitter_offset;
 use ::mesh::{Vertex, Mesh};
+
+#[cfg(feature = "packet_shaders")]
+use ::numeric::packet::Packet;
 use ::geometry::{HasDimensions, Coordinate, ScreenVertex, FaceWinding};
 use ::interpolate::Interpolate;
 
 use ::pipeline::PipelineObject;
 
-use ::framebuffer::types::DepthAttachment;
-use ::pipeline::types::{PipelineUniforms, Pixel};
+use ::pipeline::types::Pixel;
+
+use ::pipeline::stages::fragment::QuadShader;
+use ::pipeline::stages::input::QuadContext;
+
+/// Width and height of the blocks triangles are rasterized in, which must be a power of two
+pub const BLOCK_SIZE: u32 = 8;
+
+/// Screen-space position and barycentric coordinates of a vertex of a triangle clipped to the guard band
+type ClippedVertex<N> = (N, N, Vector3<N>);
+
+/// Interpolates the vertex at the given screen-space barycentric coordinates of a triangle
+fn interpolate_vertex<N, K>(screen: (N, N, N), a: &ScreenVertex<N, K>, b: &ScreenVertex<N, K>, c: &ScreenVertex<N, K>,
+                            perspective_correct: bool) -> ScreenVertex<N, K> where N: FloatScalar, K: Interpolate {
+    let (u, v, w) = screen;
 
-use ::pipeline::stages::fragment::Fragment;
+    let position = Interpolate::barycentric_interpolate(u, &a.position, v, &b.position, w, &c.position);
 
+    let perspective = if perspective_correct {
+        // Screen-space w is 1/w in clip-space, which is linear in screen-space,
+        // so weighting by it and renormalizing undoes the perspective division.
+        let (u, v, w) = (u * a.position.w, v * b.position.w, w * c.position.w);
+
+        let sum = u + v + w;
+
+        // Degenerate if every vertex with any weight is at infinity, so fall back to the screen-space weights
+        if sum > Zero::zero() { (u / sum, v / sum, w / sum) } else { screen }
+    } else { (u, v, w) };
+
+    ScreenVertex {
+        position,
+        uniforms: Interpolate::fragment_barycentric_interpolate(perspective, screen, &a.uniforms, &b.uniforms, &c.uniforms),
+    }
+}
+
+/// Rasterizes a triangle, first clipping it to the guard band if any of its vertices lie outside of it
 pub fn rasterize_triangle<P, V, K, B, F>(args: &RasterArguments<P, V>,
                                          pipeline: &mut P,
                                          blend: B,
-                                         fragment_shader: F,
+                                         fragment_shader: &F,
+                                         primitive_id: usize,
                                          a: &ScreenVertex<V::Scalar, K>,
                                          b: &ScreenVertex<V::Scalar, K>,
                                          c: &ScreenVertex<V::Scalar, K>)
@@ -31,16 +72,107 @@ pub fn rasterize_triangle<P, V, K, B, F>(args: &RasterArguments<P, V>,
           V: Vertex,
           K: Send + Sync + Interpolate,
           B: Blend<Pixel<P>>,
-          F: Fn(&ScreenVertex<V::Scalar, K>, &PipelineUniforms<P>) -> Fragment<Pixel<P>> + Send + Sync {
+          F: QuadShader<P, V::Scalar, K> {
+    let band: V::Scalar = cast(GUARD_BAND).unwrap();
+
+    let within_band = |vertex: &ScreenVertex<V::Scalar, K>| vertex.position.x.abs() <= band && vertex.position.y.abs() <= band;
+
+    if within_band(a) && within_band(b) && within_band(c) {
+        return rasterize_in_guard_band(args, pipeline, &blend, fragment_shader, primitive_id, a, b, c, None);
+    }
+
+    if !(a.position.iter().all(|x| x.is_finite()) && b.position.iter().all(|x| x.is_finite()) && c.position.iter().all(|x| x.is_finite())) {
+        return;
+    }
+
+    let (zero, one) = (<V::Scalar as Zero>::zero(), <V::Scalar as One>::one());
+
+    // Vertices of the clipped polygon, given by their position and barycentric coordinates within the triangle
+    let mut polygon: SmallVec<[ClippedVertex<V::Scalar>; 8]> = SmallVec::new();
+
+    polygon.push((a.position.x, a.position.y, Vector3::new(one, zero, zero)));
+    polygon.push((b.position.x, b.position.y, Vector3::new(zero, one, zero)));
+    polygon.push((c.position.x, c.position.y, Vector3::new(zero, zero, one)));
+
+    // Snapped coordinates only have a limited range, so clip the triangle to the guard band with the Sutherland-Hodgman algorithm
+    for &(vertical, sign) in &[(false, one), (false, -one), (true, one), (true, -one)] {
+        let input = mem::replace(&mut polygon, SmallVec::new());
+
+        // Distance outside of the clipping edge, which is positive outside of it
+        let outside = |&(x, y, _): &ClippedVertex<V::Scalar>| (if vertical { y } else { x }) * sign - band;
+
+        for i in 0..input.len() {
+            let (s, p) = (input[(i + input.len() - 1) % input.len()], input[i]);
+
+            let (ds, dp) = (outside(&s), outside(&p));
+
+            // Edge crosses the clipping edge
+            if (ds > zero) != (dp > zero) {
+                let t = ds / (ds - dp);
+
+                let (mut x, mut y) = (s.0 + (p.0 - s.0) * t, s.1 + (p.1 - s.1) * t);
+
+                // Place the intersection exactly on the clipping edge
+                if vertical { y = band * sign; } else { x = band * sign; }
+
+                polygon.push((x, y, Interpolate::linear_interpolate(t, &s.2, &p.2)));
+            }
+
+            if dp <= zero {
+                polygon.push(p);
+            }
+        }
+    }
+
+    let vertex = |&(x, y, barycentric): &ClippedVertex<V::Scalar>| {
+        let mut vertex = interpolate_vertex((barycentric.x, barycentric.y, barycentric.z), a, b, c, args.perspective_correct);
+
+        vertex.position.x = x;
+        vertex.position.y = y;
+
+        vertex
+    };
+
+    if polygon.len() >= 3 {
+        let first = vertex(&polygon[0]);
+
+        // The clipped polygon is convex and keeps the winding of the triangle, so it can be rasterized as a fan
+        for i in 1..polygon.len() - 1 {
+            let basis = [polygon[0].2, polygon[i].2, polygon[i + 1].2];
+
+            rasterize_in_guard_band(args, pipeline, &blend, fragment_shader, primitive_id,
+                                    &first, &vertex(&polygon[i]), &vertex(&polygon[i + 1]), Some(basis));
+        }
+    }
+}
+
+/// Rasterizes a triangle with all of its vertices within the guard band.
+///
+/// Triangles clipped to the guard band also give the barycentric coordinates of each of their vertices
+/// within the original triangle, so the barycentric coordinates given to the fragment shader are relative to it.
+fn rasterize_in_guard_band<P, V, K, B, F>(args: &RasterArguments<P, V>,
+                                          pipeline: &mut P,
+                                          blend: B,
+                                          fragment_shader: &F,
+                                          primitive_id: usize,
+                                          a: &ScreenVertex<V::Scalar, K>,
+                                          b: &ScreenVertex<V::Scalar, K>,
+                                          c: &ScreenVertex<V::Scalar, K>,
+                                          basis: Option<[Vector3<V::Scalar>; 3]>)
+    where P: PipelineObject,
+          V: Vertex,
+          K: Send + Sync + Interpolate,
+          B: Blend<Pixel<P>>,
+          F: QuadShader<P, V::Scalar, K> {
     let RasterArguments {
         dimensions,
         tile,
         bounds,
-        stencil_value,
-        stencil_test,
-        stencil_op,
         antialiased_lines,
+        perspective_correct,
         cull_faces,
+        front_face,
+        ..
     } = *args;
 
     let (uniforms, framebuffer, _) = pipeline.all_mut();
@@ -50,18 +182,67 @@ pub fn rasterize_triangle<P, V, K, B, F>(args: &RasterArguments<P, V>,
     let XYZW { x: x2, y: y2, .. } = *b.position;
     let XYZW { x: x3, y: y3, .. } = *c.position;
 
-    // do backface culling
-    if let Some(winding) = cull_faces {
+    let winding = {
         // Shoelace algorithm for a triangle
         let a = x1 * y2 + x2 * y3 + x3 * y1 - x2 * y1 - x3 * y2 - x1 * y3;
 
-        if winding == if a.is_sign_negative() { FaceWinding::Clockwise } else { FaceWinding::CounterClockwise } {
-            return;
-        }
+        if a.is_sign_negative() { FaceWinding::Clockwise } else { FaceWinding::CounterClockwise }
+    };
+
+    // do backface culling
+    if cull_faces == Some(winding) {
+        return;
     }
 
-    // calculate determinant
-    let det = (y2 - y3) * (x1 - x3) + (x3 - x2) * (y1 - y3);
+    let front_facing = winding == front_face;
+
+    // Back faces use their own stencil state
+    let back_args;
+
+    let args = if front_facing { args } else {
+        back_args = RasterArguments { stencil: args.back_stencil, ..*args };
+        &back_args
+    };
+
+    // Snap vertices to fixed-point, so edge functions are exact and shared edges are watertight
+    let (p1, p2, p3) = match (snap(x1), snap(y1), snap(x2), snap(y2), snap(x3), snap(y3)) {
+        (Some(x1), Some(y1), Some(x2), Some(y2), Some(x3), Some(y3)) => ((x1, y1), (x2, y2), (x3, y3)),
+        _ => unreachable!("Triangle vertices outside of the guard band"),
+    };
+
+    // Twice the signed area of the snapped triangle
+    let area = EdgeFunction::new(p1, p2, 1).evaluate(p3.0, p3.1);
+
+    // Degenerate triangles cover no pixels
+    if area == 0 {
+        return;
+    }
+
+    let orientation = area.signum();
+
+    // Each edge function is the barycentric weight of the opposite vertex, scaled by the area
+    let edges = [EdgeFunction::new(p2, p3, orientation),
+                 EdgeFunction::new(p3, p1, orientation),
+                 EdgeFunction::new(p1, p2, orientation)];
+
+    // Reciprocal of the area, so barycentric coordinates only need a multiplication per pixel
+    let inv_area: V::Scalar = <V::Scalar as One>::one() / cast::<_, V::Scalar>(area * orientation).unwrap();
+
+    // Largest change in screen-space depth per pixel across the triangle, for slope-scaled depth bias
+    let slope = {
+        let dz = |step: [i64; 3]| {
+            (cast::<_, V::Scalar>(step[0]).unwrap() * a.position.z +
+                cast::<_, V::Scalar>(step[1]).unwrap() * b.position.z +
+                cast::<_, V::Scalar>(step[2]).unwrap() * c.position.z) * inv_area
+        };
+
+        let dzdx = dz([edges[0].step_x(), edges[1].step_x(), edges[2].step_x()]);
+        let dzdy = dz([edges[0].step_y(), edges[1].step_y(), edges[2].step_y()]);
+
+        dzdx.abs().max(dzdy.abs())
+    };
+
+    let depth_offset = args.depth_offset(slope);
 
     macro_rules! clamp_as_int {
         ($value:expr, $min:expr, $max:expr) => {{
@@ -77,80 +258,412 @@ pub fn rasterize_triangle<P, V, K, B, F>(args: &RasterArguments<P, V>,
     let max = Coordinate::new(clamp_as_int!(x1.max(x2).max(x3), tile.0.x, tile.1.x),
                               clamp_as_int!(y1.max(y2).max(y3), tile.0.y, tile.1.y));
 
-    let mut pixel = min;
+    let samples = framebuffer.samples();
+
+    // Edge function offsets from the center of a pixel to each of its samples
+    let mut sample_offsets = [[0; 3]; MAX_SAMPLES];
+
+    for (sample, &(sx, sy)) in sample_positions(samples).expect("Invalid Sample Count").iter().enumerate() {
+        let (dx, dy) = (sx as i64 * SUBPIXEL_SCALE / 16, sy as i64 * SUBPIXEL_SCALE / 16);
+
+        for i in 0..3 {
+            sample_offsets[sample][i] = edges[i].delta(dx, dy);
+        }
+    }
+
+    // Coverage mask of a pixel that is entirely inside the triangle
+    let full_coverage = ((1u64 << samples) - 1) as u32;
+
+    let jitter = framebuffer.jitter();
+
+    // Samples may be up to half a pixel away from pixel centers
+    let margin = if samples > 1 || jitter.is_some() { SUBPIXEL_SCALE / 2 } else { 0 };
+
+    // calculate barycentric coordinates from edge function values
+    let barycentric = |e: [i64; 3]| {
+        let u = cast::<_, V::Scalar>(e[0]).unwrap() * inv_area;
+        let v = cast::<_, V::Scalar>(e[1]).unwrap() * inv_area;
+
+        (u, v, <V::Scalar as One>::one() - u - v)
+    };
+
+    // Edge function values of a sample, given the values at the center of its pixel
+    #[cfg(not(feature = "packet_shaders"))]
+    let sample_edges = |e: [i64; 3], sample: usize| {
+        [e[0] + sample_offsets[sample][0], e[1] + sample_offsets[sample][1], e[2] + sample_offsets[sample][2]]
+    };
+
+    // Shades the covered pixels of a 2x2 quad, given the edge function values and coverage mask of each lane
+    let mut shade_quad = |quad: Coordinate, e: &[[i64; 3]; 4], coverage: [u32; 4]| {
+        let mut indices = [0; 4];
+
+        for lane in 0..4 {
+            if coverage[lane] != 0 {
+                let pixel = Coordinate::new(quad.x + (lane as u32 & 1), quad.y + (lane as u32 >> 1));
+
+                indices[lane] = pixel.into_index(dimensions);
+
+                debug_assert!(indices[lane] < dimensions.area());
+            }
+        }
+
+        #[cfg(not(feature = "packet_shaders"))]
+        let depth = |lane: usize, sample: u32| {
+            let (u, v, w) = barycentric(sample_edges(e[lane], sample as usize));
+
+            // interpolate screen-space depth
+            u * a.position.z + v * b.position.z + w * c.position.z - depth_offset
+        };
+
+        // Interpolate screen-space depth of every sample for all lanes at once
+        #[cfg(feature = "packet_shaders")]
+        let depths = {
+            let e = [Packet::generate(|lane| e[lane][0]), Packet::generate(|lane| e[lane][1])];
+
+            let (za, zb, zc) = (Packet::splat(a.position.z), Packet::splat(b.position.z), Packet::splat(c.position.z));
+
+            let mut depths = [Packet::splat(depth_offset); MAX_SAMPLES];
+
+            for sample in 0..samples as usize {
+                let to_barycentric = |e: Packet<i64>| e.map(|e| cast::<_, V::Scalar>(e).unwrap()) * Packet::splat(inv_area);
+
+                let u = to_barycentric(e[0] + Packet::splat(sample_offsets[sample][0]));
+                let v = to_barycentric(e[1] + Packet::splat(sample_offsets[sample][1]));
+                let w = Packet::splat(<V::Scalar as One>::one()) - u - v;
+
+                depths[sample] = u * za + v * zb + w * zc - Packet::splat(depth_offset);
+            }
+
+            depths
+        };
+
+        #[cfg(feature = "packet_shaders")]
+        let depth = |lane: usize, sample: u32| depths[sample as usize].lane(lane);
 
-    while pixel.y <= max.y {
-        pixel.x = min.x;
+        args.shade_quad(framebuffer, &blend, indices, coverage, F::late_tests(), depth, |coverage| {
+            let interpolate = |screen| interpolate_vertex(screen, a, b, c, perspective_correct);
 
-        while pixel.x <= max.x {
-            let index = pixel.into_index(dimensions);
+            // Helper lanes, and the sample positions of partially covered pixels, may lie outside of the triangle,
+            // so clamp them onto it to avoid extrapolating attributes beyond the values at its vertices.
+            let clamped = |e: [i64; 3]| {
+                let (u, v, w) = barycentric(e);
 
-            debug_assert!(index < dimensions.area());
+                let zero = <V::Scalar as Zero>::zero();
 
-            // Get stencil buffer value for this pixel
-            let framebuffer_stencil_value = unsafe { framebuffer.get_stencil_unchecked(index) };
+                let (u, v, w) = (u.max(zero), v.max(zero), w.max(zero));
 
-            // perform stencil test
-            if stencil_test.test(framebuffer_stencil_value, stencil_value) {
-                // Calculate new stencil value
-                let new_stencil_value = stencil_op.op(framebuffer_stencil_value, stencil_value);
+                let sum = u + v + w;
 
-                // Set stencil value for this pixel
-                unsafe { framebuffer.set_stencil_unchecked(index, new_stencil_value); }
+                (u / sum, v / sum, w / sum)
+            };
 
-                //continue on to fragment shading
+            let barycentrics = [clamped(e[0]), clamped(e[1]), clamped(e[2]), clamped(e[3])];
 
-                // Real screen position should be in the center of the pixel.
-                let (x, y) = (cast::<_, V::Scalar>(pixel.x).unwrap() + NumCast::from(0.5).unwrap(),
-                              cast::<_, V::Scalar>(pixel.y).unwrap() + NumCast::from(0.5).unwrap());
+            let vertices = [interpolate(barycentrics[0]), interpolate(barycentrics[1]),
+                            interpolate(barycentrics[2]), interpolate(barycentrics[3])];
 
-                // calculate barycentric coordinates of the current point
-                let u = ((y2 - y3) * (x - x3) + (x3 - x2) * (y - y3)) / det;
-                let v = ((y3 - y1) * (x - x3) + (x1 - x3) * (y - y3)) / det;
-                let w = <V::Scalar as One>::one() - u - v;
+            let to_vector = |(u, v, w)| match basis {
+                Some(ref basis) => basis[0] * u + basis[1] * v + basis[2] * w,
+                None => Vector3::new(u, v, w),
+            };
 
-                // Determine if pixel is even within the triangle
-                if !(u < Zero::zero() || v < Zero::zero() || w < Zero::zero()) {
-                    // interpolate screen-space position
-                    let position = Interpolate::barycentric_interpolate(u, &a.position, v, &b.position, w, &c.position);
+            let context = QuadContext::new(quad, primitive_id, front_facing,
+                                           [to_vector(barycentrics[0]), to_vector(barycentrics[1]),
+                                            to_vector(barycentrics[2]), to_vector(barycentrics[3])]);
 
-                    let z = position.z;
+            // Perform fragment shading
+            fragment_shader.shade([&vertices[0], &vertices[1], &vertices[2], &vertices[3]], &context, coverage, uniforms)
+        });
+    };
 
-                    // Check if point is in front of the screen
-                    if z < Zero::zero() {
-                        let d: DepthAttachment<P::Framebuffer> = Depth::from_scalar(z);
+    // Edge function increments between neighboring pixels
+    let step_x = [edges[0].step_x(), edges[1].step_x(), edges[2].step_x()];
+    let step_y = [edges[0].step_y(), edges[1].step_y(), edges[2].step_y()];
 
-                        let dt = unsafe { framebuffer.get_depth_unchecked(index) };
+    // Triangles are rasterized in blocks aligned to multiples of the block size, and each block is rasterized
+    // in 2x2 quads aligned to even coordinates, so that every fragment has neighbors to compute
+    // screen-space derivatives with, even at the edges of the triangle.
+    let mut block = Coordinate::new(min.x & !(BLOCK_SIZE - 1), min.y & !(BLOCK_SIZE - 1));
 
-                        // Check if point is in front of other geometry
-                        if d >= dt {
-                            // Perform fragment shading
-                            let fragment = fragment_shader(&ScreenVertex {
-                                position,
-                                uniforms: Interpolate::barycentric_interpolate(u, &a.uniforms,
-                                                                               v, &b.uniforms,
-                                                                               w, &c.uniforms),
-                            }, uniforms);
+    while block.y <= max.y {
+        block.x = min.x & !(BLOCK_SIZE - 1);
 
-                            match fragment {
-                                Fragment::Discard => (),
-                                Fragment::Color(c) => {
-                                    let p = unsafe { framebuffer.get_pixel_unchecked(index) };
+        'blocks: while block.x <= max.x {
+            let (x, y) = pixel_center(block.x, block.y);
 
-                                    unsafe {
-                                        framebuffer.set_pixel_unchecked(index, blend.blend(c, p));
-                                        framebuffer.set_depth_unchecked(index, d);
-                                    }
-                                }
+            // Edge function values at the center of the top-left pixel of the block
+            let e_block = [edges[0].evaluate(x, y), edges[1].evaluate(x, y), edges[2].evaluate(x, y)];
+
+            // Blocks entirely within the bounding box and triangle don't need any per-pixel tests
+            let mut accept = min.x <= block.x && block.x + BLOCK_SIZE - 1 <= max.x &&
+                min.y <= block.y && block.y + BLOCK_SIZE - 1 <= max.y;
+
+            for i in 0..3 {
+                let (lowest, highest) = edges[i].block_range(e_block[i], BLOCK_SIZE, margin);
+
+                // Trivially reject blocks entirely outside of any edge
+                if !edges[i].test(highest) {
+                    block.x += BLOCK_SIZE;
+
+                    continue 'blocks;
+                }
+
+                accept &= edges[i].test(lowest);
+            }
+
+            let mut e_row = e_block;
+
+            let mut quad = block;
+
+            while quad.y < block.y + BLOCK_SIZE && quad.y <= max.y {
+                let mut e_quad = e_row;
+
+                quad.x = block.x;
+
+                while quad.x < block.x + BLOCK_SIZE && quad.x <= max.x {
+                    let mut e = [e_quad; 4];
+                    let mut coverage = [if accept { full_coverage } else { 0 }; 4];
+
+                    // Lanes within the tile and bounding box of the triangle
+                    let mut lanes = 0;
+
+                    for lane in 0..4 {
+                        for i in 0..3 {
+                            if lane & 1 != 0 { e[lane][i] += step_x[i]; }
+                            if lane & 2 != 0 { e[lane][i] += step_y[i]; }
+                        }
+
+                        let pixel = Coordinate::new(quad.x + (lane as u32 & 1), quad.y + (lane as u32 >> 1));
+
+                        // Move the sample position of the pixel away from its center,
+                        // which also moves where its attributes are interpolated
+                        if let Some(seed) = jitter {
+                            let (jx, jy) = jitter_offset(seed, pixel.x, pixel.y);
+
+                            for i in 0..3 {
+                                e[lane][i] += edges[i].delta(jx as i64 * SUBPIXEL_SCALE / 16, jy as i64 * SUBPIXEL_SCALE / 16);
                             }
                         }
+
+                        if min.x <= pixel.x && pixel.x <= max.x && min.y <= pixel.y && pixel.y <= max.y {
+                            lanes |= 1 << lane;
+                        }
+                    }
+
+                    // Determine which samples of each pixel are within the tile and triangle,
+                    // otherwise it's only a helper
+                    if !accept {
+                        coverage = quad_coverage(&edges, &e, &sample_offsets, samples, lanes);
                     }
+
+                    if coverage.iter().any(|&coverage| coverage != 0) {
+                        shade_quad(quad, &e, coverage);
+                    }
+
+                    for i in 0..3 { e_quad[i] += step_x[i] * 2; }
+
+                    quad.x += 2;
+                }
+
+                for i in 0..3 { e_row[i] += step_y[i] * 2; }
+
+                quad.y += 2;
+            }
+
+            block.x += BLOCK_SIZE;
+        }
+
+        block.y += BLOCK_SIZE;
+    }
+}
+
+/// Coverage mask of each lane of a quad, given the edge function values at each lane and the offsets to each sample.
+///
+/// Only lanes in the `lanes` bitmask are tested.
+#[cfg(not(feature = "packet_shaders"))]
+#[inline]
+fn quad_coverage(edges: &[EdgeFunction; 3], e: &[[i64; 3]; 4], sample_offsets: &[[i64; 3]; MAX_SAMPLES], samples: u32, lanes: u32) -> [u32; 4] {
+    let mut coverage = [0; 4];
+
+    for lane in 0..4 {
+        if lanes & (1 << lane) != 0 {
+            for sample in 0..samples as usize {
+                let offsets = &sample_offsets[sample];
+
+                if edges[0].test(e[lane][0] + offsets[0]) && edges[1].test(e[lane][1] + offsets[1]) && edges[2].test(e[lane][2] + offsets[2]) {
+                    coverage[lane] |= 1 << sample;
+                }
+            }
+        }
+    }
+
+    coverage
+}
+
+/// Coverage mask of each lane of a quad, given the edge function values at each lane and the offsets to each sample.
+///
+/// Only lanes in the `lanes` bitmask are tested. Each sample is tested for all lanes at once.
+#[cfg(feature = "packet_shaders")]
+#[inline]
+fn quad_coverage(edges: &[EdgeFunction; 3], e: &[[i64; 3]; 4], sample_offsets: &[[i64; 3]; MAX_SAMPLES], samples: u32, lanes: u32) -> [u32; 4] {
+    let e = [Packet::generate(|lane| e[lane][0]),
+             Packet::generate(|lane| e[lane][1]),
+             Packet::generate(|lane| e[lane][2])];
+
+    let mut coverage = [0; 4];
+
+    for sample in 0..samples as usize {
+        let offsets = &sample_offsets[sample];
+
+        let inside = lanes & edges[0].test_packet(e[0] + Packet::splat(offsets[0]))
+                           & edges[1].test_packet(e[1] + Packet::splat(offsets[1]))
+                           & edges[2].test_packet(e[2] + Packet::splat(offsets[2]));
+
+        for lane in 0..4 {
+            coverage[lane] |= ((inside >> lane) & 1) << sample;
+        }
+    }
+
+    coverage
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use nalgebra::{Vector4, Point3};
+
+    use ::geometry::{Dimensions, Coordinate, ClipVertex, Viewport};
+    use ::framebuffer::RenderBuffer;
+    use ::attachments::predefined::ColorDepthAttachments;
+    use ::color::blend::GenericBlend;
+    use ::pixels::PixelRead;
+    use ::primitive::Triangle;
+    use ::mesh::{Mesh, SimpleVertex};
+    use ::pipeline::{Pipeline, PipelineObject};
+    use ::pipeline::stages::fragment::Fragment;
+
+    use super::super::edge::{EdgeFunction, snap, pixel_center};
+
+    const SIZE: u32 = 32;
+
+    /// Number of cells along each side of the grid
+    const CELLS: usize = 8;
+
+    /// Renders triangles given in screen-space with additive blending,
+    /// and returns the number of times each pixel was shaded.
+    fn coverage(points: &[(f32, f32)], indices: Vec<usize>) -> Vec<f32> {
+        let dimensions = Dimensions::new(SIZE, SIZE);
+
+        // Screen-space to normalized device coordinates
+        let vertices = points.iter().map(|&(x, y)| SimpleVertex {
+            position: Point3::new(x / (SIZE as f32 / 2.0) - 1.0, 1.0 - y / (SIZE as f32 / 2.0), 0.0),
+            data: (),
+        }).collect();
+
+        let buffer = RenderBuffer::<ColorDepthAttachments<Vector4<f32>, f32>>::with_dimensions(dimensions);
+
+        let mut pipeline: Pipeline<_, _> = Pipeline::from_framebuffer(buffer, ());
+
+        pipeline.render_mesh(Triangle, Arc::new(Mesh { vertices, indices }), None)
+            .run_to_fragment(Viewport::new(dimensions, Coordinate::new(0, 0), 0.0, 1.0), |vertex, _| {
+                ClipVertex::new(vertex.position.to_homogeneous(), ())
+            })
+            .with_blend(GenericBlend::new(|a, b| a + b))
+            .run(|_, _| Fragment::Color(Vector4::new(1.0, 0.0, 0.0, 0.0)));
+
+        let mut counts = Vec::new();
+
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                counts.push(pipeline.framebuffer().pixel_ref(Coordinate::new(x, y)).unwrap().get().x);
+            }
+        }
+
+        counts
+    }
+
+    /// Renders a tessellated grid of triangles covering the whole screen.
+    ///
+    /// Grid lines start off-screen and are spaced 5 pixels apart, offset by half a pixel so they pass through pixel centers,
+    /// and `jitter` moves each vertex by a pseudo-random offset.
+    fn grid_coverage(jitter: f32) -> Vec<f32> {
+        let mut points = Vec::new();
+
+        for j in 0..(CELLS + 1) {
+            for i in 0..(CELLS + 1) {
+                let offset = |k: usize| jitter * (((i * 7 + j * 13 + k * 5) % 11) as f32 / 10.0 - 0.5);
+
+                points.push((i as f32 * 5.0 - 4.5 + offset(0), j as f32 * 5.0 - 4.5 + offset(1)));
+            }
+        }
+
+        let mut indices = Vec::new();
+
+        for j in 0..CELLS {
+            for i in 0..CELLS {
+                let tl = j * (CELLS + 1) + i;
+                let (tr, bl, br) = (tl + 1, tl + CELLS + 1, tl + CELLS + 2);
+
+                // Alternate both the diagonal and the winding order of each cell
+                match (i + j) % 4 {
+                    0 => indices.extend_from_slice(&[tl, tr, bl, tr, br, bl]),
+                    1 => indices.extend_from_slice(&[tl, br, tr, tl, bl, br]),
+                    2 => indices.extend_from_slice(&[tl, bl, tr, tr, bl, br]),
+                    _ => indices.extend_from_slice(&[tl, tr, br, tl, br, bl]),
                 }
             }
+        }
+
+        coverage(&points, indices)
+    }
+
+    /// Tests every pixel center against the edge functions of a single triangle
+    fn reference_coverage(points: &[(f32, f32); 3]) -> Vec<f32> {
+        let p: Vec<_> = points.iter().map(|&(x, y)| (snap(x).unwrap(), snap(y).unwrap())).collect();
+
+        let orientation = EdgeFunction::new(p[0], p[1], 1).evaluate(p[2].0, p[2].1).signum();
 
-            pixel.x += 1;
+        let edges = [EdgeFunction::new(p[1], p[2], orientation),
+                     EdgeFunction::new(p[2], p[0], orientation),
+                     EdgeFunction::new(p[0], p[1], orientation)];
+
+        let mut counts = Vec::new();
+
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                let (x, y) = pixel_center(x, y);
+
+                counts.push(if edges.iter().all(|edge| edge.test(edge.evaluate(x, y))) { 1.0 } else { 0.0 });
+            }
         }
 
-        pixel.y += 1;
+        counts
+    }
+
+    #[test]
+    fn test_fill_rule_grid() {
+        assert!(grid_coverage(0.0).iter().all(|&count| count == 1.0));
+    }
+
+    #[test]
+    fn test_fill_rule_jittered_grid() {
+        assert!(grid_coverage(2.0).iter().all(|&count| count == 1.0));
+    }
+
+    #[test]
+    fn test_block_coverage() {
+        let triangles = [
+            // Large triangle with many fully covered blocks
+            [(-3.2, 1.7), (34.6, 4.1), (6.3, 30.9)],
+            // Thin sliver crossing many blocks it barely covers
+            [(0.3, 0.6), (31.7, 29.2), (30.1, 31.4)],
+            // Small triangle within a single block
+            [(9.1, 9.4), (14.8, 10.2), (10.7, 15.3)],
+        ];
+
+        for points in &triangles {
+            assert_eq!(coverage(points, vec![0, 1, 2]), reference_coverage(points));
+        }
     }
-}
\ No newline at end of file
+}
@@ -7,11 +7,12 @@ use ::parallel::{TrustedThreadSafe, CACHE_LINE_SIZE, Mapper};
 
 use ::pipeline::storage::{SeparablePrimitiveStorage, SeparableScreenPrimitiveStorage};
 use ::pipeline::{PipelineObject, GeometryShader, FragmentShader};
-use ::pipeline::stages::fragment::DEFAULT_TILE_SIZE;
+use ::pipeline::stages::fragment::{DEFAULT_TILE_SIZE, DEFAULT_DEPTH_TEST, DepthBias};
 use ::primitive::Primitive;
 use ::mesh::{Vertex, Mesh};
 use ::interpolate::Interpolate;
-use ::geometry::{ScreenVertex, Viewport, ClipVertex};
+use ::color::ColorMask;
+use ::geometry::{ScreenVertex, Viewport, ClipVertex, FaceWinding};
 
 use ::pipeline::types::{PipelineUniforms, StencilValue};
 
@@ -153,8 +154,15 @@ impl<'a, P: 'a, V, T> VertexShader<'a, P, V, T> where P: PipelineObject,
             indexed_vertices: Arc::new(Some(indexed_vertices)),
             generated_primitives: Arc::new(SeparableScreenPrimitiveStorage::default()),
             cull_faces: None,
+            front_face: FaceWinding::Clockwise,
             blend: (),
             antialiased_lines: false,
+            perspective_correct: true,
+            depth_test: Some(DEFAULT_DEPTH_TEST),
+            depth_write: true,
+            depth_bias: DepthBias::default(),
+            scissor: None,
+            color_mask: ColorMask::all(),
             tile_size: DEFAULT_TILE_SIZE,
         }
     }
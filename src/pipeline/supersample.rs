@@ -0,0 +1,194 @@
+//! Supersampled rendering
+
+use std::sync::Arc;
+use std::marker::PhantomData;
+
+use scoped_threadpool::Pool;
+
+use ::error::RenderResult;
+use ::mesh::{Vertex, Mesh};
+use ::primitive::Primitive;
+use ::stencil::StencilConfig;
+use ::pixels::PixelBuffer;
+use ::interpolate::Interpolate;
+use ::framebuffer::{Framebuffer, Attachments, SupersampleBuffer, DownsampleFilter};
+use ::framebuffer::types::ColorAttachment;
+
+use super::{Pipeline, PipelineObject, VertexShader};
+use super::types::StencilValue;
+
+/// Pipeline rendering into a `SupersampleBuffer`, which borrows the uniforms,
+/// stencil configuration and threadpool of the `Pipeline` it was created from.
+///
+/// See `Pipeline::supersample`.
+pub struct SupersamplePipeline<'a, U: 'a, A: Attachments, S: 'a> {
+    framebuffer: SupersampleBuffer<A>,
+    uniforms: &'a mut U,
+    stencil_config: &'a mut S,
+    threadpool: &'a mut Pool,
+}
+
+impl<'a, U: 'a, A, S: 'a> PipelineObject for SupersamplePipeline<'a, U, A, S> where U: Send + Sync,
+                                                                                    A: Attachments,
+                                                                                    S: StencilConfig {
+    type Framebuffer = SupersampleBuffer<A>;
+    type Uniforms = U;
+    type StencilConfig = S;
+
+    #[inline]
+    fn threadpool(&self) -> &Pool { self.threadpool }
+
+    #[inline]
+    fn threadpool_mut(&mut self) -> &mut Pool { self.threadpool }
+
+    #[inline]
+    fn stencil_config(&self) -> &Self::StencilConfig {
+        self.stencil_config
+    }
+
+    #[inline]
+    fn stencil_config_mut(&mut self) -> &mut Self::StencilConfig {
+        self.stencil_config
+    }
+
+    #[inline]
+    fn uniforms(&self) -> &Self::Uniforms { self.uniforms }
+    #[inline]
+    fn uniforms_mut(&mut self) -> &mut Self::Uniforms { self.uniforms }
+
+    #[inline]
+    fn framebuffer(&self) -> &Self::Framebuffer { &self.framebuffer }
+    #[inline]
+    fn framebuffer_mut(&mut self) -> &mut Self::Framebuffer { &mut self.framebuffer }
+
+    #[inline]
+    fn all_mut(&mut self) -> (&Self::Uniforms, &mut Self::Framebuffer, &mut Pool) {
+        (self.uniforms, &mut self.framebuffer, self.threadpool)
+    }
+}
+
+impl<'a, U: 'a, A, S: 'a> SupersamplePipeline<'a, U, A, S> where Self: PipelineObject, A: Attachments {
+    /// Start the shading pipeline for a given mesh, with an optional stencil value for the mesh.
+    ///
+    /// This is the same as `Pipeline::render_mesh`, so any viewport should cover the dimensions of the supersampled framebuffer.
+    #[must_use]
+    pub fn render_mesh<T, V>(&mut self, primitive: T, mesh: Arc<Mesh<V>>, stencil: Option<StencilValue<Self>>) -> VertexShader<Self, V, T>
+        where T: Primitive, V: Vertex {
+        assert_eq!(mesh.indices.len() % T::num_vertices(), 0);
+
+        drop(primitive);
+
+        VertexShader { pipeline: self, mesh, stencil_value: stencil.unwrap_or_default(), indexed_primitive: PhantomData }
+    }
+}
+
+impl<U, F, S> Pipeline<U, F, S> where U: Send + Sync,
+                                      F: Framebuffer + PixelBuffer<Color = ColorAttachment<F>>,
+                                      S: StencilConfig,
+                                      ColorAttachment<F>: Interpolate {
+    /// Renders at `scale` times the resolution of the framebuffer along each axis, then downsamples the result
+    /// into the framebuffer with the given filter.
+    ///
+    /// The supersampled framebuffer starts out as a scaled copy of the framebuffer, and everything
+    /// rendered into the `SupersamplePipeline` given to `render` is downsampled afterwards.
+    /// Only the color attachment of the framebuffer is updated.
+    ///
+    /// If `jitter` is given, the sample position of every supersampled pixel is randomly offset within the pixel,
+    /// using the value as a seed.
+    ///
+    /// Throws `RenderError::InvalidSampleCount` if the scale is zero.
+    ///
+    /// ```ignore
+    /// pipeline.supersample(4, None, DownsampleFilter::Lanczos(2), |pipeline| {
+    ///     let viewport = Viewport::new(pipeline.framebuffer().dimensions(), Coordinate::new(0, 0), 0.0, 1.0);
+    ///
+    ///     pipeline.render_mesh(Triangle, mesh.clone(), None)
+    ///             .run_to_fragment(viewport, vertex_shader)
+    ///             .run(fragment_shader);
+    /// })?;
+    /// ```
+    pub fn supersample<R>(&mut self, scale: u32, jitter: Option<u32>, filter: DownsampleFilter, render: R) -> RenderResult<()>
+        where R: FnOnce(&mut SupersamplePipeline<U, F::Attachments, S>) {
+        let framebuffer = SupersampleBuffer::from_framebuffer(&self.framebuffer, scale)?.with_jitter(jitter);
+
+        let framebuffer = {
+            let Pipeline { ref mut uniforms, ref mut stencil_config, ref mut threadpool, .. } = *self;
+
+            let mut pipeline = SupersamplePipeline { framebuffer, uniforms, stencil_config, threadpool };
+
+            render(&mut pipeline);
+
+            pipeline.framebuffer
+        };
+
+        framebuffer.downsample(&mut self.framebuffer, filter)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use nalgebra::{Vector4, Point3};
+
+    use ::geometry::{Dimensions, HasDimensions, Coordinate, ClipVertex, Viewport};
+    use ::framebuffer::{RenderBuffer, DownsampleFilter};
+    use ::attachments::predefined::ColorDepthAttachments;
+    use ::pixels::PixelRead;
+    use ::primitive::Triangle;
+    use ::mesh::{Mesh, SimpleVertex};
+    use ::pipeline::{Pipeline, PipelineObject};
+    use ::pipeline::stages::fragment::Fragment;
+
+    fn render(jitter: Option<u32>) -> Pipeline<(), RenderBuffer<ColorDepthAttachments<Vector4<f32>, f32>>> {
+        let dimensions = Dimensions::new(8, 8);
+
+        // Triangle covering the top-left half of the screen, with the diagonal passing through pixel centers
+        let vertices = vec![(-1.0, 1.0), (1.0, 1.0), (-1.0, -1.0)].into_iter().map(|(x, y)| SimpleVertex {
+            position: Point3::new(x, y, 0.0),
+            data: (),
+        }).collect();
+
+        let mesh = Arc::new(Mesh { vertices, indices: vec![0, 1, 2] });
+
+        let mut pipeline = Pipeline::from_framebuffer(RenderBuffer::with_dimensions(dimensions), ());
+
+        pipeline.supersample(4, jitter, DownsampleFilter::Box, |pipeline| {
+            let dimensions = pipeline.framebuffer().dimensions();
+
+            assert_eq!(dimensions, Dimensions::new(32, 32));
+
+            pipeline.render_mesh(Triangle, mesh, None)
+                .run_to_fragment(Viewport::new(dimensions, Coordinate::new(0, 0), 0.0, 1.0), |vertex, _| {
+                    ClipVertex::new(vertex.position.to_homogeneous(), ())
+                })
+                .run(|_, _| Fragment::Color(Vector4::new(1.0, 1.0, 1.0, 1.0)));
+        }).unwrap();
+
+        pipeline
+    }
+
+    #[test]
+    fn test_supersample() {
+        let pipeline = render(None);
+
+        let coverage = |x, y| pipeline.framebuffer().pixel_ref(Coordinate::new(x, y)).unwrap().get().x;
+
+        assert_eq!(coverage(0, 0), 1.0);
+        assert_eq!(coverage(7, 7), 0.0);
+
+        // Six of the sixteen samples of pixels along the diagonal are inside the triangle,
+        // since the four samples exactly on the diagonal are on its bottom-right edge
+        assert!((coverage(3, 4) - 6.0 / 16.0).abs() < 1e-6);
+
+        let pipeline = render(Some(1));
+
+        let coverage = |x, y| pipeline.framebuffer().pixel_ref(Coordinate::new(x, y)).unwrap().get().x;
+
+        assert_eq!(coverage(0, 0), 1.0);
+        assert_eq!(coverage(7, 7), 0.0);
+
+        // Jittered samples along the diagonal are only roughly half covered
+        assert!(coverage(3, 4) > 0.2 && coverage(3, 4) < 0.8);
+    }
+}
@@ -21,6 +21,12 @@ pub trait Stencil: ThreadSafeCopyable + Default + PartialOrd {
     fn saturating_add(self, rhs: Self) -> Self;
     /// Equivalent to `saturating_sub` on primitive integers
     fn saturating_sub(self, rhs: Self) -> Self;
+    /// Equivalent to `BitAnd::bitand`
+    fn and(self, rhs: Self) -> Self;
+    /// Equivalent to `BitOr::bitor`
+    fn or(self, rhs: Self) -> Self;
+    /// Truncates the bits of a `u64` to this type, equivalent to an `as` cast on primitive integers
+    fn from_bits(bits: u64) -> Self;
 }
 
 macro_rules! impl_stencil {
@@ -55,6 +61,15 @@ macro_rules! impl_stencil {
                 fn saturating_sub(self, rhs: $t) -> $t {
                     <$t>::saturating_sub(self, rhs)
                 }
+
+                #[inline(always)]
+                fn and(self, rhs: $t) -> $t { self & rhs }
+
+                #[inline(always)]
+                fn or(self, rhs: $t) -> $t { self | rhs }
+
+                #[inline(always)]
+                fn from_bits(bits: u64) -> $t { bits as $t }
             }
         )+
     }
@@ -83,6 +98,15 @@ impl Stencil for () {
 
     #[inline(always)]
     fn saturating_sub(self, _: Self) -> Self { () }
+
+    #[inline(always)]
+    fn and(self, _: Self) -> Self { () }
+
+    #[inline(always)]
+    fn or(self, _: Self) -> Self { () }
+
+    #[inline(always)]
+    fn from_bits(_: u64) -> Self { () }
 }
 
 /// Defines tests which can be performed on stencil buffers
@@ -133,7 +157,8 @@ pub enum StencilOp {
     Invert,
     /// Replace the previous value with zero
     Zero,
-    /// Replace the previous value with the mesh value
+    /// Replace the previous value with the stencil reference value,
+    /// which is the configured reference value if there is one, or else the mesh value
     Replace,
     /// Increment the previous value by one, wrapping as desired.
     Increment { wrap: bool },
@@ -142,14 +167,14 @@ pub enum StencilOp {
 }
 
 impl StencilOp {
-    /// Performs the operation on the value, returning the new value
+    /// Performs the operation on the value with the given reference value, returning the new value
     #[inline]
-    pub fn op<T>(&self, value: T, mask: T) -> T where T: Stencil {
+    pub fn op<T>(&self, value: T, reference: T) -> T where T: Stencil {
         match *self {
             StencilOp::Keep => value,
             StencilOp::Invert => Stencil::not(value),
             StencilOp::Zero => Stencil::zero(),
-            StencilOp::Replace => mask,
+            StencilOp::Replace => reference,
             StencilOp::Increment { wrap: true } => Stencil::wrapping_add(value, Stencil::one()),
             StencilOp::Decrement { wrap: true } => Stencil::wrapping_sub(value, Stencil::one()),
             StencilOp::Increment { wrap: false } => Stencil::saturating_add(value, Stencil::one()),
@@ -158,12 +183,55 @@ impl StencilOp {
     }
 }
 
-/// Defines a stateful configuration for a stencil buffer
+/// Defines a stateful configuration for a stencil buffer, similar to `glStencilFunc`, `glStencilOp` and `glStencilMask` in OpenGL.
+///
+/// Stencil reference values and masks are given as `u64` bits, which are truncated to the stencil type of the framebuffer.
+///
+/// Back-facing triangles use the `get_back_*` test and operations, which default to those of front faces,
+/// similar to `glStencilFuncSeparate` and `glStencilOpSeparate`. The reference value and masks are shared by both faces.
 pub trait StencilConfig: Clone + Copy + Default {
-    /// Return the operation to be performed
+    /// Return the operation to be performed when both the stencil and depth tests pass,
+    /// and the fragment is not discarded by the fragment shader
     fn get_op(&self) -> StencilOp;
     /// Return the test to be performed
     fn get_test(&self) -> StencilTest;
+
+    /// Return the operation to be performed when the stencil test fails
+    #[inline(always)]
+    fn get_fail_op(&self) -> StencilOp { StencilOp::Keep }
+
+    /// Return the operation to be performed when the stencil test passes but the depth test fails
+    #[inline(always)]
+    fn get_depth_fail_op(&self) -> StencilOp { StencilOp::Keep }
+
+    /// Return the reference value used by the stencil test and `StencilOp::Replace`,
+    /// or `None` to use the stencil value given for each mesh.
+    #[inline(always)]
+    fn get_reference(&self) -> Option<u64> { None }
+
+    /// Return the mask applied to both the reference and stored values before the stencil test
+    #[inline(always)]
+    fn get_read_mask(&self) -> u64 { !0 }
+
+    /// Return the mask of bits the stencil operations are allowed to write
+    #[inline(always)]
+    fn get_write_mask(&self) -> u64 { !0 }
+
+    /// Return the operation to be performed on back faces when both the stencil and depth tests pass
+    #[inline(always)]
+    fn get_back_op(&self) -> StencilOp { self.get_op() }
+
+    /// Return the test to be performed on back faces
+    #[inline(always)]
+    fn get_back_test(&self) -> StencilTest { self.get_test() }
+
+    /// Return the operation to be performed on back faces when the stencil test fails
+    #[inline(always)]
+    fn get_back_fail_op(&self) -> StencilOp { self.get_fail_op() }
+
+    /// Return the operation to be performed on back faces when the stencil test passes but the depth test fails
+    #[inline(always)]
+    fn get_back_depth_fail_op(&self) -> StencilOp { self.get_depth_fail_op() }
 }
 
 impl StencilConfig for () {
@@ -174,11 +242,71 @@ impl StencilConfig for () {
     fn get_test(&self) -> StencilTest { StencilTest::Always }
 }
 
-/// Generic stencil config that just stores the `StencilOp` and `StencilTest` structures.
+/// Generic stencil config that just stores the stencil test, operations, reference value and masks.
+///
+/// The `back_*` fields are used for back-facing triangles. The constructor and `with_ops` set both faces at once,
+/// while `with_back_test` and `with_back_ops` only set back faces.
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct GenericStencilConfig {
+    /// Operation performed when both the stencil and depth tests pass
     pub op: StencilOp,
     pub test: StencilTest,
+    /// Operation performed when the stencil test fails
+    pub fail_op: StencilOp,
+    /// Operation performed when the stencil test passes but the depth test fails
+    pub depth_fail_op: StencilOp,
+    pub back_op: StencilOp,
+    pub back_test: StencilTest,
+    pub back_fail_op: StencilOp,
+    pub back_depth_fail_op: StencilOp,
+    /// Reference value, or `None` to use the stencil value given for each mesh
+    pub reference: Option<u64>,
+    pub read_mask: u64,
+    pub write_mask: u64,
+}
+
+impl GenericStencilConfig {
+    /// Create a new `GenericStencilConfig` with the given test and operation for when both tests pass,
+    /// keeping the stencil value on failure, for both front and back faces.
+    pub fn new(test: StencilTest, op: StencilOp) -> GenericStencilConfig {
+        GenericStencilConfig { op, test, back_op: op, back_test: test, ..GenericStencilConfig::default() }
+    }
+
+    /// Sets the operations performed when the stencil test fails, when the depth test fails and when both pass,
+    /// for both front and back faces, similar to `glStencilOp`.
+    pub fn with_ops(self, fail_op: StencilOp, depth_fail_op: StencilOp, op: StencilOp) -> GenericStencilConfig {
+        GenericStencilConfig {
+            fail_op,
+            depth_fail_op,
+            op,
+            back_fail_op: fail_op,
+            back_depth_fail_op: depth_fail_op,
+            back_op: op,
+            ..self
+        }
+    }
+
+    /// Sets the stencil test of back faces only
+    pub fn with_back_test(self, back_test: StencilTest) -> GenericStencilConfig {
+        GenericStencilConfig { back_test, ..self }
+    }
+
+    /// Sets the operations of back faces only, similar to `glStencilOpSeparate` with `GL_BACK`.
+    pub fn with_back_ops(self, back_fail_op: StencilOp, back_depth_fail_op: StencilOp, back_op: StencilOp) -> GenericStencilConfig {
+        GenericStencilConfig { back_fail_op, back_depth_fail_op, back_op, ..self }
+    }
+
+    pub fn with_reference(self, reference: Option<u64>) -> GenericStencilConfig {
+        GenericStencilConfig { reference, ..self }
+    }
+
+    pub fn with_read_mask(self, read_mask: u64) -> GenericStencilConfig {
+        GenericStencilConfig { read_mask, ..self }
+    }
+
+    pub fn with_write_mask(self, write_mask: u64) -> GenericStencilConfig {
+        GenericStencilConfig { write_mask, ..self }
+    }
 }
 
 impl StencilConfig for GenericStencilConfig {
@@ -187,6 +315,33 @@ impl StencilConfig for GenericStencilConfig {
 
     #[inline(always)]
     fn get_test(&self) -> StencilTest { self.test }
+
+    #[inline(always)]
+    fn get_fail_op(&self) -> StencilOp { self.fail_op }
+
+    #[inline(always)]
+    fn get_depth_fail_op(&self) -> StencilOp { self.depth_fail_op }
+
+    #[inline(always)]
+    fn get_reference(&self) -> Option<u64> { self.reference }
+
+    #[inline(always)]
+    fn get_read_mask(&self) -> u64 { self.read_mask }
+
+    #[inline(always)]
+    fn get_write_mask(&self) -> u64 { self.write_mask }
+
+    #[inline(always)]
+    fn get_back_op(&self) -> StencilOp { self.back_op }
+
+    #[inline(always)]
+    fn get_back_test(&self) -> StencilTest { self.back_test }
+
+    #[inline(always)]
+    fn get_back_fail_op(&self) -> StencilOp { self.back_fail_op }
+
+    #[inline(always)]
+    fn get_back_depth_fail_op(&self) -> StencilOp { self.back_depth_fail_op }
 }
 
 impl Default for GenericStencilConfig {
@@ -194,6 +349,99 @@ impl Default for GenericStencilConfig {
         GenericStencilConfig {
             op: StencilOp::Keep,
             test: StencilTest::Always,
+            fail_op: StencilOp::Keep,
+            depth_fail_op: StencilOp::Keep,
+            back_op: StencilOp::Keep,
+            back_test: StencilTest::Always,
+            back_fail_op: StencilOp::Keep,
+            back_depth_fail_op: StencilOp::Keep,
+            reference: None,
+            read_mask: !0,
+            write_mask: !0,
         }
     }
 }
+
+/// Stencil state of a draw, resolved from a `StencilConfig` for the stencil type of the framebuffer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StencilState<T: Stencil> {
+    pub test: StencilTest,
+    pub fail_op: StencilOp,
+    pub depth_fail_op: StencilOp,
+    pub pass_op: StencilOp,
+    pub reference: T,
+    pub read_mask: T,
+    pub write_mask: T,
+}
+
+impl<T: Stencil> StencilState<T> {
+    /// Resolves the stencil state of front faces from a configuration, using the given stencil value of the mesh
+    /// if the configuration doesn't have its own reference value.
+    pub fn new<S: StencilConfig>(config: &S, mesh_value: T) -> StencilState<T> {
+        StencilState {
+            test: config.get_test(),
+            fail_op: config.get_fail_op(),
+            depth_fail_op: config.get_depth_fail_op(),
+            pass_op: config.get_op(),
+            reference: config.get_reference().map_or(mesh_value, T::from_bits),
+            read_mask: T::from_bits(config.get_read_mask()),
+            write_mask: T::from_bits(config.get_write_mask()),
+        }
+    }
+
+    /// Resolves the stencil state of back faces from a configuration, like `new`
+    pub fn back<S: StencilConfig>(config: &S, mesh_value: T) -> StencilState<T> {
+        StencilState {
+            test: config.get_back_test(),
+            fail_op: config.get_back_fail_op(),
+            depth_fail_op: config.get_back_depth_fail_op(),
+            pass_op: config.get_back_op(),
+            ..StencilState::new(config, mesh_value)
+        }
+    }
+
+    /// Performs the stencil test on a stored value with the given reference value, after applying the read mask to both
+    #[inline]
+    pub fn test(&self, value: T, reference: T) -> bool {
+        self.test.test(value.and(self.read_mask), reference.and(self.read_mask))
+    }
+
+    /// Performs a stencil operation on a stored value with the given reference value,
+    /// only changing the bits in the write mask.
+    #[inline]
+    pub fn op(&self, op: StencilOp, value: T, reference: T) -> T {
+        let new_value = op.op(value, reference);
+
+        new_value.and(self.write_mask).or(value.and(self.write_mask.not()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_stencil_masks() {
+        let config = GenericStencilConfig::new(StencilTest::Equal, StencilOp::Replace)
+            .with_reference(Some(0x1F3))
+            .with_read_mask(0x0F)
+            .with_write_mask(0xF0);
+
+        let state = StencilState::<u8>::new(&config, 7);
+
+        // The reference value is truncated to the stencil type
+        assert_eq!(state.reference, 0xF3);
+
+        // Only the low bits are compared
+        assert!(state.test(0x03, state.reference));
+        assert!(!state.test(0x04, state.reference));
+
+        // Only the high bits are written
+        assert_eq!(state.op(StencilOp::Replace, 0x0A, state.reference), 0xFA);
+        assert_eq!(state.op(StencilOp::Invert, 0x0A, state.reference), 0xFA);
+        assert_eq!(state.op(StencilOp::Zero, 0xAA, state.reference), 0x0A);
+
+        // Without a reference value, the stencil value of the mesh is used
+        assert_eq!(StencilState::<u8>::new(&GenericStencilConfig::default(), 7).reference, 7);
+    }
+}
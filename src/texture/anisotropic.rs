@@ -0,0 +1,114 @@
+//! Anisotropic texture filtering
+
+use nalgebra::Vector2;
+use nalgebra::coordinates::XY;
+
+use num_traits::NumCast;
+
+use ::error::RenderResult;
+
+use ::numeric::FloatScalar;
+use ::interpolate::Interpolate;
+use ::geometry::Dimensions;
+
+/// Footprint of a single pixel in texture-space, approximated by probes along its major axis.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Footprint<N: FloatScalar> {
+    /// Number of probes to take along the major axis
+    pub probes: u32,
+    /// The major axis of the footprint in texture coordinates. Probes are evenly spread along its length.
+    pub major: Vector2<N>,
+    /// Level of detail for each probe, determined by the footprint length covered by each probe.
+    pub lod: N,
+}
+
+impl<N: FloatScalar> Footprint<N> {
+    /// Computes the footprint of a pixel from the derivatives of the texture coordinate
+    /// along the screen-space x and y axes, for a texture of the given dimensions.
+    ///
+    /// The number of probes is limited to `max_anisotropy`, which should be at least one.
+    pub fn new(dimensions: Dimensions, dx: Vector2<N>, dy: Vector2<N>, max_anisotropy: u32) -> Footprint<N> {
+        let (width, height) = (N::from(dimensions.width).unwrap(), N::from(dimensions.height).unwrap());
+
+        let XY { x: dxu, y: dxv } = *dx;
+        let XY { x: dyu, y: dyv } = *dy;
+
+        // Lengths in texel-space
+        let px = (dxu * width).hypot(dxv * height);
+        let py = (dyu * width).hypot(dyv * height);
+
+        let (major, pmax, pmin) = if px > py { (dx, px, py) } else { (dy, py, px) };
+
+        let max_anisotropy = N::from(max_anisotropy.max(1)).unwrap();
+
+        let ratio = if pmax > N::zero() { (pmax / pmin).ceil() } else { N::one() };
+
+        // Also maps NaN and infinite ratios to the maximum
+        let probes = if ratio < max_anisotropy { ratio.max(N::one()) } else { max_anisotropy };
+
+        Footprint {
+            probes: <u32 as NumCast>::from(probes).unwrap(),
+            major,
+            lod: (pmax / probes).log2(),
+        }
+    }
+
+    /// Takes and averages all probes around the given texture coordinate,
+    /// using the given function to sample each one at the footprint level of detail.
+    pub fn sample<C, F>(&self, coord: Vector2<N>, mut probe: F) -> RenderResult<C> where C: Interpolate,
+                                                                                       F: FnMut(Vector2<N>, N) -> RenderResult<C> {
+        let probes = N::from(self.probes).unwrap();
+        let half = N::from(0.5).unwrap();
+
+        let mut color = probe(coord + self.major * (half / probes - half), self.lod)?;
+
+        for i in 1..self.probes {
+            let t = (N::from(i).unwrap() + half) / probes - half;
+
+            let next = probe(coord + self.major * t, self.lod)?;
+
+            // Running average of all probes
+            color = Interpolate::linear_interpolate(N::one() / N::from(i + 1).unwrap(), &color, &next);
+        }
+
+        Ok(color)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use nalgebra::Vector2;
+
+    use ::geometry::Dimensions;
+
+    use super::Footprint;
+
+    #[test]
+    fn test_footprint() {
+        let dimensions = Dimensions::new(16, 16);
+
+        // Isotropic footprint of two texels per pixel
+        let f = Footprint::new(dimensions, Vector2::new(0.125f32, 0.0), Vector2::new(0.0, 0.125), 16);
+
+        assert_eq!(f.probes, 1);
+        assert_eq!(f.lod, 1.0);
+
+        // Eight texels by two texels
+        let f = Footprint::new(dimensions, Vector2::new(0.0f32, 0.125), Vector2::new(0.5, 0.0), 16);
+
+        assert_eq!(f.probes, 4);
+        assert_eq!(f.major, Vector2::new(0.5, 0.0));
+        assert_eq!(f.lod, 1.0);
+
+        // Limited anisotropy blurs along the major axis instead
+        let f = Footprint::new(dimensions, Vector2::new(0.0f32, 0.125), Vector2::new(0.5, 0.0), 2);
+
+        assert_eq!(f.probes, 2);
+        assert_eq!(f.lod, 2.0);
+
+        // Degenerate footprints use the maximum number of probes
+        let f = Footprint::new(dimensions, Vector2::new(0.0f32, 0.0), Vector2::new(0.5, 0.0), 8);
+
+        assert_eq!(f.probes, 8);
+    }
+}
@@ -0,0 +1,136 @@
+//! Layered texture arrays
+
+use nalgebra::{Vector2, Vector3};
+use nalgebra::coordinates::XYZ;
+
+use num_traits::NumCast;
+
+use ::error::{RenderResult, RenderError};
+
+use ::numeric::FloatScalar;
+use ::interpolate::Interpolate;
+use ::pixels::PixelRead;
+use ::geometry::{Dimensions, HasDimensions};
+
+use super::{TextureRead, TextureColor, Filter, Edge};
+
+/// Array of equally sized 2D textures, where each layer is sampled separately.
+///
+/// Unlike a `VolumeTexture`, there is no filtering between layers,
+/// which makes texture arrays useful for things like terrain splatting layers or sprite sheets.
+pub struct TextureArray<T> where T: PixelRead {
+    layers: Vec<T>,
+}
+
+impl<T> HasDimensions for TextureArray<T> where T: PixelRead {
+    /// The dimensions of a single layer
+    #[inline]
+    fn dimensions(&self) -> Dimensions { self.layers[0].dimensions() }
+}
+
+impl<T> TextureArray<T> where T: PixelRead {
+    /// Creates a new texture array from its layers.
+    ///
+    /// Throws `RenderError::InvalidDimensions` if there are no layers, or if the layers are not all the same non-zero size.
+    pub fn new(layers: Vec<T>) -> RenderResult<TextureArray<T>> {
+        match layers.first() {
+            Some(first) if first.dimensions().width > 0 && first.dimensions().height > 0 &&
+                layers.iter().all(|layer| layer.dimensions() == first.dimensions()) => {}
+            _ => throw!(RenderError::InvalidDimensions),
+        }
+
+        Ok(TextureArray { layers })
+    }
+
+    /// Returns the number of layers in the array
+    #[inline]
+    pub fn num_layers(&self) -> usize { self.layers.len() }
+
+    /// Returns a reference to the given layer, if it exists
+    #[inline]
+    pub fn layer(&self, layer: usize) -> Option<&T> { self.layers.get(layer) }
+
+    /// Returns a mutable reference to the given layer, if it exists
+    #[inline]
+    pub fn layer_mut(&mut self, layer: usize) -> Option<&mut T> { self.layers.get_mut(layer) }
+
+    /// Returns the layers as a slice
+    #[inline]
+    pub fn layers(&self) -> &[T] { &self.layers }
+
+    /// Samples the given layer from a floating-point coordinate, applying the selected `Filter` and `Edge` behavior.
+    ///
+    /// Throws `RenderError::InvalidPixelCoordinate` if the layer does not exist.
+    pub fn sample_layer<N: FloatScalar>(&self, coord: Vector2<N>, layer: usize, filter: Filter, edge: Edge<TextureColor<T>>) -> RenderResult<TextureColor<T>>
+        where TextureColor<T>: Interpolate {
+        match self.layers.get(layer) {
+            Some(layer) => layer.sample(coord, filter, edge),
+            None => throw!(RenderError::InvalidPixelCoordinate),
+        }
+    }
+
+    /// Samples the texture array, where the `z` component of the coordinate is the layer.
+    ///
+    /// As with OpenGL, the layer is rounded to the nearest integer and clamped to the available layers.
+    pub fn sample<N: FloatScalar>(&self, coord: Vector3<N>, filter: Filter, edge: Edge<TextureColor<T>>) -> RenderResult<TextureColor<T>>
+        where TextureColor<T>: Interpolate {
+        let XYZ { x, y, z } = *coord;
+
+        let max_layer = N::from(self.layers.len() - 1).unwrap();
+
+        // Also maps NaN to the first layer
+        let layer = <usize as NumCast>::from(z.round().max(N::zero()).min(max_layer)).unwrap();
+
+        self.layers[layer].sample(Vector2::new(x, y), filter, edge)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use nalgebra::{Vector1, Vector2, Vector3};
+
+    use ::geometry::{Dimensions, Coordinate};
+    use ::pixels::PixelWrite;
+    use ::framebuffer::RenderBuffer;
+    use ::attachments::predefined::ColorAttachment;
+    use ::texture::{Filter, Edge};
+
+    use super::*;
+
+    type Layer = RenderBuffer<ColorAttachment<Vector1<f32>>>;
+
+    fn layer(value: f32) -> Layer {
+        let mut layer = Layer::with_dimensions(Dimensions::new(2, 1));
+
+        layer.pixel_mut(Coordinate::new(0, 0)).unwrap().set(Vector1::new(value));
+        layer.pixel_mut(Coordinate::new(1, 0)).unwrap().set(Vector1::new(value + 1.0));
+
+        layer
+    }
+
+    #[test]
+    fn test_texture_array() {
+        let array = TextureArray::new(vec![layer(0.0), layer(10.0), layer(20.0)]).unwrap();
+
+        assert_eq!(array.num_layers(), 3);
+
+        let sample = |x: f32, z: f32| array.sample(Vector3::new(x, 0.5, z), Filter::Bilinear, Edge::Clamp).unwrap().x;
+
+        assert_eq!(sample(0.25, 0.0), 0.0);
+        assert_eq!(sample(0.5, 1.2), 10.5);
+        assert_eq!(sample(0.75, 1.6), 21.0);
+        assert_eq!(sample(0.75, 7.0), 21.0);
+        assert_eq!(sample(0.25, -3.0), 0.0);
+
+        assert_eq!(array.sample_layer(Vector2::new(0.75f32, 0.5), 1, Filter::Nearest, Edge::Clamp).unwrap().x, 11.0);
+        assert!(array.sample_layer(Vector2::new(0.75f32, 0.5), 3, Filter::Nearest, Edge::Clamp).is_err());
+    }
+
+    #[test]
+    fn test_texture_array_dimensions() {
+        assert!(TextureArray::<Layer>::new(Vec::new()).is_err());
+        assert!(TextureArray::new(vec![layer(0.0), Layer::with_dimensions(Dimensions::new(1, 1))]).is_err());
+        assert!(TextureArray::new(vec![Layer::with_dimensions(Dimensions::new(0, 1))]).is_err());
+        assert!(TextureArray::new(vec![Layer::with_dimensions(Dimensions::new(2, 0)), Layer::with_dimensions(Dimensions::new(2, 0))]).is_err());
+    }
+}
@@ -0,0 +1,189 @@
+//! S3TC/RGTC block compression formats, also known as BC1 through BC5
+//!
+//! Texels within a block are in row-major order, with all values stored little-endian.
+
+use nalgebra::{Vector1, Vector2, Vector4};
+
+use ::color::predefined::formats::{RGBAu8Color, RGu8Color, Ru8Color};
+
+use super::BlockFormat;
+
+/// RGB color with optional 1-bit alpha, 8 bytes per block. Also known as DXT1.
+#[derive(Debug, Clone, Copy)]
+pub struct BC1;
+
+/// RGB color with interpolated alpha, 16 bytes per block. Also known as DXT5.
+#[derive(Debug, Clone, Copy)]
+pub struct BC3;
+
+/// Single unsigned channel, 8 bytes per block. Also known as RGTC1 or ATI1.
+#[derive(Debug, Clone, Copy)]
+pub struct BC4;
+
+/// Two unsigned channels, 16 bytes per block. Also known as RGTC2 or ATI2.
+#[derive(Debug, Clone, Copy)]
+pub struct BC5;
+
+/// Expands a 5:6:5 packed color to 8 bits per channel
+#[inline]
+fn rgb565(color: u16) -> [u32; 3] {
+    let (r, g, b) = ((color >> 11) as u32 & 0x1F, (color >> 5) as u32 & 0x3F, color as u32 & 0x1F);
+
+    [(r << 3) | (r >> 2), (g << 2) | (g >> 4), (b << 3) | (b >> 2)]
+}
+
+/// Decodes a single texel from an 8-byte color block.
+///
+/// When `punchthrough` is true and the first endpoint is not greater than the second,
+/// the block uses three colors and transparent black, as in BC1.
+fn decode_color(block: &[u8], i: u32, punchthrough: bool) -> RGBAu8Color {
+    let c0 = block[0] as u16 | (block[1] as u16) << 8;
+    let c1 = block[2] as u16 | (block[3] as u16) << 8;
+
+    let indices = block[4] as u32 | (block[5] as u32) << 8 | (block[6] as u32) << 16 | (block[7] as u32) << 24;
+
+    let (e0, e1) = (rgb565(c0), rgb565(c1));
+
+    let mix = |w0: u32, w1: u32| {
+        let d = w0 + w1;
+
+        Vector4::new(((e0[0] * w0 + e1[0] * w1) / d) as u8,
+                     ((e0[1] * w0 + e1[1] * w1) / d) as u8,
+                     ((e0[2] * w0 + e1[2] * w1) / d) as u8,
+                     255)
+    };
+
+    match (indices >> (2 * i)) & 0x3 {
+        0 => mix(1, 0),
+        1 => mix(0, 1),
+        2 if c0 > c1 || !punchthrough => mix(2, 1),
+        3 if c0 > c1 || !punchthrough => mix(1, 2),
+        2 => mix(1, 1),
+        _ => Vector4::new(0, 0, 0, 0),
+    }
+}
+
+/// Decodes a single texel from an 8-byte single channel block, as used by BC3 alpha, BC4 and BC5.
+fn decode_channel(block: &[u8], i: u32) -> u8 {
+    let (a0, a1) = (block[0] as u32, block[1] as u32);
+
+    let indices = block[2..8].iter().rev().fold(0u64, |bits, &byte| bits << 8 | byte as u64);
+
+    let code = ((indices >> (3 * i)) & 0x7) as u32;
+
+    match code {
+        0 => a0 as u8,
+        1 => a1 as u8,
+        _ if a0 > a1 => (((8 - code) * a0 + (code - 1) * a1 + 3) / 7) as u8,
+        6 => 0,
+        7 => 255,
+        _ => (((6 - code) * a0 + (code - 1) * a1 + 2) / 5) as u8,
+    }
+}
+
+impl BlockFormat for BC1 {
+    type Color = RGBAu8Color;
+
+    const BLOCK_SIZE: usize = 8;
+
+    fn decode_texel(block: &[u8], x: u32, y: u32) -> RGBAu8Color {
+        decode_color(block, y * 4 + x, true)
+    }
+}
+
+impl BlockFormat for BC3 {
+    type Color = RGBAu8Color;
+
+    const BLOCK_SIZE: usize = 16;
+
+    fn decode_texel(block: &[u8], x: u32, y: u32) -> RGBAu8Color {
+        let i = y * 4 + x;
+
+        let mut color = decode_color(&block[8..16], i, false);
+
+        color.w = decode_channel(&block[0..8], i);
+
+        color
+    }
+}
+
+impl BlockFormat for BC4 {
+    type Color = Ru8Color;
+
+    const BLOCK_SIZE: usize = 8;
+
+    fn decode_texel(block: &[u8], x: u32, y: u32) -> Ru8Color {
+        Vector1::new(decode_channel(block, y * 4 + x))
+    }
+}
+
+impl BlockFormat for BC5 {
+    type Color = RGu8Color;
+
+    const BLOCK_SIZE: usize = 16;
+
+    fn decode_texel(block: &[u8], x: u32, y: u32) -> RGu8Color {
+        let i = y * 4 + x;
+
+        Vector2::new(decode_channel(&block[0..8], i), decode_channel(&block[8..16], i))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use nalgebra::{Vector1, Vector2, Vector4};
+
+    use super::*;
+
+    #[test]
+    fn test_bc1() {
+        // Red and blue endpoints, with the first four texels using each of the four codes
+        let block = [0x00, 0xF8, 0x1F, 0x00, 0xE4, 0x00, 0x00, 0x00];
+
+        assert_eq!(BC1::decode_texel(&block, 0, 0), Vector4::new(255, 0, 0, 255));
+        assert_eq!(BC1::decode_texel(&block, 1, 0), Vector4::new(0, 0, 255, 255));
+        assert_eq!(BC1::decode_texel(&block, 2, 0), Vector4::new(170, 0, 85, 255));
+        assert_eq!(BC1::decode_texel(&block, 3, 0), Vector4::new(85, 0, 170, 255));
+        assert_eq!(BC1::decode_texel(&block, 3, 3), Vector4::new(255, 0, 0, 255));
+
+        // Swapped endpoints use three colors and transparent black
+        let block = [0x1F, 0x00, 0x00, 0xF8, 0xE4, 0x00, 0x00, 0x00];
+
+        assert_eq!(BC1::decode_texel(&block, 2, 0), Vector4::new(127, 0, 127, 255));
+        assert_eq!(BC1::decode_texel(&block, 3, 0), Vector4::new(0, 0, 0, 0));
+    }
+
+    #[test]
+    fn test_bc3() {
+        let block = [255, 0, 0x88, 0x00, 0x00, 0x00, 0x00, 0x00,
+                     0x1F, 0x00, 0x00, 0xF8, 0xE4, 0x00, 0x00, 0x00];
+
+        // Four-color mode is always used for BC3 color
+        assert_eq!(BC3::decode_texel(&block, 0, 0), Vector4::new(0, 0, 255, 255));
+        assert_eq!(BC3::decode_texel(&block, 1, 0), Vector4::new(255, 0, 0, 0));
+        assert_eq!(BC3::decode_texel(&block, 2, 0), Vector4::new(85, 0, 170, 219));
+        assert_eq!(BC3::decode_texel(&block, 3, 0), Vector4::new(170, 0, 85, 255));
+    }
+
+    #[test]
+    fn test_bc4_bc5() {
+        // Eight value mode with the first three texels using codes 0, 1 and 2
+        let block = [255, 0, 0x88, 0x00, 0x00, 0x00, 0x00, 0x00];
+
+        assert_eq!(BC4::decode_texel(&block, 0, 0), Vector1::new(255));
+        assert_eq!(BC4::decode_texel(&block, 1, 0), Vector1::new(0));
+        assert_eq!(BC4::decode_texel(&block, 2, 0), Vector1::new(219));
+
+        // Six value mode, with the last texel using the implicit maximum
+        let block = [0, 255, 0x00, 0x00, 0x00, 0x00, 0x00, 0xE0];
+
+        assert_eq!(BC4::decode_texel(&block, 0, 0), Vector1::new(0));
+        assert_eq!(BC4::decode_texel(&block, 3, 3), Vector1::new(255));
+
+        let block = [255, 0, 0x88, 0x00, 0x00, 0x00, 0x00, 0x00,
+                     0, 255, 0x00, 0x00, 0x00, 0x00, 0x00, 0xE0];
+
+        assert_eq!(BC5::decode_texel(&block, 2, 0), Vector2::new(219, 0));
+        assert_eq!(BC5::decode_texel(&block, 3, 3), Vector2::new(255, 255));
+    }
+}
@@ -0,0 +1,245 @@
+//! Ericsson Texture Compression formats, as required by OpenGL ES 3.0
+//!
+//! Texels within a block are in column-major order, with all values stored big-endian.
+
+use nalgebra::Vector4;
+
+use ::color::predefined::formats::RGBAu8Color;
+
+use super::BlockFormat;
+
+/// ETC2 RGB color, 8 bytes per block. Also decodes ETC1 data.
+#[derive(Debug, Clone, Copy)]
+pub struct ETC2RGB;
+
+/// ETC2 RGB color with EAC alpha, 16 bytes per block.
+#[derive(Debug, Clone, Copy)]
+pub struct ETC2RGBA;
+
+/// Intensity modifiers for the individual and differential modes
+const MODIFIERS: [[i32; 2]; 8] = [
+    [2, 8], [5, 17], [9, 29], [13, 42], [18, 60], [24, 80], [33, 106], [47, 183],
+];
+
+/// Paint color distances for the T and H modes
+const DISTANCES: [i32; 8] = [3, 6, 11, 16, 23, 32, 41, 64];
+
+/// Alpha modifiers for EAC
+const ALPHA_MODIFIERS: [[i32; 8]; 16] = [
+    [-3, -6, -9, -15, 2, 5, 8, 14],
+    [-3, -7, -10, -13, 2, 6, 9, 12],
+    [-2, -5, -8, -13, 1, 4, 7, 12],
+    [-2, -4, -6, -13, 1, 3, 5, 12],
+    [-3, -6, -8, -12, 2, 5, 7, 11],
+    [-3, -7, -9, -11, 2, 6, 8, 10],
+    [-4, -7, -8, -11, 3, 6, 7, 10],
+    [-3, -5, -8, -11, 2, 4, 7, 10],
+    [-2, -6, -8, -10, 1, 5, 7, 9],
+    [-2, -5, -8, -10, 1, 4, 7, 9],
+    [-2, -4, -8, -10, 1, 3, 7, 9],
+    [-2, -5, -7, -10, 1, 4, 6, 9],
+    [-3, -4, -7, -10, 2, 3, 6, 9],
+    [-1, -2, -3, -10, 0, 1, 2, 9],
+    [-4, -6, -8, -9, 3, 5, 7, 8],
+    [-3, -5, -7, -9, 2, 4, 6, 8],
+];
+
+/// Expands a value of the given number of bits to 8 bits by replicating the high bits
+#[inline]
+fn extend(value: u8, bits: u32) -> i32 {
+    let value = value as i32 & ((1 << bits) - 1);
+
+    (value << (8 - bits)) | (value >> (2 * bits - 8))
+}
+
+#[inline]
+fn clamp(value: i32) -> u8 {
+    if value < 0 { 0 } else if value > 255 { 255 } else { value as u8 }
+}
+
+#[inline]
+fn rgb(color: [i32; 3], offset: i32) -> RGBAu8Color {
+    Vector4::new(clamp(color[0] + offset), clamp(color[1] + offset), clamp(color[2] + offset), 255)
+}
+
+/// Decodes a single texel from an 8-byte ETC2 color block
+fn decode_color(block: &[u8], x: u32, y: u32) -> RGBAu8Color {
+    let i = x * 4 + y;
+
+    let indices = (block[4] as u32) << 24 | (block[5] as u32) << 16 | (block[6] as u32) << 8 | block[7] as u32;
+
+    // Two bit index, with the most significant bit in the upper half
+    let index = (((indices >> (i + 16)) & 1) << 1 | ((indices >> i) & 1)) as usize;
+
+    let differential = block[3] & 0x2 != 0;
+
+    // Adds a signed three bit delta to a five bit base, which may overflow to select the ETC2 modes
+    let delta = |byte: u8| (byte >> 3) as i32 + (((byte & 0x7) as i32) << 29 >> 29);
+
+    let (dr, dg, db) = (delta(block[0]), delta(block[1]), delta(block[2]));
+
+    if differential && (dr < 0 || dr > 31) {
+        // T mode
+        let c0 = [extend((block[0] & 0x18) >> 1 | (block[0] & 0x3), 4), extend(block[1] >> 4, 4), extend(block[1], 4)];
+        let c1 = [extend(block[2] >> 4, 4), extend(block[2], 4), extend(block[3] >> 4, 4)];
+
+        let d = DISTANCES[((block[3] & 0xC) >> 1 | (block[3] & 0x1)) as usize];
+
+        match index {
+            0 => rgb(c0, 0),
+            1 => rgb(c1, d),
+            2 => rgb(c1, 0),
+            _ => rgb(c1, -d),
+        }
+    } else if differential && (dg < 0 || dg > 31) {
+        // H mode
+        let c0 = [extend((block[0] & 0x78) >> 3, 4),
+                  extend((block[0] & 0x7) << 1 | (block[1] & 0x10) >> 4, 4),
+                  extend((block[1] & 0x8) | (block[1] & 0x3) << 1 | (block[2] & 0x80) >> 7, 4)];
+        let c1 = [extend((block[2] & 0x78) >> 3, 4),
+                  extend((block[2] & 0x7) << 1 | (block[3] & 0x80) >> 7, 4),
+                  extend((block[3] & 0x78) >> 3, 4)];
+
+        let order = |c: [i32; 3]| c[0] << 16 | c[1] << 8 | c[2];
+
+        // The order of the base colors contributes the lowest bit of the distance index
+        let d = DISTANCES[((block[3] & 0x4) | (block[3] & 0x1) << 1) as usize | (order(c0) >= order(c1)) as usize];
+
+        match index {
+            0 => rgb(c0, d),
+            1 => rgb(c0, -d),
+            2 => rgb(c1, d),
+            _ => rgb(c1, -d),
+        }
+    } else if differential && (db < 0 || db > 31) {
+        // Planar mode
+        let o = [extend((block[0] & 0x7E) >> 1, 6),
+                 extend((block[0] & 0x1) << 6 | (block[1] & 0x7E) >> 1, 7),
+                 extend((block[1] & 0x1) << 5 | (block[2] & 0x18) | (block[2] & 0x3) << 1 | (block[3] & 0x80) >> 7, 6)];
+        let h = [extend((block[3] & 0x7C) >> 1 | (block[3] & 0x1), 6),
+                 extend((block[4] & 0xFE) >> 1, 7),
+                 extend((block[4] & 0x1) << 5 | (block[5] & 0xF8) >> 3, 6)];
+        let v = [extend((block[5] & 0x7) << 3 | (block[6] & 0xE0) >> 5, 6),
+                 extend((block[6] & 0x1F) << 2 | (block[7] & 0xC0) >> 6, 7),
+                 extend(block[7] & 0x3F, 6)];
+
+        let (x, y) = (x as i32, y as i32);
+
+        let channel = |c: usize| clamp((x * (h[c] - o[c]) + y * (v[c] - o[c]) + 4 * o[c] + 2) >> 2);
+
+        Vector4::new(channel(0), channel(1), channel(2), 255)
+    } else {
+        // Individual or differential mode, with two sub-blocks split either vertically or horizontally
+        let flipped = block[3] & 0x1 != 0;
+
+        let second = if flipped { y >= 2 } else { x >= 2 };
+
+        let base = match (differential, second) {
+            (true, false) => [extend(block[0] >> 3, 5), extend(block[1] >> 3, 5), extend(block[2] >> 3, 5)],
+            (true, true) => [extend(dr as u8, 5), extend(dg as u8, 5), extend(db as u8, 5)],
+            (false, false) => [extend(block[0] >> 4, 4), extend(block[1] >> 4, 4), extend(block[2] >> 4, 4)],
+            (false, true) => [extend(block[0], 4), extend(block[1], 4), extend(block[2], 4)],
+        };
+
+        let table = if second { (block[3] >> 2) & 0x7 } else { block[3] >> 5 };
+
+        let modifier = MODIFIERS[table as usize][index & 1];
+
+        rgb(base, if index & 2 != 0 { -modifier } else { modifier })
+    }
+}
+
+/// Decodes a single texel from an 8-byte EAC alpha block
+fn decode_alpha(block: &[u8], x: u32, y: u32) -> u8 {
+    let i = x * 4 + y;
+
+    let indices = block[2..8].iter().fold(0u64, |bits, &byte| bits << 8 | byte as u64);
+
+    let index = ((indices >> (45 - 3 * i)) & 0x7) as usize;
+
+    let base = block[0] as i32;
+    let multiplier = (block[1] >> 4) as i32;
+
+    clamp(base + ALPHA_MODIFIERS[(block[1] & 0xF) as usize][index] * multiplier)
+}
+
+impl BlockFormat for ETC2RGB {
+    type Color = RGBAu8Color;
+
+    const BLOCK_SIZE: usize = 8;
+
+    fn decode_texel(block: &[u8], x: u32, y: u32) -> RGBAu8Color {
+        decode_color(block, x, y)
+    }
+}
+
+impl BlockFormat for ETC2RGBA {
+    type Color = RGBAu8Color;
+
+    const BLOCK_SIZE: usize = 16;
+
+    fn decode_texel(block: &[u8], x: u32, y: u32) -> RGBAu8Color {
+        let mut color = decode_color(&block[8..16], x, y);
+
+        color.w = decode_alpha(&block[0..8], x, y);
+
+        color
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use nalgebra::Vector4;
+
+    use super::*;
+
+    #[test]
+    fn test_etc2_individual() {
+        // Both sub-blocks are gray, with texel (1, 0) using the large negative modifier
+        let block = [0x88, 0x88, 0x88, 0x00, 0x00, 0x10, 0x00, 0x10];
+
+        assert_eq!(ETC2RGB::decode_texel(&block, 0, 0), Vector4::new(138, 138, 138, 255));
+        assert_eq!(ETC2RGB::decode_texel(&block, 1, 0), Vector4::new(128, 128, 128, 255));
+    }
+
+    #[test]
+    fn test_etc2_differential() {
+        // Base of 16 with a delta of +3 for the second sub-block, split horizontally
+        let block = [0x83, 0x83, 0x83, 0x03, 0x00, 0x00, 0x00, 0x00];
+
+        assert_eq!(ETC2RGB::decode_texel(&block, 3, 1), Vector4::new(134, 134, 134, 255));
+        assert_eq!(ETC2RGB::decode_texel(&block, 0, 2), Vector4::new(158, 158, 158, 255));
+    }
+
+    #[test]
+    fn test_etc2_t_mode() {
+        // Black and gray base colors, with texels using each of the four paint colors down the first column
+        let block = [0x04, 0x00, 0x88, 0x82, 0x00, 0x0C, 0x00, 0x0A];
+
+        assert_eq!(ETC2RGB::decode_texel(&block, 0, 0), Vector4::new(0, 0, 0, 255));
+        assert_eq!(ETC2RGB::decode_texel(&block, 0, 1), Vector4::new(139, 139, 139, 255));
+        assert_eq!(ETC2RGB::decode_texel(&block, 0, 2), Vector4::new(136, 136, 136, 255));
+        assert_eq!(ETC2RGB::decode_texel(&block, 0, 3), Vector4::new(133, 133, 133, 255));
+    }
+
+    #[test]
+    fn test_etc2_planar() {
+        // Red increases along the x axis
+        let block = [0x00, 0x00, 0x04, 0x7F, 0x00, 0x00, 0x00, 0x00];
+
+        for y in 0..4 {
+            assert_eq!(ETC2RGB::decode_texel(&block, 0, y), Vector4::new(0, 0, 0, 255));
+            assert_eq!(ETC2RGB::decode_texel(&block, 1, y), Vector4::new(64, 0, 0, 255));
+            assert_eq!(ETC2RGB::decode_texel(&block, 3, y), Vector4::new(191, 0, 0, 255));
+        }
+    }
+
+    #[test]
+    fn test_etc2_alpha() {
+        let block = [128, 0x10, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00,
+                     0x88, 0x88, 0x88, 0x00, 0x00, 0x00, 0x00, 0x00];
+
+        assert_eq!(ETC2RGBA::decode_texel(&block, 0, 0), Vector4::new(138, 138, 138, 130));
+        assert_eq!(ETC2RGBA::decode_texel(&block, 0, 1), Vector4::new(138, 138, 138, 125));
+    }
+}
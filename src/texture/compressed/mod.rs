@@ -0,0 +1,135 @@
+//! Block-compressed textures, decoded on demand
+//!
+//! Compressed textures keep their block data in memory exactly as it would be uploaded to a GPU,
+//! and decode individual texels only when they are read.
+
+use std::marker::PhantomData;
+
+use ::error::{RenderResult, RenderError};
+
+use ::color::Color;
+use ::pixels::{PixelBuffer, PixelRead};
+use ::geometry::{Dimensions, HasDimensions, Coordinate};
+
+pub mod bc;
+pub mod etc;
+
+pub use self::bc::{BC1, BC3, BC4, BC5};
+pub use self::etc::{ETC2RGB, ETC2RGBA};
+
+/// Compression format made of 4x4 texel blocks with a fixed number of bytes per block.
+pub trait BlockFormat {
+    /// Color type of decoded texels
+    type Color: Color;
+
+    /// Number of bytes in a single compressed block
+    const BLOCK_SIZE: usize;
+
+    /// Decodes the texel at `x`, `y` within the given block, where both are less than four.
+    fn decode_texel(block: &[u8], x: u32, y: u32) -> Self::Color;
+}
+
+/// Texture of block-compressed data, where texels are decoded on demand when read.
+///
+/// Blocks are stored in row-major order, and partial blocks are used for dimensions that are not a multiple of four.
+///
+/// The data can be any byte container, so memory-mapped or otherwise borrowed data can be used without copying.
+pub struct CompressedTexture<F, D = Vec<u8>> where F: BlockFormat, D: AsRef<[u8]> {
+    dimensions: Dimensions,
+    data: D,
+    format: PhantomData<F>,
+}
+
+impl<F, D> CompressedTexture<F, D> where F: BlockFormat, D: AsRef<[u8]> {
+    /// Creates a new compressed texture from block data.
+    ///
+    /// Throws `RenderError::InvalidDimensions` if the length of the data does not match the given dimensions.
+    pub fn new(dimensions: Dimensions, data: D) -> RenderResult<CompressedTexture<F, D>> {
+        if data.as_ref().len() != CompressedTexture::<F, D>::data_len(dimensions) {
+            throw!(RenderError::InvalidDimensions);
+        }
+
+        Ok(CompressedTexture { dimensions, data, format: PhantomData })
+    }
+
+    /// Returns the number of bytes required to store a texture of the given dimensions
+    pub fn data_len(dimensions: Dimensions) -> usize {
+        let blocks = CompressedTexture::<F, D>::blocks(dimensions);
+
+        blocks.area() * F::BLOCK_SIZE
+    }
+
+    /// Returns the number of blocks along each axis required to store a texture of the given dimensions
+    #[inline]
+    pub fn blocks(dimensions: Dimensions) -> Dimensions {
+        Dimensions::new((dimensions.width + 3) / 4, (dimensions.height + 3) / 4)
+    }
+
+    /// Returns the compressed block data
+    #[inline]
+    pub fn data(&self) -> &[u8] { self.data.as_ref() }
+
+    /// Consumes the texture, returning the compressed block data
+    #[inline]
+    pub fn into_data(self) -> D { self.data }
+}
+
+impl<F, D> HasDimensions for CompressedTexture<F, D> where F: BlockFormat, D: AsRef<[u8]> {
+    #[inline]
+    fn dimensions(&self) -> Dimensions { self.dimensions }
+}
+
+impl<F, D> PixelBuffer for CompressedTexture<F, D> where F: BlockFormat, D: AsRef<[u8]> {
+    type Color = <F as BlockFormat>::Color;
+}
+
+impl<F, D> PixelRead for CompressedTexture<F, D> where F: BlockFormat, D: AsRef<[u8]> {
+    unsafe fn get_pixel_unchecked(&self, index: usize) -> Self::Color {
+        let Coordinate { x, y } = Coordinate::from_index(index, self.dimensions);
+
+        let blocks = CompressedTexture::<F, D>::blocks(self.dimensions);
+
+        let block = (x / 4) as usize + (y / 4) as usize * blocks.width as usize;
+
+        let start = block * F::BLOCK_SIZE;
+
+        F::decode_texel(self.data.as_ref().get_unchecked(start..(start + F::BLOCK_SIZE)), x % 4, y % 4)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use nalgebra::{Vector1, Vector2};
+
+    use ::geometry::{Dimensions, Coordinate};
+    use ::pixels::PixelRead;
+    use ::texture::{TextureRead, Filter, Edge};
+
+    use super::*;
+
+    #[test]
+    fn test_compressed_texture() {
+        // Two BC4 blocks side by side, the first all 255 and the second all 0
+        let data = [255, 255, 0, 0, 0, 0, 0, 0,
+                    0, 0, 0, 0, 0, 0, 0, 0];
+
+        let texture = CompressedTexture::<BC4, _>::new(Dimensions::new(6, 3), &data[..]).unwrap();
+
+        assert_eq!(texture.pixel_ref(Coordinate::new(3, 2)).unwrap().get(), Vector1::new(255));
+        assert_eq!(texture.pixel_ref(Coordinate::new(4, 2)).unwrap().get(), Vector1::new(0));
+
+        // Halfway between the two texels on either side of the block boundary
+        let value = texture.sample(Vector2::new(4.0f32 / 6.0, 0.5), Filter::Bilinear, Edge::Clamp).unwrap();
+
+        assert_eq!(value, Vector1::new(127));
+    }
+
+    #[test]
+    fn test_compressed_texture_dimensions() {
+        assert_eq!(CompressedTexture::<BC1>::data_len(Dimensions::new(5, 4)), 16);
+        assert_eq!(CompressedTexture::<BC3>::data_len(Dimensions::new(8, 8)), 64);
+
+        assert!(CompressedTexture::<BC1>::new(Dimensions::new(5, 4), vec![0; 8]).is_err());
+        assert!(CompressedTexture::<BC1>::new(Dimensions::new(5, 4), vec![0; 16]).is_ok());
+    }
+}
@@ -0,0 +1,261 @@
+//! Cube map textures
+
+use nalgebra::{Vector2, Vector3};
+use nalgebra::coordinates::XYZ;
+
+use ::error::{RenderResult, RenderError};
+
+use ::numeric::FloatScalar;
+use ::interpolate::Interpolate;
+use ::pixels::PixelRead;
+use ::geometry::{Dimensions, HasDimensions, Coordinate};
+
+use super::{TextureColor, Filter, texel_floor};
+
+/// Faces of a cube map, in the same order as OpenGL's `GL_TEXTURE_CUBE_MAP_*` targets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CubeFace {
+    PositiveX,
+    NegativeX,
+    PositiveY,
+    NegativeY,
+    PositiveZ,
+    NegativeZ,
+}
+
+/// All cube map faces in a constant array. Useful for iterating over all of them.
+pub const ALL_CUBE_FACES: [CubeFace; 6] = [
+    CubeFace::PositiveX,
+    CubeFace::NegativeX,
+    CubeFace::PositiveY,
+    CubeFace::NegativeY,
+    CubeFace::PositiveZ,
+    CubeFace::NegativeZ,
+];
+
+impl CubeFace {
+    /// Selects the face a direction points towards, and the normalized texture coordinate on that face.
+    ///
+    /// The direction does not need to be normalized, but must not be zero.
+    pub fn from_direction<N: FloatScalar>(direction: Vector3<N>) -> (CubeFace, Vector2<N>) {
+        let XYZ { x, y, z } = *direction;
+
+        let (ax, ay, az) = (x.abs(), y.abs(), z.abs());
+
+        // Select the major axis, then the face coordinates as defined by OpenGL
+        let (face, sc, tc, ma) = if ax >= ay && ax >= az {
+            if x.is_sign_positive() { (CubeFace::PositiveX, -z, -y, ax) } else { (CubeFace::NegativeX, z, -y, ax) }
+        } else if ay >= az {
+            if y.is_sign_positive() { (CubeFace::PositiveY, x, z, ay) } else { (CubeFace::NegativeY, x, -z, ay) }
+        } else {
+            if z.is_sign_positive() { (CubeFace::PositiveZ, x, -y, az) } else { (CubeFace::NegativeZ, -x, -y, az) }
+        };
+
+        let half = N::from(0.5).unwrap();
+
+        (face, Vector2::new((sc / ma + N::one()) * half, (tc / ma + N::one()) * half))
+    }
+
+    /// Returns the direction pointing towards the given normalized texture coordinate on this face.
+    ///
+    /// Coordinates outside of `[0, 1]` extend past the edges of the face, into the neighboring faces.
+    pub fn direction<N: FloatScalar>(self, coord: Vector2<N>) -> Vector3<N> {
+        let two = N::from(2.0).unwrap();
+
+        let (sc, tc) = (coord.x * two - N::one(), coord.y * two - N::one());
+
+        let one = N::one();
+
+        match self {
+            CubeFace::PositiveX => Vector3::new(one, -tc, -sc),
+            CubeFace::NegativeX => Vector3::new(-one, -tc, sc),
+            CubeFace::PositiveY => Vector3::new(sc, one, tc),
+            CubeFace::NegativeY => Vector3::new(sc, -one, -tc),
+            CubeFace::PositiveZ => Vector3::new(sc, -tc, one),
+            CubeFace::NegativeZ => Vector3::new(-sc, -tc, -one),
+        }
+    }
+}
+
+/// Cube map texture composed of six square faces, sampled by direction vector.
+///
+/// Useful for skyboxes, environment reflections and anything else that maps directions to colors.
+pub struct CubeMap<T> where T: PixelRead {
+    faces: [T; 6],
+}
+
+impl<T> HasDimensions for CubeMap<T> where T: PixelRead {
+    /// The dimensions of a single face
+    #[inline]
+    fn dimensions(&self) -> Dimensions { self.faces[0].dimensions() }
+}
+
+impl<T> CubeMap<T> where T: PixelRead {
+    /// Creates a new cube map from its six faces.
+    ///
+    /// Throws `RenderError::InvalidDimensions` if the faces are not all the same square, non-zero size.
+    pub fn new(positive_x: T, negative_x: T,
+               positive_y: T, negative_y: T,
+               positive_z: T, negative_z: T) -> RenderResult<CubeMap<T>> {
+        let faces = [positive_x, negative_x, positive_y, negative_y, positive_z, negative_z];
+
+        let dimensions = faces[0].dimensions();
+
+        if dimensions.width == 0 || dimensions.width != dimensions.height ||
+            faces.iter().any(|face| face.dimensions() != dimensions) {
+            throw!(RenderError::InvalidDimensions);
+        }
+
+        Ok(CubeMap { faces })
+    }
+
+    /// Returns a reference to the given face
+    #[inline]
+    pub fn face(&self, face: CubeFace) -> &T { &self.faces[face as usize] }
+
+    /// Returns a mutable reference to the given face
+    #[inline]
+    pub fn face_mut(&mut self, face: CubeFace) -> &mut T { &mut self.faces[face as usize] }
+
+    /// Returns the texel nearest to the given normalized coordinate on a face
+    fn fetch<N: FloatScalar>(&self, face: CubeFace, coord: Vector2<N>) -> RenderResult<TextureColor<T>> {
+        let size = self.dimensions().width;
+
+        let n = N::from(size).unwrap();
+
+        let clamp = |i: i64| if i < 0 { 0 } else if i >= size as i64 { size - 1 } else { i as u32 };
+
+        let coord = Coordinate::new(clamp(texel_floor(coord.x * n, size)), clamp(texel_floor(coord.y * n, size)));
+
+        Ok(self.face(face).pixel_ref(coord)?.get())
+    }
+
+    /// Samples the cube map in the given direction, applying the selected `Filter`.
+    ///
+    /// Bilinear filtering near the edges of a face blends in texels from the neighboring faces,
+    /// so there are no visible seams between faces.
+    ///
+    /// Cube maps do not have mipmap levels, so only the `Filter::level_filter` of mipmapped filters is used.
+    ///
+    /// Throws `RenderError::InvalidPixelCoordinate` if the direction is zero or not finite.
+    pub fn sample_dir<N: FloatScalar>(&self, direction: Vector3<N>, filter: Filter) -> RenderResult<TextureColor<T>>
+        where TextureColor<T>: Interpolate {
+        let XYZ { x, y, z } = *direction;
+
+        if !(x.is_finite() && y.is_finite() && z.is_finite()) || (x == N::zero() && y == N::zero() && z == N::zero()) {
+            throw!(RenderError::InvalidPixelCoordinate);
+        }
+
+        let (face, coord) = CubeFace::from_direction(direction);
+
+        match filter.level_filter() {
+            Filter::Nearest => self.fetch(face, coord),
+            Filter::Bilinear => {
+                let size = self.dimensions().width;
+
+                let n = N::from(size).unwrap();
+                let half = N::from(0.5).unwrap();
+
+                // Texel centers are offset by half a texel
+                let (tx, ty) = (coord.x * n - half, coord.y * n - half);
+
+                let (x0, y0) = (texel_floor(tx, size), texel_floor(ty, size));
+
+                let (fx, fy) = (tx - tx.floor(), ty - ty.floor());
+
+                let texel = |x: i64, y: i64| -> RenderResult<TextureColor<T>> {
+                    let center = Vector2::new((N::from(x).unwrap() + half) / n,
+                                              (N::from(y).unwrap() + half) / n);
+
+                    if 0 <= x && x < size as i64 && 0 <= y && y < size as i64 {
+                        self.fetch(face, center)
+                    } else {
+                        // Follow the direction of the texel center past the edge and onto the neighboring face
+                        let (face, coord) = CubeFace::from_direction(face.direction(center));
+
+                        self.fetch(face, coord)
+                    }
+                };
+
+                let top = Interpolate::linear_interpolate(fx, &texel(x0, y0)?, &texel(x0 + 1, y0)?);
+                let bottom = Interpolate::linear_interpolate(fx, &texel(x0, y0 + 1)?, &texel(x0 + 1, y0 + 1)?);
+
+                Ok(Interpolate::linear_interpolate(fy, &top, &bottom))
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use nalgebra::{Vector1, Vector2, Vector3};
+
+    use ::geometry::{Dimensions, Coordinate};
+    use ::pixels::PixelWrite;
+    use ::framebuffer::RenderBuffer;
+    use ::attachments::predefined::ColorAttachment;
+    use ::texture::Filter;
+
+    use super::*;
+
+    type Face = RenderBuffer<ColorAttachment<Vector1<f32>>>;
+
+    /// Creates a cube map where every texel of each face is the index of the face
+    fn cube() -> CubeMap<Face> {
+        let face = |value: f32| {
+            let mut face = Face::with_dimensions(Dimensions::new(2, 2));
+
+            for i in 0..4 {
+                face.pixel_mut(Coordinate::new(i % 2, i / 2)).unwrap().set(Vector1::new(value));
+            }
+
+            face
+        };
+
+        CubeMap::new(face(0.0), face(1.0), face(2.0), face(3.0), face(4.0), face(5.0)).unwrap()
+    }
+
+    #[test]
+    fn test_cube_face_direction() {
+        for face in &ALL_CUBE_FACES {
+            for &(u, v) in &[(0.25f32, 0.25), (0.75, 0.5), (0.1, 0.9)] {
+                let (f, coord) = CubeFace::from_direction(face.direction(Vector2::new(u, v)));
+
+                assert_eq!(f, *face);
+                assert!((coord.x - u).abs() < 1e-6 && (coord.y - v).abs() < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn test_cube_sample_dir() {
+        let cube = cube();
+
+        let directions = [
+            Vector3::new(1.0f32, 0.1, 0.2),
+            Vector3::new(-1.0, 0.1, 0.2),
+            Vector3::new(0.1, 1.0, 0.2),
+            Vector3::new(0.1, -1.0, 0.2),
+            Vector3::new(0.1, 0.2, 1.0),
+            Vector3::new(0.1, 0.2, -1.0),
+        ];
+
+        for (i, direction) in directions.iter().enumerate() {
+            assert_eq!(cube.sample_dir(*direction, Filter::Nearest).unwrap().x, i as f32);
+            assert_eq!(cube.sample_dir(*direction, Filter::Bilinear).unwrap().x, i as f32);
+        }
+
+        assert!(cube.sample_dir(Vector3::new(0.0f32, 0.0, 0.0), Filter::Nearest).is_err());
+    }
+
+    #[test]
+    fn test_cube_seams() {
+        let cube = cube();
+
+        // Right at the edge between the +X and +Z faces, which should be an even blend of both
+        let value = cube.sample_dir(Vector3::new(1.0f32, 0.0, 0.999), Filter::Bilinear).unwrap().x;
+
+        assert!((value - 2.0).abs() < 0.01);
+    }
+}
@@ -0,0 +1,449 @@
+//! Mipmapped textures
+
+use std::f64::consts::PI;
+
+use num_traits::NumCast;
+
+use nalgebra::Vector2;
+use nalgebra::coordinates::XY;
+
+use ::error::RenderResult;
+
+use ::numeric::FloatScalar;
+use ::interpolate::Interpolate;
+use ::pixels::{PixelRead, PixelWrite};
+use ::geometry::{Dimensions, HasDimensions, Coordinate};
+use ::framebuffer::RenderBuffer;
+use ::attachments::predefined::ColorAttachment;
+
+use super::{TextureRead, TextureColor, Filter, Edge, Footprint};
+
+/// Owned buffer used to store generated mipmap levels
+pub type MipmapLevel<C> = RenderBuffer<ColorAttachment<C>>;
+
+/// Defines the filter used to downsample each mipmap level from the previous level.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MipmapFilter {
+    /// Averages each 2x2 block of texels. Fast, but slightly blurry and prone to aliasing.
+    Box,
+    /// Weights texels around each 2x2 block with a Kaiser-windowed sinc, which preserves more detail than a box filter.
+    ///
+    /// The negative lobes of the sinc may ring slightly around sharp edges, and saturate for integer colors.
+    Kaiser {
+        /// Radius of the window in texels of the previous level. `3.0` is a good default.
+        width: f64,
+        /// Shape parameter of the window, where larger values give a narrower window. `4.0` is a good default.
+        alpha: f64,
+    },
+}
+
+impl Default for MipmapFilter {
+    fn default() -> MipmapFilter {
+        MipmapFilter::Box
+    }
+}
+
+/// Zeroth-order modified Bessel function of the first kind, used for the Kaiser window.
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let mut k = 1.0;
+
+    while term > sum * 1e-12 {
+        term *= (x / (2.0 * k)) * (x / (2.0 * k));
+        sum += term;
+        k += 1.0;
+    }
+
+    sum
+}
+
+/// Kaiser window for `t` in `[-1, 1]`, and zero outside of that.
+fn kaiser(t: f64, alpha: f64) -> f64 {
+    if t.abs() > 1.0 { 0.0 } else {
+        bessel_i0(PI * alpha * (1.0 - t * t).sqrt()) / bessel_i0(PI * alpha)
+    }
+}
+
+/// Kaiser-windowed sinc at `distance` texels of the previous level, with the cutoff at half its sample rate.
+fn kaiser_sinc(distance: f64, radius: f64, alpha: f64) -> f64 {
+    let x = distance * 0.5;
+
+    let sinc = if x == 0.0 { 1.0 } else { (PI * x).sin() / (PI * x) };
+
+    sinc * kaiser(distance / radius, alpha)
+}
+
+/// Texture with a full chain of downsampled mipmap levels, built on top of any readable pixelbuffer.
+///
+/// Level zero is the original pixelbuffer, and every following level is half the size of the previous,
+/// down to a single texel.
+pub struct Mipmap<T> where T: PixelRead {
+    base: T,
+    levels: Vec<MipmapLevel<TextureColor<T>>>,
+}
+
+impl<T> Mipmap<T> where T: PixelRead, TextureColor<T>: Interpolate {
+    /// Generates all mipmap levels for the given pixelbuffer using the given filter.
+    pub fn new(base: T, filter: MipmapFilter) -> RenderResult<Mipmap<T>> {
+        let mut mipmap = Mipmap { base, levels: Vec::new() };
+
+        let mut dimensions = mipmap.base.dimensions();
+
+        while dimensions.width > 1 || dimensions.height > 1 {
+            let next = Dimensions::new((dimensions.width / 2).max(1),
+                                       (dimensions.height / 2).max(1));
+
+            let level = mipmap.downsample(next, filter)?;
+
+            mipmap.levels.push(level);
+
+            dimensions = next;
+        }
+
+        Ok(mipmap)
+    }
+
+    /// Creates the next level from the current last level
+    fn downsample(&self, dimensions: Dimensions, filter: MipmapFilter) -> RenderResult<MipmapLevel<TextureColor<T>>> {
+        let source = self.levels.len();
+        let Dimensions { width, height } = self.level_dimensions(source);
+
+        let mut level = MipmapLevel::with_dimensions(dimensions);
+
+        let mut weighted = Vec::new();
+
+        let clamp = |i: i64, size: u32| -> u32 {
+            if i < 0 { 0 } else if i >= size as i64 { size - 1 } else { i as u32 }
+        };
+
+        for y in 0..dimensions.height {
+            for x in 0..dimensions.width {
+                weighted.clear();
+
+                {
+                    let mut accumulate = |c: TextureColor<T>, weight: f64| {
+                        if weight != 0.0 {
+                            weighted.push((weight, c));
+                        }
+                    };
+
+                    match filter {
+                        MipmapFilter::Box => {
+                            for &(dx, dy) in &[(0, 0), (1, 0), (0, 1), (1, 1)] {
+                                let coord = Coordinate::new(clamp(x as i64 * 2 + dx, width),
+                                                            clamp(y as i64 * 2 + dy, height));
+
+                                accumulate(self.fetch(source, coord)?, 1.0);
+                            }
+                        }
+                        MipmapFilter::Kaiser { width: radius, alpha } => {
+                            let radius = radius.max(1.0);
+
+                            // Center of the output texel in the source level
+                            let (cx, cy) = (x as f64 * 2.0 + 1.0, y as f64 * 2.0 + 1.0);
+
+                            let (x0, x1) = ((cx - radius).floor() as i64, (cx + radius).ceil() as i64);
+                            let (y0, y1) = ((cy - radius).floor() as i64, (cy + radius).ceil() as i64);
+
+                            for sy in y0..y1 {
+                                let wy = kaiser_sinc(sy as f64 + 0.5 - cy, radius, alpha);
+
+                                for sx in x0..x1 {
+                                    let wx = kaiser_sinc(sx as f64 + 0.5 - cx, radius, alpha);
+
+                                    let coord = Coordinate::new(clamp(sx, width), clamp(sy, height));
+
+                                    accumulate(self.fetch(source, coord)?, wx * wy);
+                                }
+                            }
+                        }
+                    }
+                }
+
+                let total: f64 = weighted.iter().map(|&(weight, _)| weight).sum();
+
+                // Normalize once and round once, so integer colors don't lose precision with every texel or level
+                if total > 0.0 {
+                    let norm = 1.0 / total;
+
+                    let color = Interpolate::weighted_sum(weighted.iter().map(|&(weight, ref c)| (weight * norm, c)));
+
+                    level.pixel_mut(Coordinate::new(x, y))?.set(color);
+                }
+            }
+        }
+
+        Ok(level)
+    }
+}
+
+impl<T> Mipmap<T> where T: PixelRead {
+    /// Returns a reference to the original pixelbuffer, which is also mipmap level zero.
+    #[inline]
+    pub fn base(&self) -> &T { &self.base }
+
+    /// Returns the original pixelbuffer, discarding any generated levels.
+    #[inline]
+    pub fn into_base(self) -> T { self.base }
+
+    /// Returns the number of mipmap levels, including the base level.
+    #[inline]
+    pub fn num_levels(&self) -> usize { self.levels.len() + 1 }
+
+    /// Returns the dimensions of the given mipmap level.
+    ///
+    /// Panics if the level does not exist.
+    pub fn level_dimensions(&self, level: usize) -> Dimensions {
+        if level == 0 { self.base.dimensions() } else { self.levels[level - 1].dimensions() }
+    }
+
+    /// Returns a reference to a generated mipmap level, or `None` if the level is the base level or does not exist.
+    pub fn level(&self, level: usize) -> Option<&MipmapLevel<TextureColor<T>>> {
+        if level == 0 { None } else { self.levels.get(level - 1) }
+    }
+
+    #[inline]
+    fn fetch(&self, level: usize, coord: Coordinate) -> RenderResult<TextureColor<T>> {
+        Ok(if level == 0 {
+            self.base.pixel_ref(coord)?.get()
+        } else {
+            self.levels[level - 1].pixel_ref(coord)?.get()
+        })
+    }
+
+    /// Computes the level of detail from the derivatives of the texture coordinate
+    /// along the screen-space x and y axes, where `0` is the base level.
+    pub fn lod<N: FloatScalar>(&self, dx: Vector2<N>, dy: Vector2<N>) -> N {
+        let Dimensions { width, height } = self.base.dimensions();
+
+        let (width, height) = (N::from(width).unwrap(), N::from(height).unwrap());
+
+        let XY { x: dxu, y: dxv } = *dx;
+        let XY { x: dyu, y: dyv } = *dy;
+
+        let rho = (dxu * width).hypot(dxv * height).max((dyu * width).hypot(dyv * height));
+
+        rho.log2()
+    }
+}
+
+impl<T> Mipmap<T> where T: PixelRead, TextureColor<T>: Interpolate {
+    fn sample_level<N: FloatScalar>(&self, level: usize, coord: Vector2<N>, filter: Filter, edge: Edge<TextureColor<T>>) -> RenderResult<TextureColor<T>> {
+        if level == 0 {
+            self.base.sample(coord, filter, edge)
+        } else {
+            self.levels[level - 1].sample(coord, filter, edge)
+        }
+    }
+
+    /// Samples the texture at an explicit level of detail, where `0` is the base level.
+    ///
+    /// The level of detail is clamped to the available levels. `Filter::Nearest` and `Filter::Bilinear`
+    /// ignore the level of detail and only sample the base level, as with OpenGL.
+    pub fn sample_lod<N: FloatScalar>(&self, coord: Vector2<N>, lod: N, filter: Filter, edge: Edge<TextureColor<T>>) -> RenderResult<TextureColor<T>> {
+        let level_filter = filter.level_filter();
+
+        if !filter.is_mipmapped() {
+            return self.sample_level(0, coord, level_filter, edge);
+        }
+
+        let max_level = N::from(self.num_levels() - 1).unwrap();
+
+        // Also maps NaN to zero
+        let lod = lod.max(N::zero()).min(max_level);
+
+        if filter.interpolates_levels() {
+            let level = <usize as NumCast>::from(lod.floor()).unwrap();
+
+            let t = lod - lod.floor();
+
+            let a = self.sample_level(level, coord, level_filter, edge)?;
+
+            if t > N::zero() {
+                let b = self.sample_level(level + 1, coord, level_filter, edge)?;
+
+                Ok(Interpolate::linear_interpolate(t, &a, &b))
+            } else {
+                Ok(a)
+            }
+        } else {
+            let level = <usize as NumCast>::from(lod.round()).unwrap();
+
+            self.sample_level(level, coord, level_filter, edge)
+        }
+    }
+
+    /// Samples the texture with the level of detail determined by the derivatives of the texture coordinate
+    /// along the screen-space x and y axes, such as those given by `dFdx` and `dFdy` in GLSL.
+    pub fn sample_grad<N: FloatScalar>(&self, coord: Vector2<N>, dx: Vector2<N>, dy: Vector2<N>,
+                                       filter: Filter, edge: Edge<TextureColor<T>>) -> RenderResult<TextureColor<T>> {
+        let lod = self.lod(dx, dy);
+
+        self.sample_lod(coord, lod, filter, edge)
+    }
+
+    /// Samples the texture with anisotropic filtering, taking up to `max_anisotropy` probes
+    /// along the major axis of the pixel footprint given by the derivatives of the texture coordinate
+    /// along the screen-space x and y axes.
+    ///
+    /// Each probe samples at the level of detail of the footprint length it covers, so surfaces viewed
+    /// at grazing angles remain sharp along their minor axis. Use a mipmapped filter such as `Filter::Trilinear`.
+    pub fn sample_anisotropic<N: FloatScalar>(&self, coord: Vector2<N>, dx: Vector2<N>, dy: Vector2<N>, max_anisotropy: u32,
+                                              filter: Filter, edge: Edge<TextureColor<T>>) -> RenderResult<TextureColor<T>> {
+        let footprint = Footprint::new(self.base.dimensions(), dx, dy, max_anisotropy);
+
+        footprint.sample(coord, |coord, lod| self.sample_lod(coord, lod, filter, edge))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use nalgebra::{Vector1, Vector2};
+
+    use ::geometry::{Dimensions, Coordinate};
+    use ::pixels::PixelWrite;
+    use ::framebuffer::RenderBuffer;
+    use ::attachments::predefined::ColorAttachment;
+
+    use super::*;
+
+    type Grid = RenderBuffer<ColorAttachment<Vector1<f32>>>;
+
+    /// 4x2 grid of texels with the values:
+    ///
+    /// ```text
+    /// 0 2 4 6
+    /// 2 4 6 8
+    /// ```
+    fn grid() -> Grid {
+        let mut grid = Grid::with_dimensions(Dimensions::new(4, 2));
+
+        for y in 0..2 {
+            for x in 0..4 {
+                grid.pixel_mut(Coordinate::new(x, y)).unwrap().set(Vector1::new((x + y) as f32 * 2.0));
+            }
+        }
+
+        grid
+    }
+
+    #[test]
+    fn test_mipmap_levels() {
+        let mipmap = Mipmap::new(grid(), MipmapFilter::Box).unwrap();
+
+        assert_eq!(mipmap.num_levels(), 3);
+        assert_eq!(mipmap.level_dimensions(1), Dimensions::new(2, 1));
+        assert_eq!(mipmap.level_dimensions(2), Dimensions::new(1, 1));
+
+        let level = mipmap.level(1).unwrap();
+
+        assert_eq!(level.pixel_ref(Coordinate::new(0, 0)).unwrap().get().x, 2.0);
+        assert_eq!(level.pixel_ref(Coordinate::new(1, 0)).unwrap().get().x, 6.0);
+
+        assert_eq!(mipmap.level(2).unwrap().pixel_ref(Coordinate::new(0, 0)).unwrap().get().x, 4.0);
+    }
+
+    #[test]
+    fn test_mipmap_kaiser() {
+        let mut flat = Grid::with_dimensions(Dimensions::new(8, 8));
+
+        for y in 0..8 {
+            for x in 0..8 {
+                flat.pixel_mut(Coordinate::new(x, y)).unwrap().set(Vector1::new(3.0));
+            }
+        }
+
+        let mipmap = Mipmap::new(flat, MipmapFilter::Kaiser { width: 3.0, alpha: 4.0 }).unwrap();
+
+        assert_eq!(mipmap.num_levels(), 4);
+
+        for level in 1..4 {
+            let value = mipmap.level(level).unwrap().pixel_ref(Coordinate::new(0, 0)).unwrap().get().x;
+
+            assert!((value - 3.0).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_mipmap_integer() {
+        let mut grid = RenderBuffer::<ColorAttachment<Vector1<u8>>>::with_dimensions(Dimensions::new(4, 4));
+
+        // One texel of every 2x2 block is darker, so each block averages to 1.75
+        for y in 0..4 {
+            for x in 0..4 {
+                grid.pixel_mut(Coordinate::new(x, y)).unwrap().set(Vector1::new(if x % 2 == 1 && y % 2 == 1 { 1 } else { 2 }));
+            }
+        }
+
+        let mipmap = Mipmap::new(grid, MipmapFilter::Box).unwrap();
+
+        assert_eq!(mipmap.level(1).unwrap().pixel_ref(Coordinate::new(1, 1)).unwrap().get().x, 2);
+        assert_eq!(mipmap.level(2).unwrap().pixel_ref(Coordinate::new(0, 0)).unwrap().get().x, 2);
+
+        // A single bright texel rings at full intensity, which must saturate rather than wrap around
+        let mut spike = RenderBuffer::<ColorAttachment<Vector1<u8>>>::with_dimensions(Dimensions::new(8, 8));
+
+        spike.pixel_mut(Coordinate::new(3, 3)).unwrap().set(Vector1::new(255));
+
+        let mipmap = Mipmap::new(spike, MipmapFilter::Kaiser { width: 3.0, alpha: 4.0 }).unwrap();
+
+        assert!(mipmap.level(1).unwrap().pixel_ref(Coordinate::new(1, 1)).unwrap().get().x > 0);
+        assert_eq!(mipmap.level(1).unwrap().pixel_ref(Coordinate::new(0, 1)).unwrap().get().x, 0);
+    }
+
+    #[test]
+    fn test_mipmap_sample_lod() {
+        let mipmap = Mipmap::new(grid(), MipmapFilter::Box).unwrap();
+
+        let coord = Vector2::new(0.25f32, 0.5);
+
+        let sample = |lod: f32, filter: Filter| mipmap.sample_lod(coord, lod, filter, Edge::Clamp).unwrap().x;
+
+        assert_eq!(sample(0.0, Filter::NearestMipmapNearest), 4.0);
+        assert_eq!(sample(1.0, Filter::NearestMipmapNearest), 2.0);
+        assert_eq!(sample(10.0, Filter::NearestMipmapNearest), 4.0);
+
+        // Non-mipmapped filters always use the base level
+        assert_eq!(sample(1.0, Filter::Nearest), 4.0);
+
+        assert_eq!(sample(0.5, Filter::NearestMipmapLinear), 3.0);
+        assert_eq!(sample(0.25, Filter::NearestMipmapLinear), 3.5);
+        assert_eq!(sample(-1.0, Filter::NearestMipmapLinear), 4.0);
+    }
+
+    #[test]
+    fn test_mipmap_sample_grad() {
+        let mipmap = Mipmap::new(grid(), MipmapFilter::Box).unwrap();
+
+        // One texel per pixel
+        assert_eq!(mipmap.lod(Vector2::new(0.25f32, 0.0), Vector2::new(0.0, 0.5)), 0.0);
+        // Two texels per pixel
+        assert_eq!(mipmap.lod(Vector2::new(0.5f32, 0.0), Vector2::new(0.0, 0.5)), 1.0);
+
+        let coord = Vector2::new(0.25f32, 0.5);
+
+        let sample = mipmap.sample_grad(coord, Vector2::new(0.5, 0.0), Vector2::new(0.0, 0.5),
+                                        Filter::Trilinear, Edge::Clamp).unwrap();
+
+        assert_eq!(sample.x, 2.0);
+    }
+
+    #[test]
+    fn test_mipmap_sample_anisotropic() {
+        let mipmap = Mipmap::new(grid(), MipmapFilter::Box).unwrap();
+
+        let coord = Vector2::new(0.5f32, 0.5);
+
+        // Footprint four texels wide and one texel tall, so each probe stays on the base level
+        let sample = |max_anisotropy: u32| {
+            mipmap.sample_anisotropic(coord, Vector2::new(1.0, 0.0), Vector2::new(0.0, 0.5), max_anisotropy,
+                                      Filter::NearestMipmapNearest, Edge::Clamp).unwrap().x
+        };
+
+        // Probes at each texel of the bottom row
+        assert_eq!(sample(4), 5.0);
+        // Two probes on the second level
+        assert_eq!(sample(2), 4.0);
+    }
+}
@@ -0,0 +1,518 @@
+//! Texture handling
+use nalgebra::{Vector1, Vector2, Scalar};
+use nalgebra::coordinates::XY;
+
+use num_traits::{NumCast, ToPrimitive};
+
+use ::error::{RenderResult, RenderError};
+
+use ::numeric::FloatScalar;
+use ::color::Color;
+use ::color::blend::Blend;
+use ::interpolate::Interpolate;
+use ::pixels::{PixelBuffer, PixelRead, PixelWrite};
+use ::geometry::Coordinate;
+use ::attachments::{Depth, DepthTest};
+
+pub mod mipmap;
+pub mod anisotropic;
+pub mod cubemap;
+pub mod array;
+pub mod volume;
+pub mod compressed;
+pub mod slice;
+pub mod shadow;
+
+pub use self::mipmap::{Mipmap, MipmapFilter};
+pub use self::anisotropic::Footprint;
+pub use self::cubemap::{CubeMap, CubeFace};
+pub use self::array::TextureArray;
+pub use self::volume::VolumeTexture;
+pub use self::compressed::{CompressedTexture, BlockFormat};
+pub use self::slice::{SliceTexture, SliceTextureMut};
+pub use self::shadow::ShadowFilter;
+
+pub type TextureColor<T> = <T as PixelBuffer>::Color;
+
+/// A more traditional texture sampling method reminiscent of OpenGL.
+pub fn texture<T: TextureRead, N: FloatScalar>(t: &T, coord: Vector2<N>,
+                                               filter: Filter,
+                                               edge: Edge<TextureColor<T>>) -> RenderResult<TextureColor<T>>
+    where TextureColor<T>: Interpolate {
+    t.sample(coord, filter, edge)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    /// Use the texel nearest to the coordinate. Equivalent to `GL_NEAREST`
+    Nearest,
+    /// Interpolate the four texels nearest to the coordinate. Equivalent to `GL_LINEAR`
+    Bilinear,
+    /// Use the texel nearest to the coordinate on the nearest mipmap level. Equivalent to `GL_NEAREST_MIPMAP_NEAREST`
+    NearestMipmapNearest,
+    /// Bilinear filtering on the nearest mipmap level. Equivalent to `GL_LINEAR_MIPMAP_NEAREST`
+    BilinearMipmapNearest,
+    /// Use the nearest texel on the two nearest mipmap levels and interpolate between them. Equivalent to `GL_NEAREST_MIPMAP_LINEAR`
+    NearestMipmapLinear,
+    /// Bilinear filtering on the two nearest mipmap levels and interpolate between them. Equivalent to `GL_LINEAR_MIPMAP_LINEAR`
+    Trilinear,
+}
+
+impl Filter {
+    /// Returns the filter used to sample texels within a single mipmap level, which is either `Nearest` or `Bilinear`.
+    #[inline]
+    pub fn level_filter(self) -> Filter {
+        match self {
+            Filter::Nearest | Filter::NearestMipmapNearest | Filter::NearestMipmapLinear => Filter::Nearest,
+            Filter::Bilinear | Filter::BilinearMipmapNearest | Filter::Trilinear => Filter::Bilinear,
+        }
+    }
+
+    /// Returns true if the filter uses mipmap levels at all.
+    #[inline]
+    pub fn is_mipmapped(self) -> bool {
+        match self {
+            Filter::Nearest | Filter::Bilinear => false,
+            _ => true,
+        }
+    }
+
+    /// Returns true if the filter interpolates between the two nearest mipmap levels.
+    #[inline]
+    pub fn interpolates_levels(self) -> bool {
+        match self {
+            Filter::NearestMipmapLinear | Filter::Trilinear => true,
+            _ => false,
+        }
+    }
+}
+
+impl Default for Filter {
+    fn default() -> Filter {
+        Filter::Nearest
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Edge<C: Color> {
+    /// Equivalent to `GL_CLAMP_TO_EDGE`
+    Clamp,
+    /// Equivalent to `GL_REPEAT`
+    Wrap,
+    /// Equivalent to `GL_CLAMP_TO_BORDER`
+    Border(C)
+}
+
+impl<C: Color> Default for Edge<C> {
+    fn default() -> Edge<C> { Edge::Clamp }
+}
+
+impl<C: Color> Edge<C> {
+    /// Resolves a possibly out-of-bounds texel index along a single axis of the given size.
+    ///
+    /// Returns `None` if the texel lies outside of the texture and the border color should be used instead.
+    #[inline]
+    pub fn texel(&self, i: i64, size: u32) -> Option<u32> {
+        let size = size as i64;
+
+        match *self {
+            Edge::Clamp => Some(if i < 0 { 0 } else if i >= size { size - 1 } else { i } as u32),
+            Edge::Wrap => Some((((i % size) + size) % size) as u32),
+            Edge::Border(_) => if 0 <= i && i < size { Some(i as u32) } else { None },
+        }
+    }
+
+    /// Maps a normalized texture coordinate into the range expected for this edge behavior.
+    ///
+    /// Wrapped coordinates are reduced to `[0, 1)` so they can safely be scaled to texel space.
+    #[inline]
+    fn normalize<N: FloatScalar>(&self, t: N) -> N {
+        match *self {
+            Edge::Wrap => t - t.floor(),
+            _ => t,
+        }
+    }
+}
+
+/// Converts a texel-space coordinate into the index of the texel it lies within.
+///
+/// Anything beyond the edges of the texture behaves the same regardless of distance,
+/// so the value is limited to just outside of the texture to avoid overflow.
+#[inline]
+fn texel_floor<N: FloatScalar>(t: N, size: u32) -> i64 {
+    let limit = N::from(size).unwrap() + N::one();
+
+    <i64 as NumCast>::from(t.floor().max(-limit).min(limit)).unwrap()
+}
+
+pub trait Texture: PixelBuffer {}
+
+pub trait TextureRead: Texture + PixelRead {
+    /// Samples a pixel from a floating-point coordinate, applying the selected `Filter` and `Edge` behavior.
+    ///
+    /// Coordinates are normalized, so `(0, 0)` is the top left corner of the first texel
+    /// and `(1, 1)` is the bottom right corner of the last texel.
+    ///
+    /// Textures without mipmap levels only use the `Filter::level_filter` of mipmapped filters.
+    ///
+    /// Throws `RenderError::InvalidPixelCoordinate` if the coordinate is not finite,
+    /// or if the texture is empty and there is no border color to fall back on.
+    fn sample<N: FloatScalar>(&self, coord: Vector2<N>, filter: Filter, edge: Edge<TextureColor<Self>>) -> RenderResult<TextureColor<Self>>
+        where TextureColor<Self>: Interpolate {
+        let dimensions = self.dimensions();
+
+        let XY { x, y } = *coord;
+
+        if !(x.is_finite() && y.is_finite()) {
+            throw!(RenderError::InvalidPixelCoordinate);
+        }
+
+        let border = match edge {
+            Edge::Border(color) => color,
+            _ => Color::empty(),
+        };
+
+        if dimensions.width == 0 || dimensions.height == 0 {
+            if let Edge::Border(_) = edge {
+                return Ok(border);
+            }
+
+            throw!(RenderError::InvalidPixelCoordinate);
+        }
+
+        let fetch = |x: i64, y: i64| -> RenderResult<TextureColor<Self>> {
+            match (edge.texel(x, dimensions.width), edge.texel(y, dimensions.height)) {
+                (Some(x), Some(y)) => Ok(self.pixel_ref(Coordinate::new(x, y))?.get()),
+                _ => Ok(border),
+            }
+        };
+
+        // Scale to texel-space
+        let x = edge.normalize(x) * N::from(dimensions.width).unwrap();
+        let y = edge.normalize(y) * N::from(dimensions.height).unwrap();
+
+        match filter.level_filter() {
+            Filter::Nearest => {
+                fetch(texel_floor(x, dimensions.width), texel_floor(y, dimensions.height))
+            }
+            Filter::Bilinear => {
+                // Texel centers are offset by half a texel
+                let half = N::from(0.5).unwrap();
+
+                let (x, y) = (x - half, y - half);
+
+                let (x0, y0) = (texel_floor(x, dimensions.width), texel_floor(y, dimensions.height));
+
+                let (fx, fy) = (x - x.floor(), y - y.floor());
+
+                let top = Interpolate::linear_interpolate(fx, &fetch(x0, y0)?, &fetch(x0 + 1, y0)?);
+                let bottom = Interpolate::linear_interpolate(fx, &fetch(x0, y0 + 1)?, &fetch(x0 + 1, y0 + 1)?);
+
+                Ok(Interpolate::linear_interpolate(fy, &top, &bottom))
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Samples the texture with anisotropic filtering, taking up to `max_anisotropy` probes
+    /// along the major axis of the pixel footprint given by the derivatives of the texture coordinate
+    /// along the screen-space x and y axes.
+    ///
+    /// Textures without mipmap levels cannot reduce the detail of each probe,
+    /// so see `Mipmap::sample_anisotropic` for the complete effect.
+    fn sample_anisotropic<N: FloatScalar>(&self, coord: Vector2<N>, dx: Vector2<N>, dy: Vector2<N>, max_anisotropy: u32,
+                                          filter: Filter, edge: Edge<TextureColor<Self>>) -> RenderResult<TextureColor<Self>>
+        where TextureColor<Self>: Interpolate {
+        let footprint = Footprint::new(self.dimensions(), dx, dy, max_anisotropy);
+
+        footprint.sample(coord, |coord, _| self.sample(coord, filter, edge))
+    }
+
+    /// Samples a single channel depth texture, such as a shadow map, comparing each texel against the `reference` depth with the given test.
+    ///
+    /// Returns the fraction of texels passing the test, weighted by the selected `ShadowFilter` kernel,
+    /// so zero is fully in shadow and one is fully lit.
+    ///
+    /// Throws `RenderError::InvalidPixelCoordinate` if the coordinate is not finite,
+    /// or if the texture is empty and there is no border depth to fall back on.
+    fn sample_compare<N: FloatScalar, D>(&self, coord: Vector2<N>, reference: D, test: DepthTest,
+                                         filter: ShadowFilter, edge: Edge<Vector1<D>>) -> RenderResult<N>
+        where Self: PixelBuffer<Color = Vector1<D>>, D: Depth + Scalar + ToPrimitive, Vector1<D>: Color {
+        shadow::sample_compare(self, coord, reference, test, filter, edge)
+    }
+}
+
+pub trait TextureWrite: Texture + PixelWrite {
+    /// "Unsamples", or writes, to a floating-point coordinate, applying the selected `Filter` and `Edge` behavior.
+    ///
+    /// This allows writing to multiple pixels based on fractional coordinates. With `Filter::Nearest`, the color is written
+    /// to the single texel the coordinate lies within. With `Filter::Bilinear`, the color is splatted onto the four nearest texels,
+    /// each scaled by the same weight the texel would have when sampled at that coordinate.
+    /// Taps that land on the same texel, such as clamped taps past an edge, are combined into a single write.
+    ///
+    /// The scaled color is blended over each existing texel with the given `Blend`, so an additive blend function can be used
+    /// to accumulate values such as light maps or histograms. Texels outside of an `Edge::Border` texture are discarded.
+    ///
+    /// Throws `RenderError::InvalidPixelCoordinate` if the coordinate is not finite,
+    /// or if the texture is empty and does not have a border.
+    fn unsample<N: FloatScalar, B>(&mut self, coord: Vector2<N>, color: TextureColor<Self>, filter: Filter,
+                                   edge: Edge<TextureColor<Self>>, blend: B) -> RenderResult<()>
+        where TextureColor<Self>: Interpolate, B: Blend<TextureColor<Self>> {
+        let dimensions = self.dimensions();
+
+        let XY { x, y } = *coord;
+
+        if !(x.is_finite() && y.is_finite()) {
+            throw!(RenderError::InvalidPixelCoordinate);
+        }
+
+        if dimensions.width == 0 || dimensions.height == 0 {
+            if let Edge::Border(_) = edge {
+                return Ok(());
+            }
+
+            throw!(RenderError::InvalidPixelCoordinate);
+        }
+
+        let texel = |x: i64, y: i64| -> Option<(u32, u32)> {
+            match (edge.texel(x, dimensions.width), edge.texel(y, dimensions.height)) {
+                (Some(x), Some(y)) => Some((x, y)),
+                _ => None,
+            }
+        };
+
+        let mut splat = |texel: Option<(u32, u32)>, weight: N| -> RenderResult<()> {
+            if weight > N::zero() {
+                if let Some((x, y)) = texel {
+                    let mut pixel = self.pixel_mut(Coordinate::new(x, y))?;
+
+                    let existing = pixel.get();
+
+                    pixel.set(blend.blend(Interpolate::linear_interpolate(weight, &Color::empty(), &color), existing));
+                }
+            }
+
+            Ok(())
+        };
+
+        // Scale to texel-space
+        let x = edge.normalize(x) * N::from(dimensions.width).unwrap();
+        let y = edge.normalize(y) * N::from(dimensions.height).unwrap();
+
+        match filter.level_filter() {
+            Filter::Nearest => {
+                splat(texel(texel_floor(x, dimensions.width), texel_floor(y, dimensions.height)), N::one())
+            }
+            Filter::Bilinear => {
+                // Texel centers are offset by half a texel
+                let half = N::from(0.5).unwrap();
+
+                let (x, y) = (x - half, y - half);
+
+                let (x0, y0) = (texel_floor(x, dimensions.width), texel_floor(y, dimensions.height));
+
+                let (fx, fy) = (x - x.floor(), y - y.floor());
+
+                let (gx, gy) = (N::one() - fx, N::one() - fy);
+
+                let mut taps = [
+                    (texel(x0, y0), gx * gy),
+                    (texel(x0 + 1, y0), fx * gy),
+                    (texel(x0, y0 + 1), gx * fy),
+                    (texel(x0 + 1, y0 + 1), fx * fy),
+                ];
+
+                // Clamped edges and tiny textures can map several taps to the same texel,
+                // which must be blended once with their combined weight rather than once per tap
+                for i in 1..taps.len() {
+                    if let Some(j) = (0..i).find(|&j| taps[j].0 == taps[i].0) {
+                        taps[j].1 = taps[j].1 + taps[i].1;
+                        taps[i].1 = N::zero();
+                    }
+                }
+
+                for &(texel, weight) in taps.iter() {
+                    splat(texel, weight)?;
+                }
+
+                Ok(())
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<T> Texture for T where T: PixelBuffer {}
+
+impl<T> TextureRead for T where T: Texture + PixelRead {}
+
+impl<T> TextureWrite for T where T: Texture + PixelWrite {}
+
+#[cfg(test)]
+mod test {
+    use nalgebra::{Vector1, Vector2};
+
+    use ::geometry::{Dimensions, Coordinate};
+    use ::pixels::{PixelBuffer, PixelWrite};
+    use ::framebuffer::RenderBuffer;
+    use ::attachments::predefined::ColorAttachment;
+
+    use super::*;
+
+    type Grid = RenderBuffer<ColorAttachment<Vector1<f32>>>;
+
+    /// 2x2 grid of texels with the values:
+    ///
+    /// ```text
+    /// 0 1
+    /// 2 3
+    /// ```
+    fn grid() -> Grid {
+        let mut grid = Grid::with_dimensions(Dimensions::new(2, 2));
+
+        for i in 0..4 {
+            grid.pixel_mut(Coordinate::new(i % 2, i / 2)).unwrap().set(Vector1::new(i as f32));
+        }
+
+        grid
+    }
+
+    fn sample(grid: &Grid, x: f32, y: f32, filter: Filter, edge: Edge<Vector1<f32>>) -> f32 {
+        grid.sample(Vector2::new(x, y), filter, edge).unwrap().x
+    }
+
+    #[test]
+    fn test_sample_nearest() {
+        let grid = grid();
+
+        assert_eq!(sample(&grid, 0.25, 0.25, Filter::Nearest, Edge::Clamp), 0.0);
+        assert_eq!(sample(&grid, 0.75, 0.25, Filter::Nearest, Edge::Clamp), 1.0);
+        assert_eq!(sample(&grid, 0.25, 0.75, Filter::Nearest, Edge::Clamp), 2.0);
+        assert_eq!(sample(&grid, 0.99, 0.99, Filter::Nearest, Edge::Clamp), 3.0);
+    }
+
+    #[test]
+    fn test_sample_bilinear() {
+        let grid = grid();
+
+        // Texel centers return the exact texel
+        assert_eq!(sample(&grid, 0.25, 0.25, Filter::Bilinear, Edge::Clamp), 0.0);
+        assert_eq!(sample(&grid, 0.75, 0.75, Filter::Bilinear, Edge::Clamp), 3.0);
+
+        // Halfway between texel centers
+        assert_eq!(sample(&grid, 0.5, 0.25, Filter::Bilinear, Edge::Clamp), 0.5);
+        assert_eq!(sample(&grid, 0.25, 0.5, Filter::Bilinear, Edge::Clamp), 1.0);
+        assert_eq!(sample(&grid, 0.5, 0.5, Filter::Bilinear, Edge::Clamp), 1.5);
+    }
+
+    #[test]
+    fn test_sample_edges() {
+        let grid = grid();
+
+        let border = Edge::Border(Vector1::new(10.0));
+
+        // Clamp repeats the edge texels
+        assert_eq!(sample(&grid, -3.0, 0.25, Filter::Nearest, Edge::Clamp), 0.0);
+        assert_eq!(sample(&grid, 0.0, 0.25, Filter::Bilinear, Edge::Clamp), 0.0);
+
+        // Wrap repeats the whole texture
+        assert_eq!(sample(&grid, 1.75, 2.25, Filter::Nearest, Edge::Wrap), 1.0);
+        assert_eq!(sample(&grid, -0.25, 0.25, Filter::Nearest, Edge::Wrap), 1.0);
+        assert_eq!(sample(&grid, 0.0, 0.25, Filter::Bilinear, Edge::Wrap), 0.5);
+
+        // Border blends into the border color
+        assert_eq!(sample(&grid, 1.5, 0.25, Filter::Nearest, border), 10.0);
+        assert_eq!(sample(&grid, 0.0, 0.25, Filter::Bilinear, border), 5.0);
+    }
+
+    #[test]
+    fn test_sample_partial() {
+        let mut grid = Grid::with_dimensions(Dimensions::new(4, 4));
+
+        grid.pixel_mut(Coordinate::new(2, 1)).unwrap().set(Vector1::new(1.0));
+
+        let partial = grid.partial_ref(Coordinate::new(2, 1), Coordinate::new(4, 3)).unwrap();
+
+        assert_eq!(partial.sample(Vector2::new(0.0, 0.0), Filter::Nearest, Edge::Clamp).unwrap().x, 1.0);
+        assert_eq!(partial.sample(Vector2::new(0.75, 0.0), Filter::Nearest, Edge::Clamp).unwrap().x, 0.0);
+    }
+
+    #[test]
+    fn test_sample_invalid() {
+        let grid = grid();
+
+        assert!(grid.sample(Vector2::new(::std::f32::NAN, 0.0), Filter::Nearest, Edge::Clamp).is_err());
+        assert!(Grid::new().sample(Vector2::new(0.5, 0.5), Filter::Bilinear, Edge::Clamp).is_err());
+    }
+
+    #[test]
+    fn test_sample_anisotropic() {
+        let grid = grid();
+
+        let sample = |max_anisotropy: u32| {
+            grid.sample_anisotropic(Vector2::new(0.5f32, 0.25), Vector2::new(1.0, 0.0), Vector2::new(0.0, 0.25),
+                                    max_anisotropy, Filter::Nearest, Edge::Clamp).unwrap().x
+        };
+
+        assert_eq!(sample(1), 1.0);
+        assert_eq!(sample(2), 0.5);
+    }
+
+    #[test]
+    fn test_unsample() {
+        use ::color::blend::GenericBlend;
+
+        let add = GenericBlend::new(|a: Vector1<f32>, b: Vector1<f32>| a + b);
+
+        let mut grid = Grid::with_dimensions(Dimensions::new(2, 2));
+
+        let get = |grid: &Grid, x, y| grid.pixel_ref(Coordinate::new(x, y)).unwrap().get().x;
+
+        grid.unsample(Vector2::new(0.75f32, 0.25), Vector1::new(1.0), Filter::Nearest, Edge::Clamp, &add).unwrap();
+
+        assert_eq!(get(&grid, 1, 0), 1.0);
+
+        // Splat evenly between all four texels
+        grid.unsample(Vector2::new(0.5f32, 0.5), Vector1::new(4.0), Filter::Bilinear, Edge::Clamp, &add).unwrap();
+
+        assert_eq!(get(&grid, 0, 0), 1.0);
+        assert_eq!(get(&grid, 1, 0), 2.0);
+        assert_eq!(get(&grid, 0, 1), 1.0);
+        assert_eq!(get(&grid, 1, 1), 1.0);
+
+        // Half of the color falls outside the border and is lost
+        grid.unsample(Vector2::new(0.0f32, 0.25), Vector1::new(2.0), Filter::Bilinear, Edge::Border(Vector1::new(0.0)), &add).unwrap();
+
+        assert_eq!(get(&grid, 0, 0), 2.0);
+
+        // Wrapped around to the other side instead
+        grid.unsample(Vector2::new(0.0f32, 0.25), Vector1::new(2.0), Filter::Bilinear, Edge::Wrap, &add).unwrap();
+
+        assert_eq!(get(&grid, 0, 0), 3.0);
+        assert_eq!(get(&grid, 1, 0), 3.0);
+    }
+
+    #[test]
+    fn test_unsample_edge() {
+        use ::color::blend::GenericBlend;
+
+        // Non-additive, so blending a texel once per tap would give a different result
+        let max = GenericBlend::new(|a: Vector1<f32>, b: Vector1<f32>| Vector1::new(a.x.max(b.x)));
+
+        let mut grid = Grid::with_dimensions(Dimensions::new(2, 2));
+
+        let get = |grid: &Grid, x, y| grid.pixel_ref(Coordinate::new(x, y)).unwrap().get().x;
+
+        // Both taps left of the edge clamp onto the same column
+        grid.unsample(Vector2::new(0.0f32, 0.25), Vector1::new(4.0), Filter::Bilinear, Edge::Clamp, &max).unwrap();
+
+        assert_eq!(get(&grid, 0, 0), 4.0);
+        assert_eq!(get(&grid, 1, 0), 0.0);
+
+        // All four taps clamp onto the corner texel
+        grid.unsample(Vector2::new(1.0f32, 1.0), Vector1::new(2.0), Filter::Bilinear, Edge::Clamp, &max).unwrap();
+
+        assert_eq!(get(&grid, 1, 1), 2.0);
+        assert_eq!(get(&grid, 0, 1), 0.0);
+    }
+}
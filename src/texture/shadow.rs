@@ -0,0 +1,263 @@
+//! Depth comparison sampling and percentage-closer filtering for shadow maps
+
+use nalgebra::{Vector1, Vector2, Scalar};
+use nalgebra::coordinates::XY;
+
+use num_traits::ToPrimitive;
+
+use ::error::{RenderResult, RenderError};
+
+use ::numeric::FloatScalar;
+use ::color::Color;
+use ::pixels::PixelRead;
+use ::geometry::Coordinate;
+use ::attachments::{Depth, DepthTest};
+
+use super::{Edge, texel_floor};
+
+/// Poisson disc of sixteen points within the unit circle, used for the `Poisson` and `PCSS` filters
+const POISSON_DISC: [[f64; 2]; 16] = [
+    [-0.94201624, -0.39906216],
+    [0.94558609, -0.76890725],
+    [-0.09418410, -0.92938870],
+    [0.34495938, 0.29387760],
+    [-0.91588581, 0.45771432],
+    [-0.81544232, -0.87912464],
+    [-0.38277543, 0.27676845],
+    [0.97484398, 0.75648379],
+    [0.44323325, -0.97511554],
+    [0.53742981, -0.47373420],
+    [-0.26496911, -0.41893023],
+    [0.79197514, 0.19090188],
+    [-0.24188840, 0.99706507],
+    [-0.81409955, 0.91437590],
+    [0.19984126, 0.78641367],
+    [0.14383161, -0.14100790],
+];
+
+/// Percentage-closer filtering kernels for depth comparison sampling.
+///
+/// All radii are given in texels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShadowFilter {
+    /// Single comparison against the nearest texel
+    Nearest,
+    /// Bilinearly weighted comparisons against the four nearest texels, like most GPUs
+    Bilinear,
+    /// Bilinear comparisons spread over a Poisson disc of the given radius
+    Poisson {
+        radius: f64,
+    },
+    /// Percentage-closer soft shadows.
+    ///
+    /// Blockers are first searched for within `search_radius`, then the radius of the Poisson disc
+    /// is estimated from the average blocker depth and `light_size`, up to `max_radius`.
+    ///
+    /// This assumes depth values are proportional to the distance from the light.
+    PCSS {
+        light_size: f64,
+        search_radius: f64,
+        max_radius: f64,
+    },
+}
+
+impl Default for ShadowFilter {
+    fn default() -> ShadowFilter {
+        ShadowFilter::Bilinear
+    }
+}
+
+/// Implementation of `TextureRead::sample_compare`
+pub ( in ::texture) fn sample_compare<T, D, N>(texture: &T, coord: Vector2<N>, reference: D, test: DepthTest,
+                                               filter: ShadowFilter, edge: Edge<Vector1<D>>) -> RenderResult<N>
+    where T: PixelRead<Color = Vector1<D>>,
+          D: Depth + Scalar + ToPrimitive,
+          N: FloatScalar,
+          Vector1<D>: Color {
+    let dimensions = texture.dimensions();
+
+    let XY { x, y } = *coord;
+
+    if !(x.is_finite() && y.is_finite()) {
+        throw!(RenderError::InvalidPixelCoordinate);
+    }
+
+    if dimensions.width == 0 || dimensions.height == 0 {
+        if let Edge::Border(border) = edge {
+            return Ok(if test.test(border.x, reference) { N::one() } else { N::zero() });
+        }
+
+        throw!(RenderError::InvalidPixelCoordinate);
+    }
+
+    let fetch = |x: i64, y: i64| -> RenderResult<D> {
+        match (edge.texel(x, dimensions.width), edge.texel(y, dimensions.height)) {
+            (Some(x), Some(y)) => Ok(texture.pixel_ref(Coordinate::new(x, y))?.get().x),
+            _ => Ok(match edge {
+                Edge::Border(border) => border.x,
+                _ => Depth::far(),
+            }),
+        }
+    };
+
+    let compare = |x: i64, y: i64| -> RenderResult<N> {
+        Ok(if test.test(fetch(x, y)?, reference) { N::one() } else { N::zero() })
+    };
+
+    // Bilinearly weighted comparisons at a position in texel-space
+    let bilinear = |x: N, y: N| -> RenderResult<N> {
+        let half = N::from(0.5).unwrap();
+
+        let (x, y) = (x - half, y - half);
+
+        let (x0, y0) = (texel_floor(x, dimensions.width), texel_floor(y, dimensions.height));
+
+        let (fx, fy) = (x - x.floor(), y - y.floor());
+
+        let top = compare(x0, y0)? * (N::one() - fx) + compare(x0 + 1, y0)? * fx;
+        let bottom = compare(x0, y0 + 1)? * (N::one() - fx) + compare(x0 + 1, y0 + 1)? * fx;
+
+        Ok(top * (N::one() - fy) + bottom * fy)
+    };
+
+    let disc = |i: usize, radius: N| {
+        (N::from(POISSON_DISC[i][0]).unwrap() * radius, N::from(POISSON_DISC[i][1]).unwrap() * radius)
+    };
+
+    let poisson = |x: N, y: N, radius: N| -> RenderResult<N> {
+        let mut visibility = N::zero();
+
+        for i in 0..POISSON_DISC.len() {
+            let (dx, dy) = disc(i, radius);
+
+            visibility += bilinear(x + dx, y + dy)?;
+        }
+
+        Ok(visibility / N::from(POISSON_DISC.len()).unwrap())
+    };
+
+    // Scale to texel-space
+    let x = edge.normalize(x) * N::from(dimensions.width).unwrap();
+    let y = edge.normalize(y) * N::from(dimensions.height).unwrap();
+
+    match filter {
+        ShadowFilter::Nearest => compare(texel_floor(x, dimensions.width), texel_floor(y, dimensions.height)),
+        ShadowFilter::Bilinear => bilinear(x, y),
+        ShadowFilter::Poisson { radius } => poisson(x, y, N::from(radius).unwrap()),
+        ShadowFilter::PCSS { light_size, search_radius, max_radius } => {
+            let search_radius = N::from(search_radius).unwrap();
+            let max_radius = N::from(max_radius).unwrap();
+
+            let mut blockers = 0;
+            let mut blocker_depth = N::zero();
+
+            for i in 0..POISSON_DISC.len() {
+                let (dx, dy) = disc(i, search_radius);
+
+                let depth = fetch(texel_floor(x + dx, dimensions.width), texel_floor(y + dy, dimensions.height))?;
+
+                if !test.test(depth, reference) {
+                    blockers += 1;
+                    blocker_depth += N::from(depth).unwrap();
+                }
+            }
+
+            if blockers == 0 {
+                return Ok(N::one());
+            }
+
+            let blocker_depth = blocker_depth / N::from(blockers).unwrap();
+            let reference_depth = N::from(reference).unwrap();
+
+            // Penumbra width from similar triangles between the light, blockers and receiver
+            let radius = if blocker_depth == N::zero() { max_radius } else {
+                N::from(light_size).unwrap() * ((reference_depth - blocker_depth) / blocker_depth).abs()
+            };
+
+            poisson(x, y, radius.min(max_radius))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use nalgebra::{Vector1, Vector2};
+
+    use ::geometry::{Dimensions, Coordinate};
+    use ::framebuffer::{Framebuffer, RenderBuffer};
+    use ::attachments::predefined::DepthAttachment;
+    use ::attachments::DepthTest;
+    use ::texture::{TextureRead, Edge};
+
+    use super::*;
+
+    type ShadowMap = RenderBuffer<DepthAttachment<f32>>;
+
+    /// 4x4 shadow map where the left half is covered by an occluder at depth 0.5, and the right half is empty at depth 0
+    fn shadow_map() -> ShadowMap {
+        let mut map = ShadowMap::with_dimensions(Dimensions::new(4, 4));
+
+        for i in 0..16 {
+            let depth = if i % 4 < 2 { 0.5 } else { 0.0 };
+
+            map.attachments_mut(Coordinate::new(i % 4, i / 4)).unwrap().set_depth(depth);
+        }
+
+        map
+    }
+
+    fn sample(map: &ShadowMap, x: f32, filter: ShadowFilter) -> f32 {
+        // Larger depths are closer, so the receiver is lit if it is at least as close as the shadow map
+        map.depth_texture().sample_compare(Vector2::new(x, 0.5), 0.25, DepthTest::GreaterThanEq, filter, Edge::Clamp).unwrap()
+    }
+
+    #[test]
+    fn test_compare_nearest_bilinear() {
+        let map = shadow_map();
+
+        assert_eq!(sample(&map, 0.125, ShadowFilter::Nearest), 0.0);
+        assert_eq!(sample(&map, 0.875, ShadowFilter::Nearest), 1.0);
+
+        // Halfway between the occluded and lit texels at the shadow edge
+        assert_eq!(sample(&map, 0.5, ShadowFilter::Bilinear), 0.5);
+        assert_eq!(sample(&map, 0.125, ShadowFilter::Bilinear), 0.0);
+    }
+
+    #[test]
+    fn test_compare_poisson() {
+        let map = shadow_map();
+
+        let edge = sample(&map, 0.5, ShadowFilter::Poisson { radius: 1.0 });
+
+        assert!(edge > 0.2 && edge < 0.8);
+
+        assert_eq!(sample(&map, 0.0, ShadowFilter::Poisson { radius: 0.25 }), 0.0);
+        assert_eq!(sample(&map, 1.0, ShadowFilter::Poisson { radius: 0.25 }), 1.0);
+    }
+
+    #[test]
+    fn test_compare_pcss() {
+        let map = shadow_map();
+
+        let filter = ShadowFilter::PCSS { light_size: 1.0, search_radius: 1.0, max_radius: 2.0 };
+
+        // No blockers nearby
+        assert_eq!(sample(&map, 1.0, filter), 1.0);
+
+        let edge = sample(&map, 0.5, filter);
+
+        assert!(edge > 0.0 && edge < 1.0);
+    }
+
+    #[test]
+    fn test_compare_border() {
+        let map = shadow_map();
+
+        let texture = map.depth_texture();
+
+        let border = Edge::Border(Vector1::new(1.0));
+
+        assert_eq!(texture.sample_compare(Vector2::new(1.5f32, 0.5), 0.25, DepthTest::GreaterThanEq, ShadowFilter::Nearest, border).unwrap(), 0.0);
+        assert_eq!(texture.sample_compare(Vector2::new(1.5f32, 0.5), 0.25, DepthTest::Always, ShadowFilter::Nearest, border).unwrap(), 1.0);
+    }
+}
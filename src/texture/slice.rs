@@ -0,0 +1,205 @@
+//! Zero-copy textures backed by borrowed slices
+
+use ::error::{RenderResult, RenderError};
+
+use ::color::Color;
+use ::pixels::{PixelBuffer, PixelRead, PixelWrite};
+use ::geometry::{Dimensions, HasDimensions};
+
+/// Computes the minimum slice length required for the given dimensions and row stride
+fn required_len(dimensions: Dimensions, stride: usize) -> RenderResult<usize> {
+    if stride < dimensions.width as usize {
+        throw!(RenderError::InvalidDimensions);
+    }
+
+    Ok(match dimensions.height as usize {
+        0 => 0,
+        height => stride * (height - 1) + dimensions.width as usize,
+    })
+}
+
+/// Translates a pixel index into an index within the slice, accounting for the row stride
+#[inline(always)]
+fn slice_index(index: usize, width: u32, stride: usize) -> usize {
+    let width = width as usize;
+
+    if stride == width { index } else {
+        let x = index % width;
+
+        x + (index - x) / width * stride
+    }
+}
+
+/// Texture backed by a borrowed slice of colors, such as a decoded image or shared memory.
+///
+/// Pixels are stored in row-major order, with an optional row stride for padded rows or sub-images.
+#[derive(Debug, Clone, Copy)]
+pub struct SliceTexture<'a, C: Color + 'a> {
+    slice: &'a [C],
+    dimensions: Dimensions,
+    stride: usize,
+}
+
+/// Mutable texture backed by a borrowed slice of colors.
+///
+/// Pixels are stored in row-major order, with an optional row stride for padded rows or sub-images.
+#[derive(Debug)]
+pub struct SliceTextureMut<'a, C: Color + 'a> {
+    slice: &'a mut [C],
+    dimensions: Dimensions,
+    stride: usize,
+}
+
+impl<'a, C: Color + 'a> SliceTexture<'a, C> {
+    /// Creates a new texture over a slice of tightly packed rows.
+    ///
+    /// Throws `RenderError::InvalidDimensions` if the slice is too short for the given dimensions.
+    pub fn new(slice: &'a [C], dimensions: Dimensions) -> RenderResult<SliceTexture<'a, C>> {
+        SliceTexture::with_stride(slice, dimensions, dimensions.width as usize)
+    }
+
+    /// Creates a new texture over a slice where each row starts `stride` colors after the previous row.
+    ///
+    /// Throws `RenderError::InvalidDimensions` if the stride is less than the width,
+    /// or if the slice is too short for the given dimensions and stride.
+    pub fn with_stride(slice: &'a [C], dimensions: Dimensions, stride: usize) -> RenderResult<SliceTexture<'a, C>> {
+        if slice.len() < required_len(dimensions, stride)? {
+            throw!(RenderError::InvalidDimensions);
+        }
+
+        Ok(SliceTexture { slice, dimensions, stride })
+    }
+
+    /// Returns the number of colors between the start of each row
+    #[inline]
+    pub fn stride(&self) -> usize { self.stride }
+
+    /// Returns the underlying slice
+    #[inline]
+    pub fn as_slice(&self) -> &'a [C] { self.slice }
+}
+
+impl<'a, C: Color + 'a> SliceTextureMut<'a, C> {
+    /// Creates a new mutable texture over a slice of tightly packed rows.
+    ///
+    /// Throws `RenderError::InvalidDimensions` if the slice is too short for the given dimensions.
+    pub fn new(slice: &'a mut [C], dimensions: Dimensions) -> RenderResult<SliceTextureMut<'a, C>> {
+        SliceTextureMut::with_stride(slice, dimensions, dimensions.width as usize)
+    }
+
+    /// Creates a new mutable texture over a slice where each row starts `stride` colors after the previous row.
+    ///
+    /// Throws `RenderError::InvalidDimensions` if the stride is less than the width,
+    /// or if the slice is too short for the given dimensions and stride.
+    pub fn with_stride(slice: &'a mut [C], dimensions: Dimensions, stride: usize) -> RenderResult<SliceTextureMut<'a, C>> {
+        if slice.len() < required_len(dimensions, stride)? {
+            throw!(RenderError::InvalidDimensions);
+        }
+
+        Ok(SliceTextureMut { slice, dimensions, stride })
+    }
+
+    /// Returns the number of colors between the start of each row
+    #[inline]
+    pub fn stride(&self) -> usize { self.stride }
+
+    /// Returns the underlying slice
+    #[inline]
+    pub fn as_slice(&self) -> &[C] { self.slice }
+
+    /// Returns the underlying slice mutably
+    #[inline]
+    pub fn as_mut_slice(&mut self) -> &mut [C] { self.slice }
+
+    /// Downcast the mutable texture into an immutable `SliceTexture`
+    #[inline]
+    pub fn into_ref(self) -> SliceTexture<'a, C> {
+        SliceTexture { slice: self.slice, dimensions: self.dimensions, stride: self.stride }
+    }
+}
+
+impl<'a, C: Color + 'a> HasDimensions for SliceTexture<'a, C> {
+    #[inline]
+    fn dimensions(&self) -> Dimensions { self.dimensions }
+}
+
+impl<'a, C: Color + 'a> HasDimensions for SliceTextureMut<'a, C> {
+    #[inline]
+    fn dimensions(&self) -> Dimensions { self.dimensions }
+}
+
+impl<'a, C: Color + 'a> PixelBuffer for SliceTexture<'a, C> {
+    type Color = C;
+}
+
+impl<'a, C: Color + 'a> PixelBuffer for SliceTextureMut<'a, C> {
+    type Color = C;
+}
+
+impl<'a, C: Color + 'a> PixelRead for SliceTexture<'a, C> {
+    #[inline]
+    unsafe fn get_pixel_unchecked(&self, index: usize) -> C {
+        *self.slice.get_unchecked(slice_index(index, self.dimensions.width, self.stride))
+    }
+}
+
+impl<'a, C: Color + 'a> PixelRead for SliceTextureMut<'a, C> {
+    #[inline]
+    unsafe fn get_pixel_unchecked(&self, index: usize) -> C {
+        *self.slice.get_unchecked(slice_index(index, self.dimensions.width, self.stride))
+    }
+}
+
+impl<'a, C: Color + 'a> PixelWrite for SliceTextureMut<'a, C> {
+    #[inline]
+    unsafe fn set_pixel_unchecked(&mut self, index: usize, color: C) {
+        *self.slice.get_unchecked_mut(slice_index(index, self.dimensions.width, self.stride)) = color;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use nalgebra::{Vector1, Vector2};
+
+    use ::geometry::{Dimensions, Coordinate};
+    use ::pixels::{PixelRead, PixelWrite};
+    use ::texture::{TextureRead, Filter, Edge};
+
+    use super::*;
+
+    #[test]
+    fn test_slice_texture() {
+        let data: Vec<_> = (0..4).map(|i| Vector1::new(i as f32)).collect();
+
+        let texture = SliceTexture::new(&data, Dimensions::new(2, 2)).unwrap();
+
+        assert_eq!(texture.pixel_ref(Coordinate::new(1, 1)).unwrap().get().x, 3.0);
+        assert_eq!(texture.sample(Vector2::new(0.5f32, 0.5), Filter::Bilinear, Edge::Clamp).unwrap().x, 1.5);
+
+        assert!(SliceTexture::new(&data[..3], Dimensions::new(2, 2)).is_err());
+    }
+
+    #[test]
+    fn test_slice_texture_stride() {
+        // 2x2 texture inside of 3 wide rows, where the last row does not need padding
+        let mut data: Vec<_> = (0..5).map(|i| Vector1::new(i as f32)).collect();
+
+        {
+            let mut texture = SliceTextureMut::with_stride(&mut data, Dimensions::new(2, 2), 3).unwrap();
+
+            assert_eq!(texture.pixel_ref(Coordinate::new(0, 1)).unwrap().get().x, 3.0);
+
+            texture.pixel_mut(Coordinate::new(1, 1)).unwrap().set(Vector1::new(-1.0));
+
+            let texture = texture.into_ref();
+
+            assert_eq!(texture.sample(Vector2::new(0.75f32, 0.75), Filter::Nearest, Edge::Clamp).unwrap().x, -1.0);
+        }
+
+        assert_eq!(data[4].x, -1.0);
+        assert_eq!(data[2].x, 2.0);
+
+        assert!(SliceTexture::with_stride(&data, Dimensions::new(2, 2), 1).is_err());
+        assert!(SliceTexture::with_stride(&data, Dimensions::new(2, 2), 4).is_err());
+    }
+}
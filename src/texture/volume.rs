@@ -0,0 +1,197 @@
+//! 3D volume textures
+
+use nalgebra::Vector3;
+use nalgebra::coordinates::XYZ;
+
+use ::error::{RenderResult, RenderError};
+
+use ::numeric::FloatScalar;
+use ::color::Color;
+use ::interpolate::Interpolate;
+use ::pixels::PixelRead;
+use ::geometry::{Dimensions, HasDimensions, Coordinate};
+
+use super::{TextureColor, Filter, Edge, texel_floor};
+
+/// 3D texture composed of a stack of equally sized 2D slices along the z axis.
+///
+/// Useful for volumetric effects and color grading lookup tables.
+pub struct VolumeTexture<T> where T: PixelRead {
+    slices: Vec<T>,
+}
+
+impl<T> HasDimensions for VolumeTexture<T> where T: PixelRead {
+    /// The dimensions of a single slice
+    #[inline]
+    fn dimensions(&self) -> Dimensions { self.slices[0].dimensions() }
+}
+
+impl<T> VolumeTexture<T> where T: PixelRead {
+    /// Creates a new volume texture from its slices, ordered along the z axis.
+    ///
+    /// Throws `RenderError::InvalidDimensions` if there are no slices, or if the slices are not all the same non-zero size.
+    pub fn new(slices: Vec<T>) -> RenderResult<VolumeTexture<T>> {
+        match slices.first() {
+            Some(first) if first.dimensions().width > 0 && first.dimensions().height > 0 &&
+                slices.iter().all(|slice| slice.dimensions() == first.dimensions()) => {}
+            _ => throw!(RenderError::InvalidDimensions),
+        }
+
+        Ok(VolumeTexture { slices })
+    }
+
+    /// Returns the number of slices along the z axis
+    #[inline]
+    pub fn depth(&self) -> u32 { self.slices.len() as u32 }
+
+    /// Returns a reference to the given slice, if it exists
+    #[inline]
+    pub fn slice(&self, z: usize) -> Option<&T> { self.slices.get(z) }
+
+    /// Returns a mutable reference to the given slice, if it exists
+    #[inline]
+    pub fn slice_mut(&mut self, z: usize) -> Option<&mut T> { self.slices.get_mut(z) }
+
+    /// Samples the volume from a normalized floating-point coordinate, applying the selected `Filter`
+    /// and a separate `Edge` behavior for each of the x, y and z axes.
+    ///
+    /// `Filter::Bilinear` interpolates between the eight nearest texels, which is trilinear filtering in 3D.
+    /// Volume textures do not have mipmap levels, so only the `Filter::level_filter` of mipmapped filters is used.
+    ///
+    /// Coordinates outside of a `Border` edge on any axis return that border color.
+    ///
+    /// Throws `RenderError::InvalidPixelCoordinate` if the coordinate is not finite.
+    pub fn sample<N: FloatScalar>(&self, coord: Vector3<N>, filter: Filter, edges: [Edge<TextureColor<T>>; 3]) -> RenderResult<TextureColor<T>>
+        where TextureColor<T>: Interpolate {
+        let XYZ { x, y, z } = *coord;
+
+        if !(x.is_finite() && y.is_finite() && z.is_finite()) {
+            throw!(RenderError::InvalidPixelCoordinate);
+        }
+
+        let Dimensions { width, height } = self.dimensions();
+        let depth = self.depth();
+
+        let fetch = |x: i64, y: i64, z: i64| -> RenderResult<TextureColor<T>> {
+            let texels = [(x, width), (y, height), (z, depth)];
+
+            let mut resolved = [0; 3];
+
+            for (axis, &(i, size)) in texels.iter().enumerate() {
+                match edges[axis].texel(i, size) {
+                    Some(i) => resolved[axis] = i,
+                    None => return Ok(match edges[axis] {
+                        Edge::Border(color) => color,
+                        _ => Color::empty(),
+                    }),
+                }
+            }
+
+            Ok(self.slices[resolved[2] as usize].pixel_ref(Coordinate::new(resolved[0], resolved[1]))?.get())
+        };
+
+        // Scale to texel-space
+        let x = edges[0].normalize(x) * N::from(width).unwrap();
+        let y = edges[1].normalize(y) * N::from(height).unwrap();
+        let z = edges[2].normalize(z) * N::from(depth).unwrap();
+
+        match filter.level_filter() {
+            Filter::Nearest => {
+                fetch(texel_floor(x, width), texel_floor(y, height), texel_floor(z, depth))
+            }
+            Filter::Bilinear => {
+                // Texel centers are offset by half a texel
+                let half = N::from(0.5).unwrap();
+
+                let (x, y, z) = (x - half, y - half, z - half);
+
+                let (x0, y0, z0) = (texel_floor(x, width), texel_floor(y, height), texel_floor(z, depth));
+
+                let (fx, fy, fz) = (x - x.floor(), y - y.floor(), z - z.floor());
+
+                let slice = |z: i64| -> RenderResult<TextureColor<T>> {
+                    let top = Interpolate::linear_interpolate(fx, &fetch(x0, y0, z)?, &fetch(x0 + 1, y0, z)?);
+                    let bottom = Interpolate::linear_interpolate(fx, &fetch(x0, y0 + 1, z)?, &fetch(x0 + 1, y0 + 1, z)?);
+
+                    Ok(Interpolate::linear_interpolate(fy, &top, &bottom))
+                };
+
+                Ok(Interpolate::linear_interpolate(fz, &slice(z0)?, &slice(z0 + 1)?))
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use nalgebra::{Vector1, Vector3};
+
+    use ::geometry::{Dimensions, Coordinate};
+    use ::pixels::PixelWrite;
+    use ::framebuffer::RenderBuffer;
+    use ::attachments::predefined::ColorAttachment;
+    use ::texture::{Filter, Edge};
+
+    use super::*;
+
+    type Slice = RenderBuffer<ColorAttachment<Vector1<f32>>>;
+
+    /// Creates a 2x2x2 volume where each texel is `x + 2y + 4z`
+    fn volume() -> VolumeTexture<Slice> {
+        let slice = |z: u32| {
+            let mut slice = Slice::with_dimensions(Dimensions::new(2, 2));
+
+            for i in 0..4 {
+                slice.pixel_mut(Coordinate::new(i % 2, i / 2)).unwrap().set(Vector1::new((i + 4 * z) as f32));
+            }
+
+            slice
+        };
+
+        VolumeTexture::new(vec![slice(0), slice(1)]).unwrap()
+    }
+
+    #[test]
+    fn test_volume_sample() {
+        let volume = volume();
+
+        let clamp = [Edge::Clamp; 3];
+
+        assert_eq!(volume.sample(Vector3::new(0.75f32, 0.25, 0.75), Filter::Nearest, clamp).unwrap().x, 5.0);
+
+        // Center of the volume is the average of all eight texels
+        assert_eq!(volume.sample(Vector3::new(0.5f32, 0.5, 0.5), Filter::Bilinear, clamp).unwrap().x, 3.5);
+
+        assert_eq!(volume.sample(Vector3::new(0.25f32, 0.25, 0.5), Filter::Trilinear, clamp).unwrap().x, 2.0);
+
+        assert!(volume.sample(Vector3::new(0.5f32, ::std::f32::NAN, 0.5), Filter::Nearest, clamp).is_err());
+    }
+
+    #[test]
+    fn test_volume_edges() {
+        let volume = volume();
+
+        let border = Vector1::new(-1.0);
+
+        // Wrapping along x, but clamping along z
+        let edges = [Edge::Wrap, Edge::Clamp, Edge::Clamp];
+
+        assert_eq!(volume.sample(Vector3::new(1.25f32, 0.25, 1.5), Filter::Nearest, edges).unwrap().x, 4.0);
+        assert_eq!(volume.sample(Vector3::new(0.0f32, 0.25, 0.75), Filter::Bilinear, edges).unwrap().x, 4.5);
+
+        // Only the z axis has a border
+        let edges = [Edge::Clamp, Edge::Clamp, Edge::Border(border)];
+
+        assert_eq!(volume.sample(Vector3::new(2.0f32, 0.25, 0.75), Filter::Nearest, edges).unwrap().x, 5.0);
+        assert_eq!(volume.sample(Vector3::new(0.25f32, 0.25, 1.5), Filter::Nearest, edges).unwrap().x, -1.0);
+        assert_eq!(volume.sample(Vector3::new(0.25f32, 0.25, 1.0), Filter::Bilinear, edges).unwrap().x, 1.5);
+    }
+
+    #[test]
+    fn test_volume_dimensions() {
+        assert!(VolumeTexture::<Slice>::new(Vec::new()).is_err());
+        assert!(VolumeTexture::new(vec![Slice::with_dimensions(Dimensions::new(2, 2)),
+                                        Slice::with_dimensions(Dimensions::new(2, 1))]).is_err());
+    }
+}
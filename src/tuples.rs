@@ -31,6 +31,10 @@ macro_rules! tuple_impls {
                 fn get_alpha(&self) -> Self::Alpha {
                     ($(<$T as $crate::color::Color>::get_alpha(&self.$idx),)+)
                 }
+
+                fn mask(self, previous: Self, mask: $crate::color::ColorMask) -> Self {
+                    ($(<$T as $crate::color::Color>::mask(self.$idx, previous.$idx, mask),)+)
+                }
             }
 
             impl<$($T),+> $crate::interpolate::Interpolate for ($($T,)+) where $($T: $crate::interpolate::Interpolate,)+ {
@@ -43,6 +47,19 @@ macro_rules! tuple_impls {
                 fn linear_interpolate<N: Float>(t: N, x1: &Self, x2: &Self) -> Self {
                     ($($crate::interpolate::Interpolate::linear_interpolate(t, &x1.$idx, &x2.$idx),)+)
                 }
+
+                fn fragment_barycentric_interpolate<N: Float>(perspective: (N, N, N), screen: (N, N, N), x1: &Self, x2: &Self, x3: &Self) -> Self {
+                    ($($crate::interpolate::Interpolate::fragment_barycentric_interpolate(perspective, screen,
+                                                                             &x1.$idx, &x2.$idx, &x3.$idx),)+)
+                }
+
+                fn fragment_linear_interpolate<N: Float>(perspective: N, screen: N, x1: &Self, x2: &Self) -> Self {
+                    ($($crate::interpolate::Interpolate::fragment_linear_interpolate(perspective, screen, &x1.$idx, &x2.$idx),)+)
+                }
+
+                fn weighted_sum<'a, N: Float, It>(values: It) -> Self where Self: 'a, It: Iterator<Item = (N, &'a Self)> + Clone {
+                    ($($crate::interpolate::Interpolate::weighted_sum(values.clone().map(|(weight, value)| (weight, &value.$idx))),)+)
+                }
             }
         )+
     }
@@ -0,0 +1,86 @@
+extern crate nalgebra;
+#[macro_use]
+extern crate softrender;
+
+use std::sync::Arc;
+
+use nalgebra::{Point3, Vector4};
+
+use softrender::prelude::*;
+use softrender::attachments::predefined::ColorDepthAttachments;
+
+declare_uniforms! {
+    /// Uniforms with every interpolation qualifier
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct Uniforms {
+        /// Perspective-correct
+        pub value: f32,
+        pub flat id: u32,
+        pub noperspective screen: f32,
+    }
+}
+
+fn uniforms(value: f32, id: u32) -> Uniforms {
+    Uniforms { value, id, screen: value }
+}
+
+#[test]
+fn test_interpolation_qualifiers() {
+    let (a, b, c) = (uniforms(0.0, 1), uniforms(1.0, 2), uniforms(2.0, 3));
+
+    assert_eq!(Interpolate::barycentric_interpolate(0.25, &a, 0.25, &b, 0.5, &c), Uniforms { value: 1.25, id: 1, screen: 1.25 });
+    assert_eq!(Interpolate::linear_interpolate(0.5, &b, &c), Uniforms { value: 1.5, id: 2, screen: 1.5 });
+
+    // Only noperspective members use the screen-space coordinates
+    assert_eq!(Interpolate::fragment_barycentric_interpolate((0.0, 0.0, 1.0), (0.0, 1.0, 0.0), &a, &b, &c),
+               Uniforms { value: 2.0, id: 1, screen: 1.0 });
+
+    assert_eq!(Interpolate::fragment_linear_interpolate(0.0, 1.0, &b, &c), Uniforms { value: 1.0, id: 2, screen: 2.0 });
+}
+
+#[test]
+fn test_rasterized_qualifiers() {
+    const SIZE: u32 = 32;
+
+    let dimensions = Dimensions::new(SIZE, SIZE);
+
+    let vertices = vec![(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)].into_iter().enumerate().map(|(i, (x, y))| SimpleVertex {
+        position: Point3::new(x, y, 0.0),
+        data: uniforms((x + 1.0) / 2.0, i as u32 + 1),
+    }).collect();
+
+    let buffer = RenderBuffer::<ColorDepthAttachments<Vector4<f32>, f32>>::with_dimensions(dimensions);
+
+    let mut pipeline: Pipeline<_, _> = Pipeline::from_framebuffer(buffer, ());
+
+    pipeline.render_mesh(Triangle, Arc::new(Mesh { vertices, indices: vec![0, 1, 2, 2, 1, 3] }), None)
+        .run_to_fragment(Viewport::new(dimensions, Coordinate::new(0, 0), 0.0, 1.0), |vertex, _| {
+            // The right side of the quad is further away, without changing its position on screen
+            let w = if vertex.position.x < 0.0 { 1.0 } else { 4.0 };
+
+            ClipVertex::new(vertex.position.to_homogeneous() * w, vertex.data)
+        })
+        .run(|vertex, _| {
+            let Uniforms { value, id, screen } = vertex.uniforms;
+
+            Fragment::Color(Vector4::new(value, screen, id as f32, 1.0))
+        });
+
+    let color = |x, y| pipeline.framebuffer().pixel_ref(Coordinate::new(x, y)).unwrap().get();
+
+    for x in 0..SIZE {
+        let pixel = color(x, SIZE / 2);
+
+        let (value, screen) = (pixel.x, pixel.y);
+
+        // Linear across the screen, while perspective-correct interpolation is biased towards the nearer side
+        assert!((screen - (x as f32 + 0.5) / SIZE as f32).abs() < 1e-3);
+        assert!(value <= screen);
+    }
+
+    assert!(color(SIZE / 2, SIZE / 2).x < 0.3);
+
+    // Taken from the first vertex of each triangle
+    assert_eq!(color(0, SIZE - 1).z, 1.0);
+    assert_eq!(color(SIZE - 1, 0).z, 3.0);
+}